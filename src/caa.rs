@@ -0,0 +1,306 @@
+//! Decide whether a certification authority may issue certificates for a domain name.
+//!
+//! The owner of a domain name publishes CAA records listing the certification authorities (CAs)
+//! that may issue certificates for it. The "issue" property authorizes a CA to issue
+//! certificates, the "issuewild" property to issue wildcard certificates. Names without CAA
+//! records inherit the records of their closest ancestor that has them. See [`RFC 8659`].
+//!
+//! ```
+//! use dns::caa::Policy;
+//! use dns::rdata::RData;
+//! use dns::resource_record::{Class, ResourceRecord, Type};
+//! use dns::DomainName;
+//!
+//! // Look up the CAA records of a name, here from a static zone.
+//! let lookup = |name: &DomainName| -> Result<Vec<ResourceRecord>, std::io::Error> {
+//!     if *name != "example.com".parse().unwrap() {
+//!         return Ok(vec![]);
+//!     }
+//!     Ok(vec![ResourceRecord {
+//!         name: name.clone(),
+//!         class: Class::IN,
+//!         ttl: 3600,
+//!         rdata: RData::from_presentation(&Type::CAA, r#"0 issue "ca.example.net""#).unwrap(),
+//!     }])
+//! };
+//!
+//! let policy = Policy::find(&"www.example.com".parse().unwrap(), lookup).unwrap();
+//! assert_eq!(policy.owner(), Some(&"example.com".parse().unwrap()));
+//! assert!(policy.may_issue("ca.example.net"));
+//! assert!(!policy.may_issue("ca.example.org"));
+//! ```
+//!
+//! [`RFC 8659`]: https://www.rfc-editor.org/rfc/rfc8659
+use crate::domain_name::DomainName;
+use crate::rdata::RData;
+use crate::resource_record::ResourceRecord;
+
+/// The flag of a CAA record telling CAs not to issue certificates unless they understand the
+/// tag of the record.
+pub const ISSUER_CRITICAL: u8 = 0b1000_0000;
+
+// The tags CAs understand, see section 4 of RFC 8659.
+const KNOWN_TAGS: [&str; 3] = ["issue", "issuewild", "iodef"];
+
+/// The CAA records that apply to a domain name: the relevant RRset of section 3 of RFC 8659.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Policy {
+    owner: Option<DomainName>,
+    properties: Vec<Property>,
+}
+
+// A CAA record, with its tag in lower case.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Property {
+    flags: u8,
+    tag: String,
+    value: Vec<u8>,
+}
+
+impl Policy {
+    /// Find the policy of `name`, looking up CAA records with `lookup`.
+    ///
+    /// The CAA records of `name` apply if it has any, otherwise those of its parent and so on,
+    /// up to but excluding the root. Records of other types returned by `lookup`, like the
+    /// CNAME records leading to the CAA records, are ignored. Fails if a lookup fails, in which
+    /// case a CA must not issue.
+    ///
+    /// For a wildcard certificate, like `*.example.com`, pass the name without the wildcard
+    /// label and use `Policy::may_issue_wildcard()`.
+    pub fn find<F, E>(name: &DomainName, mut lookup: F) -> Result<Self, E>
+    where
+        F: FnMut(&DomainName) -> Result<Vec<ResourceRecord>, E>,
+    {
+        let mut name = name.clone();
+        while !name.is_root() {
+            let properties: Vec<Property> = lookup(&name)?
+                .into_iter()
+                .filter_map(|record| match record.rdata {
+                    RData::CAA { flags, tag, value } => Some(Property {
+                        flags,
+                        tag: tag.to_ascii_lowercase(),
+                        value,
+                    }),
+                    _ => None,
+                })
+                .collect();
+
+            if !properties.is_empty() {
+                return Ok(Self {
+                    owner: Some(name),
+                    properties,
+                });
+            }
+
+            name = name.parent().unwrap_or_default();
+        }
+
+        Ok(Self::default())
+    }
+
+    /// The name the CAA records were found at. `None` if no name has CAA records.
+    pub fn owner(&self) -> Option<&DomainName> {
+        self.owner.as_ref()
+    }
+
+    /// Whether the CA identified by the domain name `issuer`, like "letsencrypt.org", may issue
+    /// certificates for the name.
+    ///
+    /// Any CA may issue if there are no "issue" properties. Otherwise, `issuer` must be listed
+    /// by one of them.
+    pub fn may_issue(&self, issuer: &str) -> bool {
+        self.authorizes("issue", issuer)
+    }
+
+    /// Whether the CA identified by the domain name `issuer` may issue wildcard certificates
+    /// for the name.
+    ///
+    /// "issuewild" properties apply if there are any, "issue" properties otherwise.
+    pub fn may_issue_wildcard(&self, issuer: &str) -> bool {
+        if self
+            .properties
+            .iter()
+            .any(|property| property.tag == "issuewild")
+        {
+            self.authorizes("issuewild", issuer)
+        } else {
+            self.authorizes("issue", issuer)
+        }
+    }
+
+    // Whether the properties with `tag` authorize `issuer`. See section 4.2 and 4.3 of RFC 8659.
+    fn authorizes(&self, tag: &str, issuer: &str) -> bool {
+        // A critical property the CA doesn't understand forbids issuance.
+        let critical_unknown = self.properties.iter().any(|property| {
+            property.flags & ISSUER_CRITICAL != 0 && !KNOWN_TAGS.contains(&property.tag.as_str())
+        });
+        if critical_unknown {
+            return false;
+        }
+
+        let mut properties = self
+            .properties
+            .iter()
+            .filter(|property| property.tag == tag)
+            .peekable();
+        if properties.peek().is_none() {
+            return true;
+        }
+
+        let issuer = issuer.trim_end_matches('.');
+        properties.any(|property| {
+            issuer_of(&property.value).is_some_and(|name| name.eq_ignore_ascii_case(issuer))
+        })
+    }
+}
+
+// The issuer domain name of the value of an "issue" or "issuewild" property. `None` if the value
+// doesn't name an issuer, like ";", or is malformed. Parameters after the name are checked but
+// ignored. See section 4.2 of RFC 8659.
+fn issuer_of(value: &[u8]) -> Option<&str> {
+    let value = std::str::from_utf8(value).ok()?;
+    let (name, parameters) = value.split_once(';').unwrap_or((value, ""));
+
+    let name = name.trim_matches([' ', '\t']);
+    let valid_label = |label: &str| {
+        let bytes = label.as_bytes();
+        !bytes.is_empty()
+            && bytes[0].is_ascii_alphanumeric()
+            && bytes[bytes.len() - 1].is_ascii_alphanumeric()
+            && bytes
+                .iter()
+                .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'-')
+    };
+    if name.is_empty() || !name.split('.').all(valid_label) {
+        return None;
+    }
+
+    // Parameters are `tag=value` pairs separated by semicolons.
+    let valid_parameter = |parameter: &str| match parameter.split_once('=') {
+        Some((tag, value)) => {
+            let tag = tag.trim_matches([' ', '\t']);
+            !tag.is_empty()
+                && tag.bytes().all(|byte| byte.is_ascii_alphanumeric())
+                && value
+                    .trim_matches([' ', '\t'])
+                    .bytes()
+                    .all(|byte| matches!(byte, 0x21..=0x3a | 0x3c..=0x7e))
+        }
+        None => parameter.trim_matches([' ', '\t']).is_empty(),
+    };
+    if !parameters.split(';').all(valid_parameter) {
+        return None;
+    }
+
+    Some(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resource_record::{Class, Type};
+    use std::collections::HashMap;
+
+    // Find the policy of `name` in a zone of CAA records in presentation format.
+    fn find(zone: &[(&str, &str)], name: &str) -> Policy {
+        let mut records: HashMap<DomainName, Vec<ResourceRecord>> = HashMap::new();
+        for (owner, value) in zone {
+            let owner: DomainName = owner.parse().unwrap();
+            records
+                .entry(owner.clone())
+                .or_default()
+                .push(ResourceRecord {
+                    name: owner,
+                    class: Class::IN,
+                    ttl: 3600,
+                    rdata: RData::from_presentation(&Type::CAA, value).unwrap(),
+                });
+        }
+
+        let lookup = |name: &DomainName| -> Result<_, ()> {
+            Ok(records.get(name).cloned().unwrap_or_default())
+        };
+        Policy::find(&name.parse().unwrap(), lookup).unwrap()
+    }
+
+    #[test]
+    fn test_find_climbs_the_name_tree() {
+        let zone = [
+            ("example.com", r#"0 issue "ca1.net""#),
+            ("sub.example.com", r#"0 issue "ca2.net""#),
+        ];
+
+        let policy = find(&zone, "www.sub.example.com");
+        assert_eq!(policy.owner(), Some(&"sub.example.com".parse().unwrap()));
+        assert!(policy.may_issue("ca2.net"));
+        assert!(!policy.may_issue("ca1.net"));
+
+        let policy = find(&zone, "www.example.com");
+        assert!(policy.may_issue("CA1.net."));
+
+        // Without CAA records, any CA may issue.
+        let policy = find(&zone, "example.org");
+        assert_eq!(policy.owner(), None);
+        assert!(policy.may_issue("ca1.net"));
+        assert!(policy.may_issue_wildcard("ca1.net"));
+
+        // Failing lookups fail the search.
+        let lookup = |_: &DomainName| -> Result<Vec<ResourceRecord>, ()> { Err(()) };
+        assert!(Policy::find(&"example.com".parse().unwrap(), lookup).is_err());
+    }
+
+    #[test]
+    fn test_issue_and_issuewild() {
+        // The examples of section 4.2 and 4.3 of RFC 8659.
+        let zone = [
+            ("example.com", r#"0 issue "ca1.example.net""#),
+            (
+                "example.com",
+                r#"0 issue "ca2.example.org; account=230123""#,
+            ),
+            ("example.com", r#"0 issuewild "ca3.example.net""#),
+        ];
+        let policy = find(&zone, "example.com");
+        assert!(policy.may_issue("ca1.example.net"));
+        assert!(policy.may_issue("ca2.example.org"));
+        assert!(!policy.may_issue("ca3.example.net"));
+        assert!(policy.may_issue_wildcard("ca3.example.net"));
+        assert!(!policy.may_issue_wildcard("ca1.example.net"));
+
+        // Without "issuewild", "issue" applies to wildcard certificates.
+        let policy = find(&zone[..2], "example.com");
+        assert!(policy.may_issue_wildcard("ca1.example.net"));
+
+        // ";" forbids issuance, as does a malformed value.
+        let policy = find(&[("example.com", r#"0 issue ";""#)], "example.com");
+        assert!(!policy.may_issue("ca1.example.net"));
+        let policy = find(
+            &[("example.com", r#"0 issue "ca1.example.net; a""#)],
+            "example.com",
+        );
+        assert!(!policy.may_issue("ca1.example.net"));
+
+        // Only "issue" restricts issuance, other properties don't.
+        let policy = find(
+            &[("example.com", r#"0 iodef "mailto:security@example.com""#)],
+            "example.com",
+        );
+        assert!(policy.may_issue("ca1.example.net"));
+    }
+
+    #[test]
+    fn test_critical_properties() {
+        let zone = [
+            ("example.com", r#"0 issue "ca1.example.net""#),
+            ("example.com", r#"128 tbs "Unknown""#),
+        ];
+        assert!(!find(&zone, "example.com").may_issue("ca1.example.net"));
+
+        // Unknown properties that aren't critical are ignored.
+        let zone = [
+            ("example.com", r#"0 issue "ca1.example.net""#),
+            ("example.com", r#"0 tbs "Unknown""#),
+        ];
+        assert!(find(&zone, "example.com").may_issue("ca1.example.net"));
+    }
+}
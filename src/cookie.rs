@@ -0,0 +1,339 @@
+//! Generate and validate server cookies.
+//!
+//! DNS Cookies give lightweight protection against off-path spoofing over UDP. A client sends a
+//! random client cookie with its queries. The server answers with a server cookie derived from
+//! the client cookie, the address of the client and a secret, which the client includes in its
+//! following queries. An off-path attacker can't learn the server cookie, so a query carrying a
+//! valid one comes from the client it claims to. See [`RFC 7873`].
+//!
+//! Server cookies are generated as described by [`RFC 9018`], so servers of an anycast
+//! deployment sharing a secret accept each other's cookies.
+//!
+//! [`RFC 7873`]: https://www.rfc-editor.org/rfc/rfc7873
+//! [`RFC 9018`]: https://www.rfc-editor.org/rfc/rfc9018
+use crate::edns::Cookie;
+use crate::message::Message;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the server cookie format of RFC 9018.
+const COOKIE_VERSION: u8 = 1;
+
+/// How many seconds a server cookie remains valid.
+const COOKIE_LIFETIME: i64 = 3600;
+
+/// How many seconds the timestamp of a server cookie may lie in the future, allowing for clock
+/// skew between the servers sharing a secret.
+const CLOCK_SKEW: i64 = 300;
+
+/// The result of checking the cookie of a request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CookieStatus {
+    /// The request holds no cookie.
+    Missing,
+    /// The request holds a client cookie, but no server cookie.
+    ClientOnly,
+    /// The server cookie of the request wasn't generated by this server, was generated for
+    /// another client or has expired.
+    Invalid,
+    /// The server cookie of the request is valid.
+    Valid,
+}
+
+/// Generates and validates server cookies using a secret shared by the servers of a
+/// deployment.
+///
+/// The secret should be rotated regularly with [`ServerCookies::rotate()`]. Cookies generated
+/// with the previous secret remain valid until the next rotation.
+///
+/// ```
+/// use dns::cookie::{CookieStatus, ServerCookies};
+/// use dns::edns::Cookie;
+/// use dns::sections::QType;
+/// use dns::Message;
+/// use std::net::Ipv4Addr;
+/// use std::time::SystemTime;
+///
+/// let cookies = ServerCookies::new([7; 16]);
+/// let client = Ipv4Addr::new(192, 0, 2, 1).into();
+/// let now = SystemTime::now();
+///
+/// let query = Message::query("orangetux.nl".parse().unwrap(), QType::A)
+///     .cookie(Cookie::new([1; 8]))
+///     .build();
+/// assert_eq!(cookies.check(&query, client, now), CookieStatus::ClientOnly);
+///
+/// // The client includes the server cookie of the response in its next query.
+/// let cookie = cookies.cookie(&query, client, now).unwrap();
+/// let query = Message::query("orangetux.nl".parse().unwrap(), QType::A)
+///     .cookie(cookie)
+///     .build();
+/// assert_eq!(cookies.check(&query, client, now), CookieStatus::Valid);
+/// ```
+#[derive(Clone)]
+pub struct ServerCookies {
+    secret: [u8; 16],
+    previous_secret: Option<[u8; 16]>,
+}
+
+impl ServerCookies {
+    /// Create an instance using `secret`.
+    pub fn new(secret: [u8; 16]) -> Self {
+        Self {
+            secret,
+            previous_secret: None,
+        }
+    }
+
+    /// Replace the secret. Server cookies generated with the current secret remain valid.
+    pub fn rotate(&mut self, secret: [u8; 16]) {
+        self.previous_secret = Some(std::mem::replace(&mut self.secret, secret));
+    }
+
+    /// Check the cookie of `request`, sent by `client`.
+    pub fn check(&self, request: &Message, client: IpAddr, now: SystemTime) -> CookieStatus {
+        let cookie = match request.cookie() {
+            Some(cookie) => cookie,
+            None => return CookieStatus::Missing,
+        };
+        let server = match cookie.server() {
+            Some(server) => server,
+            None => return CookieStatus::ClientOnly,
+        };
+
+        if server.len() != 16 || server[0] != COOKIE_VERSION {
+            return CookieStatus::Invalid;
+        }
+
+        // Timestamps are compared using serial number arithmetic, see RFC 1982.
+        let timestamp = u32::from_be_bytes([server[4], server[5], server[6], server[7]]);
+        let age = i64::from(timestamp_of(now).wrapping_sub(timestamp) as i32);
+        if !(-CLOCK_SKEW..=COOKIE_LIFETIME).contains(&age) {
+            return CookieStatus::Invalid;
+        }
+
+        // Check every secret, so the time taken doesn't tell which one matched.
+        let valid = std::iter::once(&self.secret)
+            .chain(&self.previous_secret)
+            .fold(false, |valid, secret| {
+                let expected = server_cookie(secret, cookie.client(), timestamp, client);
+                valid | constant_time_eq(&expected, server)
+            });
+        if valid {
+            CookieStatus::Valid
+        } else {
+            CookieStatus::Invalid
+        }
+    }
+
+    /// The cookie to include in the response to `request`, sent by `client`: the client cookie
+    /// of the request with a fresh server cookie. `None` if the request holds no cookie.
+    pub fn cookie(&self, request: &Message, client: IpAddr, now: SystemTime) -> Option<Cookie> {
+        let client_cookie = *request.cookie()?.client();
+        let server = server_cookie(&self.secret, &client_cookie, timestamp_of(now), client);
+
+        Cookie::with_server_cookie(client_cookie, server.to_vec()).ok()
+    }
+}
+
+impl std::fmt::Debug for ServerCookies {
+    // Keep the secrets out of logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerCookies").finish_non_exhaustive()
+    }
+}
+
+// Whether `a` and `b` are equal, in a time that depends on their length only. An early return at
+// the first differing byte would let a spoofer guess a valid server cookie byte by byte.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// The number of seconds since the Unix epoch, modulo 2^32.
+fn timestamp_of(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+// Generate a server cookie as described in section 4 of RFC 9018: a version, 3 reserved bytes,
+// a timestamp and the SipHash-2-4 of the client cookie, the preceding fields and the address
+// of the client.
+fn server_cookie(
+    secret: &[u8; 16],
+    client_cookie: &[u8; 8],
+    timestamp: u32,
+    client: IpAddr,
+) -> [u8; 16] {
+    let mut cookie = [0; 16];
+    cookie[0] = COOKIE_VERSION;
+    cookie[4..8].copy_from_slice(&timestamp.to_be_bytes());
+
+    let mut input = client_cookie.to_vec();
+    input.extend_from_slice(&cookie[..8]);
+    match client {
+        IpAddr::V4(address) => input.extend_from_slice(&address.octets()),
+        IpAddr::V6(address) => input.extend_from_slice(&address.octets()),
+    }
+    cookie[8..].copy_from_slice(&siphash24(secret, &input).to_le_bytes());
+
+    cookie
+}
+
+// SipHash-2-4, see https://cr.yp.to/siphash/siphash-20120918.pdf.
+fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+    let k1 = u64::from_le_bytes(key[8..].try_into().unwrap());
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+    let compress = |v: &mut [u64; 4], m: u64| {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    };
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        compress(&mut v, u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+
+    // The last block holds the remaining bytes and the length of the input.
+    let mut last = [0; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+    compress(&mut v, u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sections::QType;
+    use std::time::Duration;
+
+    fn query(cookie: Cookie) -> Message {
+        Message::query("orangetux.nl".parse().unwrap(), QType::A)
+            .cookie(cookie)
+            .build()
+    }
+
+    #[test]
+    fn test_siphash() {
+        // The test vector of appendix A of the SipHash paper.
+        let key: Vec<u8> = (0..16).collect();
+        let data: Vec<u8> = (0..15).collect();
+        assert_eq!(
+            siphash24(&key.try_into().unwrap(), &data),
+            0xa129_ca61_49be_45e5
+        );
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"cookie", b"cookie"));
+        assert!(!constant_time_eq(b"cookie", b"cookiE"));
+        assert!(!constant_time_eq(b"cookie", b"cook"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_server_cookie() {
+        // The test vector of appendix A.1 of RFC 9018.
+        let secret = [
+            0xe5, 0xe9, 0x73, 0xe5, 0xa6, 0xb2, 0xa4, 0x3f, 0x48, 0xe7, 0xdc, 0x84, 0x9e, 0x37,
+            0xbf, 0xcf,
+        ];
+        let client_cookie = [0x24, 0x64, 0xc4, 0xab, 0xcf, 0x10, 0xc9, 0x57];
+        assert_eq!(
+            server_cookie(
+                &secret,
+                &client_cookie,
+                1559731985,
+                [198, 51, 100, 100].into()
+            ),
+            [
+                0x01, 0x00, 0x00, 0x00, 0x5c, 0xf7, 0x9f, 0x11, 0x1f, 0x81, 0x30, 0xc3, 0xee, 0xe2,
+                0x94, 0x80
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let mut cookies = ServerCookies::new([7; 16]);
+        let client: IpAddr = [192, 0, 2, 1].into();
+        let now = SystemTime::now();
+
+        let request = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        assert_eq!(cookies.check(&request, client, now), CookieStatus::Missing);
+        assert_eq!(cookies.cookie(&request, client, now), None);
+
+        let request = query(Cookie::new([1; 8]));
+        assert_eq!(
+            cookies.check(&request, client, now),
+            CookieStatus::ClientOnly
+        );
+
+        let cookie = cookies.cookie(&request, client, now).unwrap();
+        assert_eq!(cookie.client(), &[1; 8]);
+        let request = query(cookie.clone());
+        assert_eq!(cookies.check(&request, client, now), CookieStatus::Valid);
+
+        // The cookie is bound to the client address and client cookie.
+        assert_eq!(
+            cookies.check(&request, [192, 0, 2, 2].into(), now),
+            CookieStatus::Invalid
+        );
+        let other = Cookie::with_server_cookie([2; 8], cookie.server().unwrap().to_vec());
+        assert_eq!(
+            cookies.check(&query(other.unwrap()), client, now),
+            CookieStatus::Invalid
+        );
+
+        // The cookie expires after an hour and can't be from the far future.
+        let later = now + Duration::from_secs(3601);
+        assert_eq!(
+            cookies.check(&request, client, later),
+            CookieStatus::Invalid
+        );
+        let earlier = now - Duration::from_secs(301);
+        assert_eq!(
+            cookies.check(&request, client, earlier),
+            CookieStatus::Invalid
+        );
+
+        // The cookie survives a single rotation of the secret.
+        cookies.rotate([8; 16]);
+        assert_eq!(cookies.check(&request, client, now), CookieStatus::Valid);
+        cookies.rotate([9; 16]);
+        assert_eq!(cookies.check(&request, client, now), CookieStatus::Invalid);
+    }
+}
@@ -0,0 +1,126 @@
+//! A cursor over the bytes of a message.
+//!
+//! Decoders read fields through a `Cursor` instead of iterating over the message byte by byte.
+//! The cursor keeps the complete message around, so compression pointers can jump to any offset,
+//! and knows the offset of every field it hands out, so errors can point at the offending byte.
+use crate::{DecodeError, DecodeErrorKind};
+use nom::bytes::complete::take;
+use nom::number::complete::{be_u16, be_u32, u8};
+use nom::IResult;
+
+/// A position in a message. Reads never go past `end`, which is the end of the message or, for a
+/// cursor returned by `Cursor::split()`, the end of a field like rdata.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Cursor<'a> {
+    message: &'a [u8],
+    position: usize,
+    end: usize,
+}
+
+impl<'a> Cursor<'a> {
+    /// Create a cursor at the start of `message`.
+    pub(crate) fn new(message: &'a [u8]) -> Self {
+        Self {
+            message,
+            position: 0,
+            end: message.len(),
+        }
+    }
+
+    /// The complete message.
+    pub(crate) fn message(&self) -> &'a [u8] {
+        self.message
+    }
+
+    /// The offset of the next byte relative to the start of the message.
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of bytes that can still be read.
+    pub(crate) fn remaining(&self) -> usize {
+        self.end - self.position
+    }
+
+    /// The bytes that can still be read.
+    pub(crate) fn as_bytes(&self) -> &'a [u8] {
+        &self.message[self.position..self.end]
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        self.parse(u8)
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        self.parse(be_u16)
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        self.parse(be_u32)
+    }
+
+    /// Read `count` bytes without copying them.
+    pub(crate) fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+        self.parse(take(count))
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+
+        Ok(array)
+    }
+
+    /// Return a cursor limited to the next `count` bytes and move this cursor past them.
+    pub(crate) fn split(&mut self, count: usize) -> Result<Cursor<'a>, DecodeError> {
+        let start = self.position;
+        self.read_bytes(count)?;
+
+        Ok(Cursor {
+            message: self.message,
+            position: start,
+            end: self.position,
+        })
+    }
+
+    // Run `parser` on the remaining bytes and advance the cursor past the bytes it consumed.
+    fn parse<T>(
+        &mut self,
+        mut parser: impl FnMut(&'a [u8]) -> IResult<&'a [u8], T>,
+    ) -> Result<T, DecodeError> {
+        let (rest, value) = parser(self.as_bytes())
+            .map_err(|_| DecodeError::from(DecodeErrorKind::NotEnoughBytes).at(self.position))?;
+        self.position = self.end - rest.len();
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reads_advance_the_cursor() {
+        let message = [1, 0, 2, 0, 0, 0, 3, 4, 5, 6];
+        let mut cursor = Cursor::new(&message);
+
+        assert_eq!(cursor.read_u8().unwrap(), 1);
+        assert_eq!(cursor.read_u16().unwrap(), 2);
+        assert_eq!(cursor.read_u32().unwrap(), 3);
+
+        let mut field = cursor.split(2).unwrap();
+        assert_eq!(cursor.position(), 9);
+        assert_eq!(field.read_bytes(2).unwrap(), [4, 5]);
+
+        // Reads don't go past the end of a split off field...
+        let error = field.read_u8().unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::NotEnoughBytes);
+        assert_eq!(error.offset, Some(9));
+
+        // ...or past the end of the message.
+        assert!(cursor.read_u16().is_err());
+        assert_eq!(cursor.read_array::<1>().unwrap(), [6]);
+        assert_eq!(cursor.remaining(), 0);
+    }
+}
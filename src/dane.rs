@@ -0,0 +1,444 @@
+//! Authenticate TLS servers and SSH hosts with certificates and keys published in DNS.
+//!
+//! TLSA records tie the certificate, or public key, of a TLS server to the name and port of
+//! the server, see [`RFC 6698`]. SSHFP records publish the fingerprints of the host keys of an
+//! SSH server, see [`RFC 4255`]. Both can only be trusted if the records are validated with
+//! DNSSEC.
+//!
+//! Matching records against certificates and keys requires the `dane` feature.
+//!
+//! ```
+//! # #[cfg(feature = "dane")]
+//! # mod example {
+//! use dns::dane;
+//! use dns::resource_record::ResourceRecord;
+//!
+//! // Whether the SSHFP records of a host list the host key its SSH server presented.
+//! fn verify_host_key(records: &[ResourceRecord], host_key: &[u8]) -> bool {
+//!     !dane::matching_sshfp(records, host_key).is_empty()
+//! }
+//! # }
+//! ```
+//!
+//! [`RFC 6698`]: https://www.rfc-editor.org/rfc/rfc6698
+//! [`RFC 4255`]: https://www.rfc-editor.org/rfc/rfc4255
+#[cfg(feature = "dane")]
+use crate::cookie::constant_time_eq;
+#[cfg(feature = "dane")]
+use crate::rdata::RData;
+#[cfg(feature = "dane")]
+use crate::resource_record::ResourceRecord;
+#[cfg(feature = "dane")]
+use sha1::Sha1;
+#[cfg(feature = "dane")]
+use sha2::{Digest, Sha256, Sha512};
+#[cfg(feature = "dane")]
+use x509_cert::der::{Decode, Encode};
+#[cfg(feature = "dane")]
+use x509_cert::Certificate;
+
+/// How a TLSA record constrains the certificate a server presents, see section 2.1.1 of RFC
+/// 6698. Named after the acronyms of RFC 7218.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum CertificateUsage {
+    /// The record matches a CA certificate in the chain of the server, which must also pass
+    /// PKIX validation.
+    PkixTa,
+    /// The record matches the certificate of the server, which must also pass PKIX validation.
+    PkixEe,
+    /// The record matches a trust anchor in the chain of the server.
+    DaneTa,
+    /// The record matches the certificate of the server.
+    DaneEe,
+    /// A usage this crate doesn't know about.
+    Unknown(u8),
+}
+
+impl From<u8> for CertificateUsage {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::PkixTa,
+            1 => Self::PkixEe,
+            2 => Self::DaneTa,
+            3 => Self::DaneEe,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<CertificateUsage> for u8 {
+    fn from(value: CertificateUsage) -> Self {
+        match value {
+            CertificateUsage::PkixTa => 0,
+            CertificateUsage::PkixEe => 1,
+            CertificateUsage::DaneTa => 2,
+            CertificateUsage::DaneEe => 3,
+            CertificateUsage::Unknown(value) => value,
+        }
+    }
+}
+
+/// The part of a certificate a TLSA record matches, see section 2.1.2 of RFC 6698.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Selector {
+    /// The full certificate.
+    FullCertificate,
+    /// The SubjectPublicKeyInfo of the certificate: its public key and algorithm.
+    SubjectPublicKeyInfo,
+    /// A selector this crate doesn't know about.
+    Unknown(u8),
+}
+
+impl From<u8> for Selector {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::FullCertificate,
+            1 => Self::SubjectPublicKeyInfo,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Selector> for u8 {
+    fn from(value: Selector) -> Self {
+        match value {
+            Selector::FullCertificate => 0,
+            Selector::SubjectPublicKeyInfo => 1,
+            Selector::Unknown(value) => value,
+        }
+    }
+}
+
+/// How the data of a TLSA record is compared with the selected part of a certificate, see
+/// section 2.1.3 of RFC 6698.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum MatchingType {
+    /// The data is the selected part itself.
+    Full,
+    /// The data is the SHA-256 hash of the selected part.
+    Sha256,
+    /// The data is the SHA-512 hash of the selected part.
+    Sha512,
+    /// A matching type this crate doesn't know about.
+    Unknown(u8),
+}
+
+impl From<u8> for MatchingType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Full,
+            1 => Self::Sha256,
+            2 => Self::Sha512,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<MatchingType> for u8 {
+    fn from(value: MatchingType) -> Self {
+        match value {
+            MatchingType::Full => 0,
+            MatchingType::Sha256 => 1,
+            MatchingType::Sha512 => 2,
+            MatchingType::Unknown(value) => value,
+        }
+    }
+}
+
+/// The algorithm of the SSH key an SSHFP record fingerprints. See the [`SSHFP RR Types for
+/// public key algorithms`] registry of IANA.
+///
+/// [`SSHFP RR Types for public key algorithms`]: https://www.iana.org/assignments/dns-sshfp-rr-parameters/dns-sshfp-rr-parameters.xhtml#dns-sshfp-rr-parameters-1
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SshfpAlgorithm {
+    /// RSA, "ssh-rsa" keys.
+    Rsa,
+    /// DSA, "ssh-dss" keys.
+    Dsa,
+    /// ECDSA, "ecdsa-sha2-*" keys. See RFC 6594.
+    Ecdsa,
+    /// Ed25519, "ssh-ed25519" keys. See RFC 7479.
+    Ed25519,
+    /// Ed448, "ssh-ed448" keys. See RFC 8709.
+    Ed448,
+    /// An algorithm this crate doesn't know about.
+    Unknown(u8),
+}
+
+impl SshfpAlgorithm {
+    // The algorithm of an SSH public key in the wire format of section 6.6 of RFC 4253, which
+    // starts with the name of the key type.
+    #[cfg(feature = "dane")]
+    fn of_key(public_key: &[u8]) -> Option<Self> {
+        let length = u32::from_be_bytes(public_key.get(..4)?.try_into().unwrap());
+        let key_type = public_key.get(4..4 + usize::try_from(length).ok()?)?;
+
+        let algorithm = match key_type {
+            b"ssh-rsa" => Self::Rsa,
+            b"ssh-dss" => Self::Dsa,
+            b"ssh-ed25519" => Self::Ed25519,
+            b"ssh-ed448" => Self::Ed448,
+            _ if key_type.starts_with(b"ecdsa-sha2-") => Self::Ecdsa,
+            _ => return None,
+        };
+
+        Some(algorithm)
+    }
+}
+
+impl From<u8> for SshfpAlgorithm {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Rsa,
+            2 => Self::Dsa,
+            3 => Self::Ecdsa,
+            4 => Self::Ed25519,
+            6 => Self::Ed448,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<SshfpAlgorithm> for u8 {
+    fn from(value: SshfpAlgorithm) -> Self {
+        match value {
+            SshfpAlgorithm::Rsa => 1,
+            SshfpAlgorithm::Dsa => 2,
+            SshfpAlgorithm::Ecdsa => 3,
+            SshfpAlgorithm::Ed25519 => 4,
+            SshfpAlgorithm::Ed448 => 6,
+            SshfpAlgorithm::Unknown(value) => value,
+        }
+    }
+}
+
+/// The hash function an SSHFP record fingerprints a key with.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum FingerprintType {
+    /// SHA-1.
+    Sha1,
+    /// SHA-256. See RFC 6594.
+    Sha256,
+    /// A fingerprint type this crate doesn't know about.
+    Unknown(u8),
+}
+
+impl From<u8> for FingerprintType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Sha1,
+            2 => Self::Sha256,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<FingerprintType> for u8 {
+    fn from(value: FingerprintType) -> Self {
+        match value {
+            FingerprintType::Sha1 => 1,
+            FingerprintType::Sha256 => 2,
+            FingerprintType::Unknown(value) => value,
+        }
+    }
+}
+
+/// The TLSA records in `records` that match `certificate`, a DER-encoded X.509 certificate.
+///
+/// Whether a match authenticates the server depends on the certificate usage of the record and
+/// on where `certificate` is in the chain the server presented. Records with a usage, selector
+/// or matching type this crate doesn't know about never match. Other records are ignored.
+#[cfg(feature = "dane")]
+pub fn matching_tlsa<'a>(
+    records: &'a [ResourceRecord],
+    certificate: &[u8],
+) -> Vec<&'a ResourceRecord> {
+    records
+        .iter()
+        .filter(|record| match &record.rdata {
+            RData::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => {
+                !matches!(usage, CertificateUsage::Unknown(_))
+                    && tlsa_matches(*selector, *matching_type, data, certificate)
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+#[cfg(feature = "dane")]
+fn tlsa_matches(
+    selector: Selector,
+    matching_type: MatchingType,
+    data: &[u8],
+    certificate: &[u8],
+) -> bool {
+    let selected = match selector {
+        Selector::FullCertificate => certificate.to_vec(),
+        Selector::SubjectPublicKeyInfo => match subject_public_key_info(certificate) {
+            Some(selected) => selected,
+            None => return false,
+        },
+        Selector::Unknown(_) => return false,
+    };
+
+    match matching_type {
+        MatchingType::Full => constant_time_eq(&selected, data),
+        MatchingType::Sha256 => constant_time_eq(&Sha256::digest(&selected), data),
+        MatchingType::Sha512 => constant_time_eq(&Sha512::digest(&selected), data),
+        MatchingType::Unknown(_) => false,
+    }
+}
+
+/// The SSHFP records in `records` that match `public_key`, an SSH public key in the wire format
+/// of section 6.6 of RFC 4253. That's the key a server presents during key exchange, and the
+/// base64-decoded second field of an OpenSSH public key file.
+///
+/// A record matches if both its algorithm and its fingerprint match the key. Records with a
+/// fingerprint type this crate doesn't know about never match. Other records are ignored.
+#[cfg(feature = "dane")]
+pub fn matching_sshfp<'a>(
+    records: &'a [ResourceRecord],
+    public_key: &[u8],
+) -> Vec<&'a ResourceRecord> {
+    let Some(key_algorithm) = SshfpAlgorithm::of_key(public_key) else {
+        return vec![];
+    };
+
+    records
+        .iter()
+        .filter(|record| match &record.rdata {
+            RData::SSHFP {
+                algorithm,
+                fingerprint_type,
+                fingerprint,
+            } => {
+                *algorithm == key_algorithm
+                    && match fingerprint_type {
+                        FingerprintType::Sha1 => {
+                            constant_time_eq(&Sha1::digest(public_key), fingerprint)
+                        }
+                        FingerprintType::Sha256 => {
+                            constant_time_eq(&Sha256::digest(public_key), fingerprint)
+                        }
+                        FingerprintType::Unknown(_) => false,
+                    }
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+// The DER-encoded SubjectPublicKeyInfo of a DER-encoded certificate, see section 4.1 of RFC 5280.
+#[cfg(feature = "dane")]
+fn subject_public_key_info(certificate: &[u8]) -> Option<Vec<u8>> {
+    let certificate = Certificate::from_der(certificate).ok()?;
+    certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .ok()
+}
+
+#[cfg(all(test, feature = "dane"))]
+mod test {
+    use super::*;
+    use crate::rdata::from_base64;
+    use crate::resource_record::{Class, Type};
+
+    // A self-signed certificate for example.com with an EC P-256 key.
+    const CERTIFICATE: &str = "MIIBgjCCASmgAwIBAgIUJFeP1devQ9XMPaBA89eA7zeiBaUwCgYIKoZIzj0EAwIwFjEUMBIGA1UEAwwLZXhhbXBsZS5jb20wIBcNMjYxMDE3MjEwNDQ2WhgPMjEyNjA5MjMyMTA0NDZaMBYxFDASBgNVBAMMC2V4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE1Cp1EXux/RsxubkoVmfBeXNUe/yz2clPZOlJXvNHfsQSDDNFIxV+8t3JAXXjbEHSN5Xwb1yJw5nq2Gm6WrARDKNTMFEwHQYDVR0OBBYEFFtPfVngzx7V3kB6ceXjoZSOutD+MB8GA1UdIwQYMBaAFFtPfVngzx7V3kB6ceXjoZSOutD+MA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDRwAwRAIgVkX7nZcV6kU+SUXpq8q51/A/y8HjD9z87MGUl4vYbtsCICokwcfPnGP57/cAhwUl3e0qFO0fxRC2fREvyZedSEoa";
+
+    // An Ed25519 host key, as in an OpenSSH public key file.
+    const HOST_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAILlXzWu2svH6OnyYSbH1V7G2uB5nThru0/fB8bUrA4m6";
+
+    fn records(r#type: Type, values: &[&str]) -> Vec<ResourceRecord> {
+        values
+            .iter()
+            .map(|value| ResourceRecord {
+                name: "example.com".parse().unwrap(),
+                class: Class::IN,
+                ttl: 3600,
+                rdata: RData::from_presentation(&r#type, value).unwrap(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_subject_public_key_info() {
+        let certificate = from_base64(CERTIFICATE).unwrap();
+        let spki = subject_public_key_info(&certificate).unwrap();
+        assert_eq!(spki.len(), 91);
+        assert_eq!(&spki[..4], [0x30, 0x59, 0x30, 0x13]);
+
+        assert_eq!(subject_public_key_info(&certificate[..200]), None);
+        assert_eq!(subject_public_key_info(&[]), None);
+    }
+
+    #[test]
+    fn test_matching_tlsa() {
+        let certificate = from_base64(CERTIFICATE).unwrap();
+        let records = records(
+            Type::TLSA,
+            &[
+                "3 0 1 0b4c4ac59b0ce559a233f7171ffe2df1510a3fa4ffe2e5da0094ec6c7e013784",
+                "3 1 1 7c01478d2aeb24a90e91375654ad687110dee11571bea722e1a30ee05c4662d5",
+                "2 1 2 e144f703d12c87a85a1bf3b66d040df6796072e0723b5d669dc51877ca581bbf\
+                 c8f12d487bc1fd2d13150743b2cbd682cfa75bab0601f2f9a59936d2cc337469",
+                // Another certificate.
+                "3 0 1 d2abde240d7cd3ee6b4b28c54df034b97983a1d16e8a410e4561cb106618e971",
+                // Usage, selector and matching type this crate doesn't know.
+                "4 0 1 0b4c4ac59b0ce559a233f7171ffe2df1510a3fa4ffe2e5da0094ec6c7e013784",
+                "3 2 1 0b4c4ac59b0ce559a233f7171ffe2df1510a3fa4ffe2e5da0094ec6c7e013784",
+                "3 0 3 0b4c4ac59b0ce559a233f7171ffe2df1510a3fa4ffe2e5da0094ec6c7e013784",
+            ],
+        );
+
+        assert_eq!(
+            matching_tlsa(&records, &certificate),
+            [&records[0], &records[1], &records[2]]
+        );
+
+        // The full SubjectPublicKeyInfo matches too.
+        let spki = subject_public_key_info(&certificate).unwrap();
+        let full = [ResourceRecord {
+            rdata: RData::TLSA {
+                usage: CertificateUsage::DaneEe,
+                selector: Selector::SubjectPublicKeyInfo,
+                matching_type: MatchingType::Full,
+                data: spki,
+            },
+            ..records[0].clone()
+        }];
+        assert_eq!(matching_tlsa(&full, &certificate).len(), 1);
+
+        assert!(matching_tlsa(&records, b"not a certificate").is_empty());
+    }
+
+    #[test]
+    fn test_matching_sshfp() {
+        let host_key = from_base64(HOST_KEY).unwrap();
+        let records = records(
+            Type::SSHFP,
+            &[
+                "4 1 e107a6219e064b470bd9113760ee69722291ad9f",
+                "4 2 d29677ddbcab5bbe650b54e76e51ffe571ae722407b55fd7ab59460a98a4a2d9",
+                // The algorithm of the key must match as well.
+                "3 2 d29677ddbcab5bbe650b54e76e51ffe571ae722407b55fd7ab59460a98a4a2d9",
+                "4 2 0000000000000000000000000000000000000000000000000000000000000000",
+                "4 3 d29677ddbcab5bbe650b54e76e51ffe571ae722407b55fd7ab59460a98a4a2d9",
+            ],
+        );
+
+        assert_eq!(
+            matching_sshfp(&records, &host_key),
+            [&records[0], &records[1]]
+        );
+        assert!(matching_sshfp(&records, &host_key[..10]).is_empty());
+        assert!(matching_sshfp(&records, &[]).is_empty());
+    }
+}
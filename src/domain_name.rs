@@ -1,133 +1,547 @@
-// Serialize and deserialize domain names.
-//
-// Domain names can be encoded in different ways:
-// 1) as a series of labels ending with a 0 byte.
-// 2) as a pointer
-// 3) as a series of labels ending with a pointer
-//
-// Inside the question section, domain names are always encoded with #1.
-// However, inside resource records, domain names can be encoded using all 3 methods.
-//
-// For more info, see sections:
-// * 3.1. Name space definitions
-// * 4.1.4. Message compression
-use crate::DecodeError;
-use std::iter::Peekable;
-use std::slice::Iter;
+//! Serialize and deserialize domain names.
+//!
+//! Domain names can be encoded in different ways:
+//! 1) as a series of labels ending with a 0 byte.
+//! 2) as a pointer
+//! 3) as a series of labels ending with a pointer
+//!
+//! Inside the question section, domain names are always encoded with #1.
+//! However, inside resource records, domain names can be encoded using all 3 methods.
+//!
+//! Decoding yields a [`NameRef`], which borrows the name from the message and follows pointers
+//! when its labels are iterated. [`NameRef::to_domain_name()`] copies the labels into an owned
+//! [`DomainName`]. When encoding a message, names are turned back into #2 or #3 whenever a suffix
+//! of the name has been written before.
+//!
+//! For more info, see sections:
+//! * 3.1. Name space definitions
+//! * 4.1.4. Message compression
+use crate::cursor::Cursor;
+use crate::{DecodeError, DecodeErrorKind};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Labels are limited to 63 octets.
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+/// Domain names are limited to 255 octets, including the length bytes and the terminating 0
+/// byte.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+// A name of at most 255 octets contains at most 127 labels, so a well-formed message never needs
+// more pointers than that to express a single name.
+const MAX_POINTER_HOPS: usize = 127;
+
+/// A domain name as defined in section [`3.1. Name space definitions`] of RFC 1035.
+///
+/// A `DomainName` is a list of labels, ordered from the leftmost label to the rightmost label.
+/// The root label is implicit: the root itself is a `DomainName` without labels.
+///
+/// Construction validates that labels are not empty and at most 63 bytes long, and that the
+/// name is at most 255 bytes long in wire format.
+///
+/// Comparison is case-insensitive. Ordering follows the canonical ordering of section
+/// [`6.1. Canonical DNS Name Order`] of RFC 4034.
+///
+/// ```
+/// use dns::DomainName;
+///
+/// let name: DomainName = "www.OrangeTux.nl".parse().unwrap();
+/// assert_eq!(name, "www.orangetux.nl".parse().unwrap());
+/// assert_eq!(name.labels().len(), 3);
+/// assert_eq!(name.to_string(), "www.OrangeTux.nl");
+///
+/// assert!("orangetux.nl".parse::<DomainName>().unwrap() < name);
+/// ```
+///
+/// [`3.1. Name space definitions`]: https://www.rfc-editor.org/rfc/rfc1035#section-3.1
+/// [`6.1. Canonical DNS Name Order`]: https://www.rfc-editor.org/rfc/rfc4034#section-6.1
+#[derive(Clone, Debug, Default)]
+pub struct DomainName {
+    labels: Vec<Vec<u8>>,
+}
+
+impl DomainName {
+    /// The root domain name.
+    pub fn root() -> Self {
+        Self { labels: vec![] }
+    }
+
+    /// Create a `DomainName` from a list of labels, leftmost label first.
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> Result<Self, DecodeError> {
+        let name = Self { labels };
+        for label in &name.labels {
+            check_label(label)?;
+        }
+        check_length(name.wire_len())?;
+
+        Ok(name)
+    }
 
-pub struct Name(pub Vec<u8>);
+    /// The labels of the name, leftmost label first.
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    /// Whether this is the root domain name.
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
 
-pub(crate) fn into_string(name: Name) -> Result<String, DecodeError> {
-    if is_pointer(name.0[0]) || is_pointer(name.0[name.0.len() - 1]) {
-        return Err(DecodeError::IllegalValue(String::from(
-            "can't stringify domain name since it contains pointers",
-        )));
+    /// The name without its leftmost label, or `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, labels) = self.labels.split_first()?;
+        Some(Self {
+            labels: labels.to_vec(),
+        })
     }
 
-    let mut utf8_bytes = vec![];
-    for byte in name.0 {
-        if byte == 0 {
-            continue;
+    /// Whether the name is `other` or a name below it. Every name is a subdomain of the root.
+    pub fn is_subdomain_of(&self, other: &Self) -> bool {
+        self.labels.len() >= other.labels.len()
+            && self
+                .labels
+                .iter()
+                .rev()
+                .zip(other.labels.iter().rev())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// Length of the name in uncompressed wire format.
+    pub fn wire_len(&self) -> usize {
+        self.labels
+            .iter()
+            .map(|label| label.len() + 1)
+            .sum::<usize>()
+            + 1
+    }
+
+    /// Serialize the instance into bytes, without compression.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.wire_len());
+        for label in self.labels {
+            output.push(label.len() as u8);
+            output.extend(label);
         }
+        output.push(0);
 
-        if is_label(byte) {
-            if !utf8_bytes.is_empty() {
-                utf8_bytes.push(b'.');
+        output
+    }
+}
+
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(&other.labels)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for DomainName {}
+
+impl Hash for DomainName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.labels.len().hash(state);
+        for label in &self.labels {
+            label.to_ascii_lowercase().hash(state);
+        }
+    }
+}
+
+impl Ord for DomainName {
+    // Names are sorted by their most significant (rightmost) labels first. Labels are compared
+    // as lowercase octet strings, where an absent label sorts before any other label.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut left = self.labels.iter().rev();
+        let mut right = other.labels.iter().rev();
+        loop {
+            match (left.next(), right.next()) {
+                (None, None) => return Ordering::Equal,
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(a), Some(b)) => {
+                    let ordering = a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase());
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
             }
-            continue;
         }
+    }
+}
 
-        utf8_bytes.push(byte);
+impl PartialOrd for DomainName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    Ok(std::str::from_utf8(&utf8_bytes)
-        .map_err(|_| {
-            DecodeError::IllegalValue(
-                "failed to parse value as qname: value not valid UTF-8".into(),
-            )
-        })?
-        .to_string())
+impl Display for DomainName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_labels(f, self.labels.iter().map(Vec::as_slice))
+    }
 }
 
-// Take the number of bytes that make up the domain name.
-// TODO: Not sure if the term 'take' is the correct term here. `Iterator::take()` exists, but that returns
-// a `Take` instance, not a `Vec`. Also, `Iterator::take()` is a bound method and doesn't receive
-// an argument (other than `self`).
-pub(crate) fn take_name(value: &mut Peekable<Iter<'_, u8>>) -> Result<Name, DecodeError> {
-    let high_byte = **value.peek().ok_or(DecodeError::NotEnoughBytes)?;
+// Print the labels in presentation format, without trailing dot. Special characters are escaped
+// as described in section 5.1 of RFC 1035.
+fn write_labels<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    labels: impl Iterator<Item = &'a [u8]>,
+) -> std::fmt::Result {
+    let mut labels = labels.peekable();
+    if labels.peek().is_none() {
+        return write!(f, ".");
+    }
+
+    for (index, label) in labels.enumerate() {
+        if index > 0 {
+            write!(f, ".")?;
+        }
 
-    if is_pointer(high_byte) {
-        println!("First byte of name indicate it's a pointer.");
-        // Pointers are 2 bytes wide.
-        let high_byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
-        let low_byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
-        return Ok(Name(vec![high_byte, low_byte]));
+        for byte in label {
+            match byte {
+                b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                    write!(f, "\\{}", *byte as char)?
+                }
+                0x21..=0x7e => write!(f, "{}", *byte as char)?,
+                _ => write!(f, "\\{:03}", byte)?,
+            }
+        }
     }
 
-    if !is_label(high_byte) {
-        println!(
-            "First byte of name is invalid: it should indicate it's a pointer or a label.{:b}",
-            high_byte
-        );
-        // Pointers are 2 bytes wide.
-        return Err(DecodeError::IllegalValue(
-            "failed to parse domain name: it's not a pointer or label".to_string(),
-        ));
+    Ok(())
+}
+
+impl FromStr for DomainName {
+    type Err = DecodeError;
+
+    // Parse a name in presentation format, like "orangetux.nl" or "orangetux.nl.". Supports the
+    // escapes `\X` and `\DDD` of section 5.1 of RFC 1035.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value == "." {
+            return Ok(Self::root());
+        }
+
+        let mut labels = vec![];
+        let mut label = vec![];
+        let mut bytes = value.bytes();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'.' => {
+                    check_label(&label)?;
+                    labels.push(std::mem::take(&mut label));
+                }
+                b'\\' => {
+                    let escaped = bytes.next().ok_or_else(|| {
+                        DecodeErrorKind::IllegalValue(format!(
+                            "failed to parse domain name: {} ends with an escape character",
+                            value
+                        ))
+                    })?;
+                    if escaped.is_ascii_digit() {
+                        let digits = [
+                            Some(escaped),
+                            bytes.next().filter(u8::is_ascii_digit),
+                            bytes.next().filter(u8::is_ascii_digit),
+                        ];
+                        let decimal = digits
+                            .iter()
+                            .try_fold(0u16, |total, digit| {
+                                digit.map(|digit| total * 10 + u16::from(digit - b'0'))
+                            })
+                            .and_then(|decimal| u8::try_from(decimal).ok())
+                            .ok_or_else(|| {
+                                DecodeErrorKind::IllegalValue(format!(
+                                    "failed to parse domain name: {} contains an invalid \\DDD escape",
+                                    value
+                                ))
+                            })?;
+                        label.push(decimal);
+                    } else {
+                        label.push(escaped);
+                    }
+                }
+                _ => label.push(byte),
+            }
+        }
+
+        // A trailing dot marks a fully qualified name. It doesn't leave an empty label behind.
+        if !label.is_empty() || labels.is_empty() {
+            labels.push(label);
+        }
+
+        Self::from_labels(labels)
+    }
+}
+
+fn check_label(label: &[u8]) -> Result<(), DecodeError> {
+    if label.is_empty() || label.len() > MAX_LABEL_LENGTH {
+        return Err(DecodeErrorKind::InvalidLabelLength.into());
+    }
+
+    Ok(())
+}
+
+fn check_length(length: usize) -> Result<(), DecodeError> {
+    if length > MAX_NAME_LENGTH {
+        return Err(DecodeErrorKind::NameTooLong.into());
+    }
+
+    Ok(())
+}
+
+/// A domain name borrowed from a message.
+///
+/// A `NameRef` points at the first byte of a name in the message. Compressed names are expanded
+/// while iterating over the labels, so inspecting a name doesn't allocate. The name has been
+/// validated when the message was parsed: pointers point to prior occurrences of names and the
+/// name is at most 255 bytes long.
+///
+/// Comparison with a [`DomainName`] is case-insensitive.
+#[derive(Clone, Copy)]
+pub struct NameRef<'a> {
+    message: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameRef<'a> {
+    /// Parse the name at the position of `cursor` and move the cursor past it.
+    ///
+    /// Only the bytes up to and including the terminating 0 byte or the first pointer belong to
+    /// the name at the position of the cursor. The labels a pointer leads to are validated, but
+    /// may be anywhere in the message before the pointer.
+    pub(crate) fn parse(cursor: &mut Cursor<'a>) -> Result<Self, DecodeError> {
+        let message = cursor.message();
+        let offset = cursor.position();
+
+        // The length of the name in wire format, including the terminating 0 byte.
+        let mut length = 1;
+        loop {
+            let position = cursor.position();
+            let byte = cursor.read_u8()?;
+
+            if byte == 0 {
+                break;
+            }
+
+            if is_pointer(byte) {
+                let low_byte = cursor.read_u8()?;
+                follow_pointer(
+                    message,
+                    position,
+                    pointer_offset(byte, low_byte),
+                    &mut length,
+                )?;
+                break;
+            }
+
+            if !is_label(byte) {
+                return Err(DecodeError::from(DecodeErrorKind::InvalidLabelType).at(position));
+            }
+
+            cursor.read_bytes(byte.into())?;
+            length += 1 + usize::from(byte);
+            check_length(length).map_err(|error| error.at(position))?;
+        }
+
+        Ok(Self { message, offset })
     }
-    println!("First byte of name is a label: {:b}", high_byte);
 
-    let mut labels = take_labels(value)?;
-    let byte = **value.peek().ok_or(DecodeError::NotEnoughBytes)?;
+    /// The offset of the name relative to the start of the message.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-    if byte == 0 {
-        let byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
-        labels.push(byte);
-        return Ok(Name(labels));
+    /// Whether the name is the root.
+    pub fn is_root(&self) -> bool {
+        self.labels().next().is_none()
     }
 
-    if is_pointer(byte) {
-        let high_byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
-        let low_byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
-        labels.push(low_byte);
-        return Ok(Name(labels));
+    /// Iterate over the labels of the name, from the leftmost label to the rightmost label.
+    pub fn labels(&self) -> Labels<'a> {
+        Labels {
+            message: self.message,
+            offset: self.offset,
+        }
     }
 
-    Err(DecodeError::IllegalValue(
-        "label doesn't end with a null byte or pointer".to_string(),
-    ))
+    /// Copy the labels into a `DomainName`.
+    pub fn to_domain_name(&self) -> DomainName {
+        DomainName {
+            labels: self.labels().map(<[u8]>::to_vec).collect(),
+        }
+    }
 }
 
-fn take_labels(value: &mut Peekable<Iter<'_, u8>>) -> Result<Vec<u8>, DecodeError> {
-    let mut labels = vec![];
-    loop {
-        let high_byte = **value.peek().ok_or(DecodeError::NotEnoughBytes)?;
+impl PartialEq<DomainName> for NameRef<'_> {
+    fn eq(&self, other: &DomainName) -> bool {
+        let mut labels = self.labels();
+        other
+            .labels
+            .iter()
+            .all(|label| labels.next().is_some_and(|l| l.eq_ignore_ascii_case(label)))
+            && labels.next().is_none()
+    }
+}
+
+impl Display for NameRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_labels(f, self.labels())
+    }
+}
 
-        if !is_label(high_byte) {
-            return Ok(labels);
+impl Debug for NameRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NameRef").field(&self.to_string()).finish()
+    }
+}
+
+/// An iterator over the labels of a [`NameRef`].
+#[derive(Clone, Debug)]
+pub struct Labels<'a> {
+    message: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = *self.message.get(self.offset)?;
+
+            if is_pointer(byte) {
+                let low_byte = *self.message.get(self.offset + 1)?;
+                self.offset = pointer_offset(byte, low_byte);
+                continue;
+            }
+
+            if !is_label(byte) || byte == 0 {
+                return None;
+            }
+
+            let start = self.offset + 1;
+            let label = self.message.get(start..start + usize::from(byte))?;
+            self.offset = start + label.len();
+            return Some(label);
         }
+    }
+}
+
+// Parse a name and copy it into a `DomainName`.
+pub(crate) fn take_name(cursor: &mut Cursor<'_>) -> Result<DomainName, DecodeError> {
+    Ok(NameRef::parse(cursor)?.to_domain_name())
+}
+
+// Validate the labels found at `offset` in `message`, following any further pointers, and add
+// their length to `length`.
+//
+// `pointer` is the offset of the pointer that led to `offset`. Pointers must point to a prior
+// occurrence of a name, so every jump must land strictly before the labels that contained the
+// pointer. That rules out loops; the hop limit protects against long chains of pointers.
+fn follow_pointer(
+    message: &[u8],
+    mut pointer: usize,
+    mut offset: usize,
+    length: &mut usize,
+) -> Result<(), DecodeError> {
+    let not_enough_bytes =
+        |offset: usize| DecodeError::from(DecodeErrorKind::NotEnoughBytes).at(offset);
 
-        if high_byte == 0 {
-            return Ok(labels);
+    let mut limit = pointer;
+    let mut hops = 0;
+    loop {
+        if offset >= limit {
+            return Err(DecodeError::from(DecodeErrorKind::InvalidPointer).at(pointer));
         }
 
-        if !labels.is_empty() {
-            labels.push(b'.');
+        hops += 1;
+        if hops > MAX_POINTER_HOPS {
+            return Err(DecodeError::from(DecodeErrorKind::TooManyPointers).at(pointer));
         }
 
-        labels.append(&mut take_label(&mut *value)?);
+        let start = offset;
+        loop {
+            let byte = *message
+                .get(offset)
+                .ok_or_else(|| not_enough_bytes(offset))?;
+
+            if byte == 0 {
+                return Ok(());
+            }
+
+            if is_pointer(byte) {
+                let low_byte = *message
+                    .get(offset + 1)
+                    .ok_or_else(|| not_enough_bytes(offset + 1))?;
+                pointer = offset;
+                limit = start;
+                offset = pointer_offset(byte, low_byte);
+                break;
+            }
+
+            if !is_label(byte) {
+                return Err(DecodeError::from(DecodeErrorKind::InvalidLabelType).at(offset));
+            }
+
+            let end = offset + 1 + usize::from(byte);
+            if end > message.len() {
+                return Err(not_enough_bytes(offset + 1));
+            }
+            *length += 1 + usize::from(byte);
+            check_length(*length).map_err(|error| error.at(offset))?;
+            offset = end;
+        }
     }
 }
 
-fn take_label(value: &mut Peekable<Iter<'_, u8>>) -> Result<Vec<u8>, DecodeError> {
-    let length_of_label: usize = (*value.next().ok_or(DecodeError::NotEnoughBytes)?).into();
-    println!("Label is has lenght {length_of_label}");
-    let mut label: Vec<u8> = Vec::with_capacity(length_of_label);
-    for _ in 0..length_of_label {
-        let char = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
-        label.push(char);
+/// Writes domain names into a message, replacing suffixes that have been written before with a
+/// pointer to the earlier occurrence.
+///
+/// A single `Compressor` must be used for all names of a message, and `output` must start at the
+/// first byte of the message: the table stores offsets relative to the start of the message.
+#[derive(Default)]
+pub(crate) struct Compressor {
+    // Maps a suffix of a name to the offset at which it was written.
+    suffixes: HashMap<DomainName, u16>,
+}
+
+impl Compressor {
+    /// Append `name` to `output`.
+    pub(crate) fn write_name(&mut self, name: &DomainName, output: &mut Vec<u8>) {
+        for (index, label) in name.labels.iter().enumerate() {
+            let suffix = DomainName {
+                labels: name.labels[index..].to_vec(),
+            };
+            if let Some(offset) = self.suffixes.get(&suffix) {
+                output.extend_from_slice(&(offset | 0b1100_0000_0000_0000).to_be_bytes());
+                return;
+            }
+
+            // Pointers are 14 bits wide, so only the first 16 KiB of a message can be pointed to.
+            if let Ok(offset) = u16::try_from(output.len()) {
+                if offset <= 0b0011_1111_1111_1111 {
+                    self.suffixes.insert(suffix, offset);
+                }
+            }
+
+            output.push(label.len() as u8);
+            output.extend_from_slice(label);
+        }
+
+        output.push(0);
     }
+}
 
-    Ok(label)
+// The lower 14 bits of a pointer hold the offset from the start of the message.
+fn pointer_offset(high_byte: u8, low_byte: u8) -> usize {
+    usize::from(u16::from_be_bytes([high_byte & 0b0011_1111, low_byte]))
 }
 
 // A pointer can be detected by inspecting the first 2 bits. They must be high.
@@ -139,3 +553,210 @@ fn is_pointer(value: u8) -> bool {
 fn is_label(name: u8) -> bool {
     name & 0b1100_0000 == 0b0000_0000
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn name(value: &str) -> DomainName {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_print() {
+        assert!(name(".").is_root());
+        assert_eq!(name("orangetux.nl."), name("orangetux.nl"));
+        assert_eq!(name("orangetux.nl").to_string(), "orangetux.nl");
+        assert_eq!(name(r"a\.b\092.nl").labels()[0], b"a.b\\".to_vec());
+        assert_eq!(name(r"a\.b\092.nl").to_string(), r"a\.b\\.nl");
+        assert_eq!(name(r"\000.nl").to_string(), r"\000.nl");
+
+        assert!("".parse::<DomainName>().is_err());
+        assert!("orangetux..nl".parse::<DomainName>().is_err());
+        assert!(format!("{}.nl", "a".repeat(64))
+            .parse::<DomainName>()
+            .is_err());
+        assert!(vec!["a".repeat(63); 4]
+            .join(".")
+            .parse::<DomainName>()
+            .is_err());
+        assert!(r"\256.nl".parse::<DomainName>().is_err());
+    }
+
+    #[test]
+    fn test_parent() {
+        assert_eq!(
+            name("www.orangetux.nl").parent(),
+            Some(name("orangetux.nl"))
+        );
+        assert_eq!(name("nl").parent(), Some(DomainName::root()));
+        assert_eq!(DomainName::root().parent(), None);
+    }
+
+    #[test]
+    fn test_is_subdomain_of() {
+        assert!(name("www.orangetux.nl").is_subdomain_of(&name("OrangeTux.nl")));
+        assert!(name("orangetux.nl").is_subdomain_of(&name("orangetux.nl")));
+        assert!(name("orangetux.nl").is_subdomain_of(&DomainName::root()));
+        assert!(!name("orangetux.nl").is_subdomain_of(&name("www.orangetux.nl")));
+        assert!(!name("wwworangetux.nl").is_subdomain_of(&name("orangetux.nl")));
+    }
+
+    #[test]
+    fn test_case_insensitive_equality_and_hash() {
+        let mut names = std::collections::HashSet::new();
+        names.insert(name("OrangeTux.NL"));
+        assert!(names.contains(&name("orangetux.nl")));
+        assert_ne!(name("orangetux.nl"), name("www.orangetux.nl"));
+    }
+
+    #[test]
+    fn test_canonical_ordering() {
+        // The example of section 6.1 of RFC 4034.
+        let expected = vec![
+            name("example"),
+            name("a.example"),
+            name("yljkjljk.a.example"),
+            name(r"Z.a.example"),
+            name(r"zABC.a.EXAMPLE"),
+            name("z.example"),
+            name(r"\001.z.example"),
+            name("*.z.example"),
+            name(r"\200.z.example"),
+        ];
+        let mut names = expected.clone();
+        names.reverse();
+        names.sort();
+
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn test_take_name_follows_pointers() {
+        // A name at offset 0 ("nl"), a name at offset 4 ending in a pointer ("orangetux" + ptr to
+        // 0) and a name consisting of just a pointer to offset 4.
+        let message = [
+            2, 110, 108, 0, 9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 0xc0, 0, 0xc0, 4,
+        ];
+        let mut cursor = Cursor::new(&message);
+
+        assert_eq!(take_name(&mut cursor).unwrap(), name("nl"));
+        assert_eq!(take_name(&mut cursor).unwrap(), name("orangetux.nl"));
+        assert_eq!(
+            take_name(&mut cursor).unwrap().into_bytes(),
+            vec![9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0]
+        );
+        assert_eq!(cursor.remaining(), 0);
+    }
+
+    #[test]
+    fn test_name_ref_borrows_labels_from_message() {
+        let message = [
+            2, 110, 108, 0, 9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 0xc0, 0, 0,
+        ];
+        let mut cursor = Cursor::new(&message);
+        cursor.read_bytes(4).unwrap();
+
+        let name_ref = NameRef::parse(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), 16);
+        assert_eq!(name_ref.offset(), 4);
+        assert_eq!(
+            name_ref.labels().collect::<Vec<_>>(),
+            vec![&b"orangetux"[..], &b"nl"[..]]
+        );
+        assert_eq!(name_ref.to_string(), "orangetux.nl");
+        assert_eq!(name_ref, name("OrangeTux.nl"));
+        assert_ne!(name_ref, name("nl"));
+        assert_ne!(name_ref, name("www.orangetux.nl"));
+        assert_eq!(name_ref.to_domain_name(), name("orangetux.nl"));
+
+        let root = NameRef::parse(&mut cursor).unwrap();
+        assert_eq!(root.to_string(), ".");
+        assert_eq!(root, DomainName::root());
+    }
+
+    #[test]
+    fn test_compressor_writes_pointers_for_known_suffixes() {
+        let mut compressor = Compressor::default();
+        let mut output = vec![];
+
+        compressor.write_name(&name("orangetux.nl"), &mut output);
+        compressor.write_name(&name("www.OrangeTux.nl"), &mut output);
+        compressor.write_name(&name("nl"), &mut output);
+        compressor.write_name(&name("example.com"), &mut output);
+
+        assert_eq!(
+            output,
+            [
+                vec![9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0],
+                vec![3, 119, 119, 119, 0xc0, 0],
+                vec![0xc0, 10],
+                vec![7, 101, 120, 97, 109, 112, 108, 101, 3, 99, 111, 109, 0],
+            ]
+            .concat()
+        );
+
+        // Decoding the output yields the original names.
+        let mut cursor = Cursor::new(&output);
+        let names: Vec<DomainName> = (0..4).map(|_| take_name(&mut cursor).unwrap()).collect();
+        assert_eq!(
+            names,
+            vec![
+                name("orangetux.nl"),
+                name("www.orangetux.nl"),
+                name("nl"),
+                name("example.com")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_take_name_rejects_pointer_loops() {
+        let decode = |message: &[u8]| {
+            take_name(&mut Cursor::new(message))
+                .map_err(|error| (error.kind, error.offset.unwrap()))
+        };
+
+        // A pointer pointing to itself.
+        assert_eq!(
+            decode(&[0xc0, 0]),
+            Err((DecodeErrorKind::InvalidPointer, 0))
+        );
+
+        // A label followed by a pointer pointing back to that label.
+        assert_eq!(
+            decode(&[1, 97, 0xc0, 0]),
+            Err((DecodeErrorKind::InvalidPointer, 2))
+        );
+
+        // A pointer pointing forward.
+        assert_eq!(
+            decode(&[0xc0, 2, 0]),
+            Err((DecodeErrorKind::InvalidPointer, 0))
+        );
+    }
+
+    #[test]
+    fn test_take_name_reports_offset_of_errors() {
+        let decode = |message: &[u8]| {
+            take_name(&mut Cursor::new(message))
+                .map_err(|error| (error.kind, error.offset.unwrap()))
+        };
+
+        assert_eq!(
+            decode(&[1, 97, 0b0100_0000]),
+            Err((DecodeErrorKind::InvalidLabelType, 2))
+        );
+        assert_eq!(
+            decode(&[1, 97, 3, 98]),
+            Err((DecodeErrorKind::NotEnoughBytes, 3))
+        );
+
+        // 5 labels of 63 bytes exceed the maximum length of a name.
+        let message = [vec![63; 64 * 4].as_slice(), &[63; 64]].concat();
+        assert_eq!(
+            decode(&message),
+            Err((DecodeErrorKind::NameTooLong, 64 * 3))
+        );
+    }
+}
@@ -0,0 +1,945 @@
+//! Serialize and deserialize the EDNS(0) OPT pseudo-record.
+//!
+//! EDNS extends DNS messages with a larger UDP payload size, a 12-bit response code, the DNSSEC
+//! OK bit and options. It's carried by a single OPT record in the additional section. `Message`
+//! takes the OPT record out of the additional section when decoding and puts it back when
+//! encoding, so it's never seen as a regular record.
+//!
+//! For more info, see [`RFC 6891`].
+//!
+//! [`RFC 6891`]: https://www.rfc-editor.org/rfc/rfc6891
+use crate::cursor::Cursor;
+use crate::header::Type as MessageType;
+use crate::resource_record::{RecordRef, Type};
+use crate::{write_length, DecodeError, DecodeErrorKind, EncodeError};
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The version of EDNS this crate implements.
+pub const VERSION: u8 = 0;
+
+/// The UDP payload size advertised by default. It avoids fragmentation on common links, see
+/// <https://www.dnsflagday.net/2020/>.
+pub const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// The largest UDP payload every client accepts. It's the limit for messages without EDNS, see
+/// section 4.2.1 of RFC 1035.
+pub const MINIMUM_UDP_PAYLOAD_SIZE: u16 = 512;
+
+/// The block length queries are padded to by `PaddingPolicy::Recommended`.
+pub const QUERY_BLOCK_LENGTH: u16 = 128;
+
+/// The block length responses are padded to by `PaddingPolicy::Recommended`.
+pub const RESPONSE_BLOCK_LENGTH: u16 = 468;
+
+/// The EDNS information of a message, as carried by the OPT pseudo-record. See section
+/// [`6.1. OPT Record Definition`] of RFC 6891.
+///
+/// The OPT record also holds the upper 8 bits of the response code. They're merged into
+/// `Header::r_code`, which holds the complete 12-bit response code.
+///
+/// [`6.1. OPT Record Definition`]: https://www.rfc-editor.org/rfc/rfc6891#section-6.1
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Edns {
+    /// The number of bytes of the largest UDP payload the sender can reassemble and deliver.
+    pub udp_payload_size: u16,
+
+    /// The version of EDNS the sender implements.
+    pub version: u8,
+
+    /// Whether the sender is able to accept DNSSEC security records. See RFC 3225.
+    pub dnssec_ok: bool,
+
+    /// The options, in the order they appear in the message.
+    pub options: Vec<EdnsOption>,
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Self {
+            udp_payload_size: DEFAULT_UDP_PAYLOAD_SIZE,
+            version: VERSION,
+            dnssec_ok: false,
+            options: vec![],
+        }
+    }
+}
+
+impl Edns {
+    /// Decode the OPT record `record`.
+    ///
+    /// The OPT record must be owned by the root and its options must fill the rdata exactly.
+    pub(crate) fn decode(record: &RecordRef<'_>) -> Result<Self, DecodeError> {
+        if !record.name().is_root() {
+            return Err(DecodeError::from(DecodeErrorKind::IllegalValue(
+                "failed to parse OPT record: owner name is not the root".to_string(),
+            ))
+            .at(record.name().offset()));
+        }
+
+        let mut value = record.rdata_cursor();
+        let mut options = vec![];
+        while value.remaining() > 0 {
+            options.push(EdnsOption::decode(&mut value)?);
+        }
+
+        Ok(Self {
+            udp_payload_size: record.class().into(),
+            version: (record.ttl() >> 16) as u8,
+            dnssec_ok: record.ttl() & 0x0000_8000 != 0,
+            options,
+        })
+    }
+
+    /// The Client Subnet option, if any.
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.options.iter().find_map(|option| match option {
+            EdnsOption::ClientSubnet(subnet) => Some(subnet),
+            _ => None,
+        })
+    }
+
+    /// The Cookie option, if any.
+    pub fn cookie(&self) -> Option<&Cookie> {
+        self.options.iter().find_map(|option| match option {
+            EdnsOption::Cookie(cookie) => Some(cookie),
+            _ => None,
+        })
+    }
+
+    /// The Extended DNS Error options, in the order they appear in the message.
+    pub fn extended_errors(&self) -> impl Iterator<Item = &ExtendedError> {
+        self.options.iter().filter_map(|option| match option {
+            EdnsOption::ExtendedError(error) => Some(error),
+            _ => None,
+        })
+    }
+
+    /// The data of the NSID option, if any. It's empty in queries.
+    pub fn nsid(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|option| match option {
+            EdnsOption::Nsid(nsid) => Some(nsid.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Serialize the instance as an OPT record into `output`, with `extended_rcode` as the upper
+    /// 8 bits of the response code.
+    pub(crate) fn encode(
+        self,
+        extended_rcode: u8,
+        output: &mut Vec<u8>,
+    ) -> Result<(), EncodeError> {
+        // The owner name is the root.
+        output.push(0);
+        output.extend_from_slice(&u16::from(Type::OPT).to_be_bytes());
+        output.extend_from_slice(&self.udp_payload_size.to_be_bytes());
+
+        let ttl = u32::from(extended_rcode) << 24
+            | u32::from(self.version) << 16
+            | u32::from(self.dnssec_ok) << 15;
+        output.extend_from_slice(&ttl.to_be_bytes());
+
+        let rdlength_position = output.len();
+        output.extend_from_slice(&[0, 0]);
+        for option in self.options {
+            option.encode(output)?;
+        }
+
+        write_length(output, rdlength_position)
+    }
+}
+
+/// An option of an OPT record. See section 6.1.2 of RFC 6891.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum EdnsOption {
+    /// In a query, a request for the identifier of the name server. In a response, that
+    /// identifier. See RFC 5001.
+    Nsid(Vec<u8>),
+    /// The subnet of the client on whose behalf a resolver sends a query. See RFC 7871.
+    ClientSubnet(ClientSubnet),
+    /// A client cookie and possibly a server cookie. See RFC 7873.
+    Cookie(Cookie),
+    /// The given number of padding bytes, increasing the size of the message. See RFC 7830 and
+    /// `PaddingPolicy`.
+    Padding(u16),
+    /// Additional information about the cause of an error. See RFC 8914.
+    ExtendedError(ExtendedError),
+    /// An option this crate doesn't know about.
+    Unknown {
+        /// The option code.
+        code: u16,
+        /// The option data.
+        data: Vec<u8>,
+    },
+}
+
+impl EdnsOption {
+    /// The option code.
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Nsid(_) => 3,
+            Self::ClientSubnet(_) => 8,
+            Self::Cookie(_) => 10,
+            Self::Padding(_) => 12,
+            Self::ExtendedError(_) => 15,
+            Self::Unknown { code, .. } => *code,
+        }
+    }
+
+    fn decode(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let code = value.read_u16()?;
+        let length = value.read_u16()?;
+        let mut data = value.split(length.into())?;
+
+        let option = match code {
+            3 => Self::Nsid(data.read_bytes(data.remaining())?.to_vec()),
+            8 => Self::ClientSubnet(ClientSubnet::decode(&mut data)?),
+            10 => Self::Cookie(Cookie::decode(&mut data)?),
+            12 => {
+                // The padding bytes should be zero, but receivers must accept any value.
+                data.read_bytes(data.remaining())?;
+                Self::Padding(length)
+            }
+            15 => Self::ExtendedError(ExtendedError::decode(&mut data)?),
+            _ => Self::Unknown {
+                code,
+                data: data.read_bytes(data.remaining())?.to_vec(),
+            },
+        };
+
+        if data.remaining() != 0 {
+            return Err(illegal_option(code).at(data.position()));
+        }
+
+        Ok(option)
+    }
+
+    fn encode(self, output: &mut Vec<u8>) -> Result<(), EncodeError> {
+        output.extend_from_slice(&self.code().to_be_bytes());
+
+        let length_position = output.len();
+        output.extend_from_slice(&[0, 0]);
+        match self {
+            Self::Nsid(nsid) => output.extend(nsid),
+            Self::ClientSubnet(subnet) => subnet.encode(output),
+            Self::Cookie(cookie) => cookie.encode(output),
+            Self::Padding(length) => output.resize(output.len() + usize::from(length), 0),
+            Self::ExtendedError(error) => error.encode(output),
+            Self::Unknown { data, .. } => output.extend(data),
+        }
+
+        write_length(output, length_position)
+    }
+}
+
+/// The EDNS Client Subnet option of section [`6. Option Format`] of RFC 7871.
+///
+/// A resolver sends the subnet of its client, as `address` and `source_prefix_length`, so that
+/// an authoritative server can tailor its answer to the location of the client. The server
+/// returns the subnet and, as `scope_prefix_length`, the number of leading bits of the address
+/// its answer depends on.
+///
+/// The bits of `address` beyond the source prefix are always zero.
+///
+/// ```
+/// use dns::edns::ClientSubnet;
+///
+/// let subnet = ClientSubnet::new("192.0.2.123".parse().unwrap(), 24).unwrap();
+/// assert_eq!(subnet.address(), "192.0.2.0".parse::<std::net::IpAddr>().unwrap());
+///
+/// let subnet = subnet.with_scope_prefix_length(16).unwrap();
+/// assert_eq!(subnet.scope_prefix_length(), 16);
+/// ```
+///
+/// [`6. Option Format`]: https://www.rfc-editor.org/rfc/rfc7871#section-6
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix_length: u8,
+    scope_prefix_length: u8,
+}
+
+impl ClientSubnet {
+    /// Create the option for the subnet of `address` with a prefix of `source_prefix_length`
+    /// bits. The bits of `address` beyond the prefix are cleared. The scope prefix length is 0.
+    ///
+    /// Fails if the prefix is longer than the address.
+    pub fn new(address: IpAddr, source_prefix_length: u8) -> Result<Self, DecodeError> {
+        check_prefix_length(&address, source_prefix_length)?;
+
+        let mut bytes = address_bytes(&address);
+        clear_host_bits(&mut bytes, source_prefix_length);
+
+        Ok(Self {
+            address: to_address(&address, &bytes),
+            source_prefix_length,
+            scope_prefix_length: 0,
+        })
+    }
+
+    /// Return the option with the scope prefix length set to `scope_prefix_length`.
+    ///
+    /// Fails if the prefix is longer than the address.
+    pub fn with_scope_prefix_length(
+        mut self,
+        scope_prefix_length: u8,
+    ) -> Result<Self, DecodeError> {
+        check_prefix_length(&self.address, scope_prefix_length)?;
+        self.scope_prefix_length = scope_prefix_length;
+
+        Ok(self)
+    }
+
+    /// The address of the subnet.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The number of leading bits of the address that make up the subnet.
+    pub fn source_prefix_length(&self) -> u8 {
+        self.source_prefix_length
+    }
+
+    /// The number of leading bits of the address the answer depends on.
+    pub fn scope_prefix_length(&self) -> u8 {
+        self.scope_prefix_length
+    }
+
+    /// The address family, as assigned by IANA: 1 for IPv4 and 2 for IPv6.
+    pub fn family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(_) => 1,
+            IpAddr::V6(_) => 2,
+        }
+    }
+
+    fn decode(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let position = value.position();
+        let address = match value.read_u16()? {
+            1 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            2 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            _ => return Err(illegal_option(8).at(position)),
+        };
+
+        let position = value.position();
+        let source_prefix_length = value.read_u8()?;
+        let scope_prefix_length = value.read_u8()?;
+        check_prefix_length(&address, source_prefix_length)
+            .and(check_prefix_length(&address, scope_prefix_length))
+            .map_err(|error| error.at(position))?;
+
+        // The address is truncated to the bytes that hold the source prefix, and the bits beyond
+        // the prefix must be zero. See section 6 of RFC 7871.
+        let position = value.position();
+        let truncated = value.read_bytes(value.remaining())?;
+        let mut bytes = address_bytes(&address);
+        if truncated.len() != usize::from(source_prefix_length).div_ceil(8) {
+            return Err(illegal_option(8).at(position));
+        }
+        bytes[..truncated.len()].copy_from_slice(truncated);
+
+        let mut cleared = bytes.clone();
+        clear_host_bits(&mut cleared, source_prefix_length);
+        if cleared != bytes {
+            return Err(illegal_option(8).at(position));
+        }
+
+        Ok(Self {
+            address: to_address(&address, &bytes),
+            source_prefix_length,
+            scope_prefix_length,
+        })
+    }
+
+    fn encode(self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.family().to_be_bytes());
+        output.push(self.source_prefix_length);
+        output.push(self.scope_prefix_length);
+
+        let length = usize::from(self.source_prefix_length).div_ceil(8);
+        output.extend_from_slice(&address_bytes(&self.address)[..length]);
+    }
+}
+
+/// The Cookie option of section [`4. DNS Cookie Option`] of RFC 7873.
+///
+/// A client sends its 8 byte client cookie with every query to a server. The server returns it
+/// with a server cookie of 8 to 32 bytes, which the client includes in later queries. See
+/// `cookie::ServerCookies` for generating and validating server cookies.
+///
+/// [`4. DNS Cookie Option`]: https://www.rfc-editor.org/rfc/rfc7873#section-4
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Cookie {
+    client: [u8; 8],
+    server: Option<Vec<u8>>,
+}
+
+impl Cookie {
+    /// Create the option with just a client cookie.
+    pub fn new(client: [u8; 8]) -> Self {
+        Self {
+            client,
+            server: None,
+        }
+    }
+
+    /// Create the option with a client cookie and a server cookie.
+    ///
+    /// Fails if the server cookie isn't 8 to 32 bytes long.
+    pub fn with_server_cookie(client: [u8; 8], server: Vec<u8>) -> Result<Self, DecodeError> {
+        if !(8..=32).contains(&server.len()) {
+            return Err(DecodeErrorKind::IllegalValue(format!(
+                "failed to parse server cookie: length of {} bytes is not between 8 and 32",
+                server.len()
+            ))
+            .into());
+        }
+
+        Ok(Self {
+            client,
+            server: Some(server),
+        })
+    }
+
+    /// The client cookie.
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    /// The server cookie, if any.
+    pub fn server(&self) -> Option<&[u8]> {
+        self.server.as_deref()
+    }
+
+    fn decode(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let position = value.position();
+        let client = value.read_array::<8>()?;
+        if value.remaining() == 0 {
+            return Ok(Self::new(client));
+        }
+
+        let server = value.read_bytes(value.remaining())?.to_vec();
+        Self::with_server_cookie(client, server).map_err(|_| illegal_option(10).at(position))
+    }
+
+    fn encode(self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.client);
+        if let Some(server) = self.server {
+            output.extend(server);
+        }
+    }
+}
+
+/// How `Message::into_padded_bytes()` pads messages.
+///
+/// Padding hides the length of messages sent over encrypted transports, like DNS over TLS, from
+/// observers. Block-Length Padding rounds the length up to a multiple of a block length, see
+/// [`RFC 8467`].
+///
+/// [`RFC 8467`]: https://www.rfc-editor.org/rfc/rfc8467
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PaddingPolicy {
+    /// Don't pad.
+    #[default]
+    None,
+    /// Pad to a multiple of the given number of bytes.
+    BlockLength(u16),
+    /// Pad queries to a multiple of `QUERY_BLOCK_LENGTH` and responses to a multiple of
+    /// `RESPONSE_BLOCK_LENGTH` bytes, as recommended by section 4.1 of RFC 8467.
+    Recommended,
+}
+
+impl PaddingPolicy {
+    /// The number of bytes a message of `message_type` is padded to a multiple of, if any.
+    pub fn block_length(self, message_type: &MessageType) -> Option<u16> {
+        let block_length = match (self, message_type) {
+            (Self::None, _) => return None,
+            (Self::BlockLength(block_length), _) => block_length,
+            (Self::Recommended, MessageType::Query) => QUERY_BLOCK_LENGTH,
+            (Self::Recommended, MessageType::Reply) => RESPONSE_BLOCK_LENGTH,
+        };
+
+        (block_length > 1).then_some(block_length)
+    }
+}
+
+/// Append a Padding option to the OPT record starting at `opt_position`, the last record of the
+/// message in `output`, so the length of the message becomes a multiple of `block_length`.
+///
+/// The message is left as is when the rdata of the OPT record can't hold the padding.
+pub(crate) fn pad(output: &mut Vec<u8>, opt_position: usize, block_length: u16) {
+    let block_length = usize::from(block_length);
+
+    // The option code and length take 4 bytes.
+    let length = (block_length - (output.len() + 4) % block_length) % block_length;
+
+    // The rdlength follows the root name, the type, the class and the TTL of the OPT record.
+    let rdlength_position = opt_position + 9;
+    let rdlength = u16::from_be_bytes([output[rdlength_position], output[rdlength_position + 1]]);
+    let (Ok(rdlength), Ok(length)) = (
+        u16::try_from(usize::from(rdlength) + 4 + length),
+        u16::try_from(length),
+    ) else {
+        return;
+    };
+
+    if EdnsOption::Padding(length).encode(output).is_ok() {
+        output[rdlength_position..rdlength_position + 2].copy_from_slice(&rdlength.to_be_bytes());
+    }
+}
+
+/// The Extended DNS Error option of section [`2. Extended DNS Error EDNS0 Option Format`] of RFC
+/// 8914.
+///
+/// It tells why a query failed, or why an answer is not what the client might expect, beyond
+/// what the response code can express. A response may hold several.
+///
+/// ```
+/// use dns::edns::{ExtendedError, InfoCode};
+///
+/// let error = ExtendedError::new(InfoCode::Blocked, "blocked by policy");
+/// assert_eq!(error.to_string(), "15 (Blocked): blocked by policy");
+/// ```
+///
+/// [`2. Extended DNS Error EDNS0 Option Format`]: https://www.rfc-editor.org/rfc/rfc8914#section-2
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ExtendedError {
+    /// The reason of the error.
+    pub info_code: InfoCode,
+
+    /// Text meant for humans troubleshooting the error. It may be empty.
+    pub extra_text: String,
+}
+
+impl ExtendedError {
+    /// Create the option.
+    pub fn new(info_code: InfoCode, extra_text: impl Into<String>) -> Self {
+        Self {
+            info_code,
+            extra_text: extra_text.into(),
+        }
+    }
+
+    fn decode(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let info_code = value.read_u16()?.into();
+
+        // The text is informational, so invalid UTF-8 doesn't render the option useless.
+        let extra_text = value.read_bytes(value.remaining())?;
+        let extra_text = String::from_utf8_lossy(extra_text).into_owned();
+
+        Ok(Self {
+            info_code,
+            extra_text,
+        })
+    }
+
+    fn encode(self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&u16::from(self.info_code).to_be_bytes());
+        output.extend(self.extra_text.into_bytes());
+    }
+}
+
+impl Display for ExtendedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = u16::from(self.info_code.clone());
+        write!(f, "{} ({})", code, self.info_code)?;
+        if !self.extra_text.is_empty() {
+            write!(f, ": {}", self.extra_text)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The INFO-CODE of an Extended DNS Error. See the [`Extended DNS Error Codes`] registry of IANA.
+///
+/// [`Extended DNS Error Codes`]: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#extended-dns-error-codes
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum InfoCode {
+    /// An error that matches none of the other codes. The extra text should explain it.
+    Other,
+    /// A DNSKEY RRset uses an unsupported algorithm.
+    UnsupportedDnskeyAlgorithm,
+    /// A DS RRset uses an unsupported digest type.
+    UnsupportedDsDigestType,
+    /// The answer was served from a cache past its TTL. See RFC 8767.
+    StaleAnswer,
+    /// The answer was forged by policy, for example to block a domain.
+    ForgedAnswer,
+    /// DNSSEC validation ended in the Indeterminate state.
+    DnssecIndeterminate,
+    /// DNSSEC validation ended in the Bogus state.
+    DnssecBogus,
+    /// The signatures of the answer have expired.
+    SignatureExpired,
+    /// The signatures of the answer are not yet valid.
+    SignatureNotYetValid,
+    /// A DS record exists, but no matching DNSKEY record.
+    DnskeyMissing,
+    /// Signatures are expected, but none were found.
+    RrsigsMissing,
+    /// No DNSKEY record has the Zone Key bit set.
+    NoZoneKeyBitSet,
+    /// The denial of existence can't be validated for lack of NSEC or NSEC3 records.
+    NsecMissing,
+    /// The error was served from a cache.
+    CachedError,
+    /// The server isn't ready to serve the zone yet.
+    NotReady,
+    /// The domain is on a blocklist of the operator of the server.
+    Blocked,
+    /// The domain is blocked due to an external requirement.
+    Censored,
+    /// The domain is blocked at the request of the client.
+    Filtered,
+    /// The client is not authorized to query the server.
+    Prohibited,
+    /// A NXDOMAIN answer was served from a cache past its TTL.
+    StaleNxdomainAnswer,
+    /// The server is not authoritative and doesn't perform recursion.
+    NotAuthoritative,
+    /// The requested operation or query is not supported.
+    NotSupported,
+    /// None of the authoritative servers for the domain could be reached.
+    NoReachableAuthority,
+    /// An unrecoverable network error occurred while talking to an authoritative server.
+    NetworkError,
+    /// The data of an authoritative server is invalid.
+    InvalidData,
+    /// A code this crate doesn't know about.
+    Unknown(u16),
+}
+
+impl From<u16> for InfoCode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => Self::Other,
+            1 => Self::UnsupportedDnskeyAlgorithm,
+            2 => Self::UnsupportedDsDigestType,
+            3 => Self::StaleAnswer,
+            4 => Self::ForgedAnswer,
+            5 => Self::DnssecIndeterminate,
+            6 => Self::DnssecBogus,
+            7 => Self::SignatureExpired,
+            8 => Self::SignatureNotYetValid,
+            9 => Self::DnskeyMissing,
+            10 => Self::RrsigsMissing,
+            11 => Self::NoZoneKeyBitSet,
+            12 => Self::NsecMissing,
+            13 => Self::CachedError,
+            14 => Self::NotReady,
+            15 => Self::Blocked,
+            16 => Self::Censored,
+            17 => Self::Filtered,
+            18 => Self::Prohibited,
+            19 => Self::StaleNxdomainAnswer,
+            20 => Self::NotAuthoritative,
+            21 => Self::NotSupported,
+            22 => Self::NoReachableAuthority,
+            23 => Self::NetworkError,
+            24 => Self::InvalidData,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<InfoCode> for u16 {
+    fn from(value: InfoCode) -> Self {
+        match value {
+            InfoCode::Other => 0,
+            InfoCode::UnsupportedDnskeyAlgorithm => 1,
+            InfoCode::UnsupportedDsDigestType => 2,
+            InfoCode::StaleAnswer => 3,
+            InfoCode::ForgedAnswer => 4,
+            InfoCode::DnssecIndeterminate => 5,
+            InfoCode::DnssecBogus => 6,
+            InfoCode::SignatureExpired => 7,
+            InfoCode::SignatureNotYetValid => 8,
+            InfoCode::DnskeyMissing => 9,
+            InfoCode::RrsigsMissing => 10,
+            InfoCode::NoZoneKeyBitSet => 11,
+            InfoCode::NsecMissing => 12,
+            InfoCode::CachedError => 13,
+            InfoCode::NotReady => 14,
+            InfoCode::Blocked => 15,
+            InfoCode::Censored => 16,
+            InfoCode::Filtered => 17,
+            InfoCode::Prohibited => 18,
+            InfoCode::StaleNxdomainAnswer => 19,
+            InfoCode::NotAuthoritative => 20,
+            InfoCode::NotSupported => 21,
+            InfoCode::NoReachableAuthority => 22,
+            InfoCode::NetworkError => 23,
+            InfoCode::InvalidData => 24,
+            InfoCode::Unknown(value) => value,
+        }
+    }
+}
+
+impl Display for InfoCode {
+    // The purposes listed by the registry of IANA, as shown by `dig`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let purpose = match self {
+            Self::Other => "Other",
+            Self::UnsupportedDnskeyAlgorithm => "Unsupported DNSKEY Algorithm",
+            Self::UnsupportedDsDigestType => "Unsupported DS Digest Type",
+            Self::StaleAnswer => "Stale Answer",
+            Self::ForgedAnswer => "Forged Answer",
+            Self::DnssecIndeterminate => "DNSSEC Indeterminate",
+            Self::DnssecBogus => "DNSSEC Bogus",
+            Self::SignatureExpired => "Signature Expired",
+            Self::SignatureNotYetValid => "Signature Not Yet Valid",
+            Self::DnskeyMissing => "DNSKEY Missing",
+            Self::RrsigsMissing => "RRSIGs Missing",
+            Self::NoZoneKeyBitSet => "No Zone Key Bit Set",
+            Self::NsecMissing => "NSEC Missing",
+            Self::CachedError => "Cached Error",
+            Self::NotReady => "Not Ready",
+            Self::Blocked => "Blocked",
+            Self::Censored => "Censored",
+            Self::Filtered => "Filtered",
+            Self::Prohibited => "Prohibited",
+            Self::StaleNxdomainAnswer => "Stale NXDOMAIN Answer",
+            Self::NotAuthoritative => "Not Authoritative",
+            Self::NotSupported => "Not Supported",
+            Self::NoReachableAuthority => "No Reachable Authority",
+            Self::NetworkError => "Network Error",
+            Self::InvalidData => "Invalid Data",
+            Self::Unknown(_) => "Unknown",
+        };
+
+        write!(f, "{}", purpose)
+    }
+}
+
+fn illegal_option(code: u16) -> DecodeError {
+    DecodeErrorKind::IllegalValue(format!(
+        "failed to parse EDNS option: data of option {} is malformed",
+        code
+    ))
+    .into()
+}
+
+fn check_prefix_length(address: &IpAddr, prefix_length: u8) -> Result<(), DecodeError> {
+    let maximum = match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+
+    if prefix_length > maximum {
+        return Err(DecodeErrorKind::IllegalValue(format!(
+            "failed to parse prefix length: {} exceeds the length of the address",
+            prefix_length
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+fn address_bytes(address: &IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(address) => address.octets().to_vec(),
+        IpAddr::V6(address) => address.octets().to_vec(),
+    }
+}
+
+// Create an address of the same family as `family` from `bytes`.
+fn to_address(family: &IpAddr, bytes: &[u8]) -> IpAddr {
+    match family {
+        IpAddr::V4(_) => IpAddr::from(<[u8; 4]>::try_from(bytes).unwrap_or_default()),
+        IpAddr::V6(_) => IpAddr::from(<[u8; 16]>::try_from(bytes).unwrap_or_default()),
+    }
+}
+
+// Clear the bits following the first `prefix_length` bits.
+fn clear_host_bits(bytes: &mut [u8], prefix_length: u8) {
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        let bits = usize::from(prefix_length).saturating_sub(index * 8).min(8);
+        *byte &= !(0xff_u8.checked_shr(bits as u32).unwrap_or(0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialization_and_deserialization() {
+        let edns = Edns {
+            udp_payload_size: 4096,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![
+                EdnsOption::Unknown {
+                    code: 65001,
+                    data: vec![],
+                },
+                EdnsOption::Unknown {
+                    code: 65002,
+                    data: vec![1],
+                },
+            ],
+        };
+
+        let mut bytes = vec![];
+        edns.clone().encode(1, &mut bytes).unwrap();
+        assert_eq!(
+            bytes,
+            [0, 0, 41, 16, 0, 1, 0, 128, 0, 0, 9, 253, 233, 0, 0, 253, 234, 0, 1, 1]
+        );
+
+        let record = RecordRef::parse(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(Edns::decode(&record).unwrap(), edns);
+
+        // The last option claims more data than the rdata holds.
+        bytes[10] = 8;
+        let record = RecordRef::parse(&mut Cursor::new(&bytes[..19])).unwrap();
+        let error = Edns::decode(&record).unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::NotEnoughBytes);
+        assert_eq!(error.offset, Some(19));
+    }
+
+    fn decode_option(data: &[u8]) -> Result<EdnsOption, DecodeError> {
+        EdnsOption::decode(&mut Cursor::new(data))
+    }
+
+    #[test]
+    fn test_client_subnet() {
+        let subnet = ClientSubnet::new("192.0.2.123".parse().unwrap(), 20).unwrap();
+        assert_eq!(subnet.address(), "192.0.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(subnet.family(), 1);
+
+        // The address is truncated to 3 bytes.
+        let mut bytes = vec![];
+        EdnsOption::ClientSubnet(subnet.clone())
+            .encode(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, [0, 8, 0, 7, 0, 1, 20, 0, 192, 0, 0]);
+        assert_eq!(
+            decode_option(&bytes).unwrap(),
+            EdnsOption::ClientSubnet(subnet)
+        );
+
+        let subnet = ClientSubnet::new("2001:db8::1".parse().unwrap(), 0)
+            .unwrap()
+            .with_scope_prefix_length(56)
+            .unwrap();
+        let mut bytes = vec![];
+        EdnsOption::ClientSubnet(subnet.clone())
+            .encode(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, [0, 8, 0, 4, 0, 2, 0, 56]);
+        assert_eq!(
+            decode_option(&bytes).unwrap(),
+            EdnsOption::ClientSubnet(subnet)
+        );
+
+        assert!(ClientSubnet::new("192.0.2.1".parse().unwrap(), 33).is_err());
+        assert!(ClientSubnet::new("2001:db8::1".parse().unwrap(), 128)
+            .unwrap()
+            .with_scope_prefix_length(129)
+            .is_err());
+    }
+
+    #[test]
+    fn test_client_subnet_validation() {
+        // Host bits beyond the prefix of 20 bits are set.
+        let error = decode_option(&[0, 8, 0, 7, 0, 1, 20, 0, 192, 0, 8]).unwrap_err();
+        assert_eq!(error.offset, Some(8));
+
+        // The address must be truncated to the prefix.
+        assert!(decode_option(&[0, 8, 0, 8, 0, 1, 20, 0, 192, 0, 0, 0]).is_err());
+        assert!(decode_option(&[0, 8, 0, 6, 0, 1, 20, 0, 192, 0]).is_err());
+
+        // Unknown family and prefix longer than the address.
+        assert!(decode_option(&[0, 8, 0, 4, 0, 3, 0, 0]).is_err());
+        assert!(decode_option(&[0, 8, 0, 4, 0, 1, 0, 33]).is_err());
+    }
+
+    #[test]
+    fn test_cookie() {
+        let cookie = Cookie::with_server_cookie([1; 8], vec![2; 16]).unwrap();
+        let mut bytes = vec![];
+        EdnsOption::Cookie(cookie.clone())
+            .encode(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes[..4], [0, 10, 0, 24]);
+        assert_eq!(decode_option(&bytes).unwrap(), EdnsOption::Cookie(cookie));
+
+        let cookie = Cookie::new([1; 8]);
+        let mut bytes = vec![];
+        EdnsOption::Cookie(cookie.clone())
+            .encode(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, [0, 10, 0, 8, 1, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(decode_option(&bytes).unwrap(), EdnsOption::Cookie(cookie));
+
+        // Client cookies are 8 bytes, server cookies 8 to 32 bytes.
+        assert!(decode_option(&[0, 10, 0, 4, 1, 1, 1, 1]).is_err());
+        assert!(decode_option(&[[0, 10, 0, 12].as_slice(), &[1; 12]].concat()).is_err());
+        assert!(decode_option(&[[0, 10, 0, 41].as_slice(), &[1; 41]].concat()).is_err());
+        assert!(Cookie::with_server_cookie([1; 8], vec![2; 33]).is_err());
+    }
+
+    #[test]
+    fn test_extended_error_and_nsid() {
+        let edns = Edns {
+            options: vec![
+                EdnsOption::Nsid(b"ns1.example".to_vec()),
+                EdnsOption::ExtendedError(ExtendedError::new(InfoCode::DnssecBogus, "")),
+                EdnsOption::ExtendedError(ExtendedError::new(InfoCode::StaleAnswer, "ünïcode")),
+            ],
+            ..Edns::default()
+        };
+        let mut bytes = vec![];
+        edns.clone().encode(0, &mut bytes).unwrap();
+        assert_eq!(bytes[26..32], [0, 15, 0, 2, 0, 6]);
+
+        let record = RecordRef::parse(&mut Cursor::new(&bytes)).unwrap();
+        let decoded = Edns::decode(&record).unwrap();
+        assert_eq!(decoded, edns);
+        assert_eq!(decoded.nsid(), Some(b"ns1.example".as_slice()));
+        assert_eq!(
+            decoded
+                .extended_errors()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>(),
+            ["6 (DNSSEC Bogus)", "3 (Stale Answer): ünïcode"]
+        );
+
+        // Invalid UTF-8 is replaced rather than rejected, unknown codes are kept.
+        let option = decode_option(&[0, 15, 0, 3, 0x01, 0x00, 0xff]).unwrap();
+        assert_eq!(
+            option,
+            EdnsOption::ExtendedError(ExtendedError::new(InfoCode::Unknown(256), "\u{fffd}"))
+        );
+        assert!(decode_option(&[0, 15, 0, 1, 0]).is_err());
+
+        for code in 0..=u16::MAX {
+            assert_eq!(u16::from(InfoCode::from(code)), code);
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut bytes = vec![];
+        EdnsOption::Padding(3).encode(&mut bytes).unwrap();
+        assert_eq!(bytes, [0, 12, 0, 3, 0, 0, 0]);
+        assert_eq!(decode_option(&bytes).unwrap(), EdnsOption::Padding(3));
+
+        // Padding bytes that aren't zero are accepted.
+        assert_eq!(
+            decode_option(&[0, 12, 0, 2, 1, 2]).unwrap(),
+            EdnsOption::Padding(2)
+        );
+
+        let policy = PaddingPolicy::Recommended;
+        assert_eq!(policy.block_length(&MessageType::Query), Some(128));
+        assert_eq!(policy.block_length(&MessageType::Reply), Some(468));
+        let policy = PaddingPolicy::BlockLength(1);
+        assert_eq!(policy.block_length(&MessageType::Query), None);
+        assert_eq!(PaddingPolicy::None.block_length(&MessageType::Query), None);
+    }
+}
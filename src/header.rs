@@ -1,7 +1,6 @@
 //! Serialize and deserialize `Header`s.
-use crate::DecodeError;
-use std::iter::Peekable;
-use std::slice::Iter;
+use crate::cursor::Cursor;
+use crate::{DecodeError, DecodeErrorKind};
 
 /// Header of a DNS message.
 ///
@@ -33,14 +32,24 @@ pub struct Header {
     /// May only be `true` in responses.
     pub recursion_available: bool,
 
-    /// Reserved for future use. Must be 0.
-    pub z: u8,
+    /// Whether all data in the answer and authority sections has been authenticated by the
+    /// server according to its DNSSEC policies. See section 3.2.3 of RFC 4035.
+    pub authentic_data: bool,
+
+    /// Whether the resolver should skip DNSSEC validation. See section 3.2.2 of RFC 4035.
+    pub checking_disabled: bool,
 
     /// Response code.
     /// May only be set in response.
+    ///
+    /// Response codes above 15 need an EDNS OPT record in the message to carry their upper 8
+    /// bits. `Header::into_bytes()` only writes the lower 4 bits.
     pub r_code: ResponseCode,
 
     /// Number of questions.
+    ///
+    /// The counts are derived from the sections when serializing a `Message`, see
+    /// `Message::into_bytes()`.
     pub qd_count: u16,
 
     /// Number of answers resource records.
@@ -53,42 +62,36 @@ pub struct Header {
     pub ar_count: u16,
 }
 
-impl TryFrom<&mut Peekable<Iter<'_, u8>>> for Header {
+impl TryFrom<&[u8]> for Header {
     type Error = DecodeError;
 
-    fn try_from(value: &mut Peekable<Iter<u8>>) -> Result<Self, Self::Error> {
-        let id = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(&mut Cursor::new(value))
+    }
+}
+
+impl Header {
+    /// Deserialize the header at the position of `value`.
+    pub(crate) fn decode(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let id = value.read_u16()?;
 
-        let byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
+        let byte = value.read_u8()?;
         let qr = Type::try_from((byte & 0b1000_0000) >> 7)?;
-        let opcode = OpCode::try_from((byte & 0b0111_1000) >> 3)?;
+        let opcode = OpCode::from((byte & 0b0111_1000) >> 3);
         let aa = (byte & 0b0000_0100) >> 2;
         let tc = (byte & 0b0000_0010) >> 1;
         let rd = byte & 0b0000_0001;
 
-        let byte = *value.next().ok_or(DecodeError::NotEnoughBytes)?;
+        let byte = value.read_u8()?;
         let ra = (byte & 0b1000_0000) >> 7;
-        let z = (byte & 0b0111_0000) >> 4;
-        let rcode = ResponseCode::try_from(byte & 0b0000_1111)?;
-        let qd_count = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
-        let an_count = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
-        let ns_count = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
-        let ar_count = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
+        // The Z bit, 0b0100_0000, is reserved and ignored.
+        let ad = (byte & 0b0010_0000) >> 5;
+        let cd = (byte & 0b0001_0000) >> 4;
+        let rcode = ResponseCode::from(u16::from(byte & 0b0000_1111));
+        let qd_count = value.read_u16()?;
+        let an_count = value.read_u16()?;
+        let ns_count = value.read_u16()?;
+        let ar_count = value.read_u16()?;
 
         let header = Header {
             id,
@@ -98,7 +101,8 @@ impl TryFrom<&mut Peekable<Iter<'_, u8>>> for Header {
             truncated: tc == 1,
             recursion_desired: rd == 1,
             recursion_available: ra == 1,
-            z,
+            authentic_data: ad == 1,
+            checking_disabled: cd == 1,
             r_code: rcode,
             qd_count,
             an_count,
@@ -111,13 +115,14 @@ impl TryFrom<&mut Peekable<Iter<'_, u8>>> for Header {
 }
 
 impl Header {
+    /// Serialize the instance into bytes.
     pub fn into_bytes(self) -> Vec<u8> {
         let mut header = Vec::with_capacity(12);
         header.append(&mut self.id.to_be_bytes().to_vec());
 
         let mut byte: u8 = 0;
         byte += Into::<u8>::into(self.message_type) << 7;
-        byte += Into::<u8>::into(self.op_code) << 3;
+        byte += (Into::<u8>::into(self.op_code) & 0b1111) << 3;
         byte += Into::<u8>::into(self.authoritive_answer) << 2;
         byte += Into::<u8>::into(self.truncated) << 1;
         byte += Into::<u8>::into(self.recursion_desired);
@@ -125,8 +130,10 @@ impl Header {
 
         let mut byte: u8 = 0;
         byte += Into::<u8>::into(self.recursion_available) << 7;
-        byte += Into::<u8>::into(self.z) << 4;
-        byte += Into::<u8>::into(self.r_code);
+        byte += Into::<u8>::into(self.authentic_data) << 5;
+        byte += Into::<u8>::into(self.checking_disabled) << 4;
+        // Only the lower 4 bits of an extended response code fit in the header.
+        byte += (u16::from(self.r_code) & 0b1111) as u8;
         header.push(byte);
 
         header.append(&mut self.qd_count.to_be_bytes().to_vec());
@@ -138,24 +145,38 @@ impl Header {
     }
 }
 
+/// Kind of query in a message.
+/// See section 4.1.1 of RFC 1035 and the [`DNS OpCodes`] registry of IANA.
+///
+/// [`DNS OpCodes`]: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum OpCode {
+    /// A standard query.
     Query,
+    /// An inverse query.
     IQuery,
+    /// A server status request.
     Status,
+    /// A notification of a zone change. See RFC 1996.
+    Notify,
+    /// A dynamic update. See RFC 2136.
+    Update,
+    /// A DNS Stateful Operation. See RFC 8490.
+    Dso,
+    /// An opcode this crate doesn't know about. Only the lower 4 bits can be carried by a header.
+    Unknown(u8),
 }
 
-impl TryFrom<u8> for OpCode {
-    type Error = DecodeError;
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl From<u8> for OpCode {
+    fn from(value: u8) -> Self {
         match value {
-            0 => Ok(Self::Query),
-            1 => Ok(Self::IQuery),
-            2 => Ok(Self::Status),
-            _ => Err(DecodeError::IllegalValue(format!(
-                "failed to parse value as OpCode: {} is not a valid value",
-                value
-            ))),
+            0 => Self::Query,
+            1 => Self::IQuery,
+            2 => Self::Status,
+            4 => Self::Notify,
+            5 => Self::Update,
+            6 => Self::Dso,
+            _ => Self::Unknown(value),
         }
     }
 }
@@ -166,12 +187,20 @@ impl From<OpCode> for u8 {
             OpCode::Query => 0,
             OpCode::IQuery => 1,
             OpCode::Status => 2,
+            OpCode::Notify => 4,
+            OpCode::Update => 5,
+            OpCode::Dso => 6,
+            OpCode::Unknown(value) => value,
         }
     }
 }
+
+/// Whether a message is a query or a reply.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Type {
+    /// The message is a query.
     Query,
+    /// The message is a reply.
     Reply,
 }
 
@@ -181,10 +210,11 @@ impl TryFrom<u8> for Type {
         match value {
             0 => Ok(Self::Query),
             1 => Ok(Self::Reply),
-            _ => Err(DecodeError::IllegalValue(format!(
+            _ => Err(DecodeErrorKind::IllegalValue(format!(
                 "failed to parse value as Type : {} is not a valid value",
                 value
-            ))),
+            ))
+            .into()),
         }
     }
 }
@@ -198,43 +228,117 @@ impl From<Type> for u8 {
     }
 }
 
+/// Response code of a reply.
+///
+/// The header holds the lower 4 bits of the response code. Messages with an EDNS OPT record
+/// carry the upper 8 bits in that record, extending the response code to 12 bits. See the
+/// [`DNS RCODEs`] registry of IANA.
+///
+/// [`DNS RCODEs`]: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum ResponseCode {
+    /// No error condition.
     NoError,
+    /// The name server was unable to interpret the query.
     FormatError,
+    /// The name server was unable to process the query due to a problem with the name server.
     ServerFailure,
+    /// The domain name referenced in the query doesn't exist.
     NameError,
+    /// The name server doesn't support the requested kind of query.
     NotImplemented,
+    /// The name server refuses to perform the specified operation for policy reasons.
     Refused,
+    /// A name exists when it should not. See RFC 2136.
+    YXDomain,
+    /// An RRset exists when it should not. See RFC 2136.
+    YXRRSet,
+    /// An RRset that should exist doesn't. See RFC 2136.
+    NXRRSet,
+    /// The server is not authoritative for the zone, or the request is not authorized. See RFC
+    /// 2136 and RFC 8945.
+    NotAuth,
+    /// A name is not contained in the zone. See RFC 2136.
+    NotZone,
+    /// The DSO-TYPE is not implemented. See RFC 8490.
+    DsoTypeNotImplemented,
+    /// The EDNS version is not supported. See RFC 6891.
+    ///
+    /// Shares its value, 16, with `BadSignature`. 16 is decoded as `BadVersion`.
+    BadVersion,
+    /// The TSIG signature failed to verify. See RFC 8945.
+    BadSignature,
+    /// The key is not recognized. See RFC 8945.
+    BadKey,
+    /// The signature is out of its time window. See RFC 8945.
+    BadTime,
+    /// Bad TKEY mode. See RFC 2930.
+    BadMode,
+    /// Duplicate key name. See RFC 2930.
+    BadName,
+    /// The algorithm is not supported. See RFC 2930.
+    BadAlgorithm,
+    /// Bad truncation. See RFC 8945.
+    BadTruncation,
+    /// Bad or missing server cookie. See RFC 7873.
+    BadCookie,
+    /// A response code this crate doesn't know about. Only the lower 12 bits can be carried by a
+    /// message.
+    Unknown(u16),
 }
 
-impl TryFrom<u8> for ResponseCode {
-    type Error = DecodeError;
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+impl From<u16> for ResponseCode {
+    fn from(value: u16) -> Self {
         match value {
-            0 => Ok(Self::NoError),
-            1 => Ok(Self::FormatError),
-            2 => Ok(Self::ServerFailure),
-            3 => Ok(Self::NameError),
-            4 => Ok(Self::NotImplemented),
-            5 => Ok(Self::Refused),
-            _ => Err(DecodeError::IllegalValue(format!(
-                "failed to parse value as ResponseCode: {} is not a valid value",
-                value
-            ))),
+            0 => Self::NoError,
+            1 => Self::FormatError,
+            2 => Self::ServerFailure,
+            3 => Self::NameError,
+            4 => Self::NotImplemented,
+            5 => Self::Refused,
+            6 => Self::YXDomain,
+            7 => Self::YXRRSet,
+            8 => Self::NXRRSet,
+            9 => Self::NotAuth,
+            10 => Self::NotZone,
+            11 => Self::DsoTypeNotImplemented,
+            16 => Self::BadVersion,
+            17 => Self::BadKey,
+            18 => Self::BadTime,
+            19 => Self::BadMode,
+            20 => Self::BadName,
+            21 => Self::BadAlgorithm,
+            22 => Self::BadTruncation,
+            23 => Self::BadCookie,
+            _ => Self::Unknown(value),
         }
     }
 }
 
-impl From<ResponseCode> for u8 {
+impl From<ResponseCode> for u16 {
     fn from(val: ResponseCode) -> Self {
         match val {
             ResponseCode::NoError => 0,
             ResponseCode::FormatError => 1,
             ResponseCode::ServerFailure => 2,
-            ResponseCode::NameError => 4,
-            ResponseCode::NotImplemented => 5,
-            ResponseCode::Refused => 6,
+            ResponseCode::NameError => 3,
+            ResponseCode::NotImplemented => 4,
+            ResponseCode::Refused => 5,
+            ResponseCode::YXDomain => 6,
+            ResponseCode::YXRRSet => 7,
+            ResponseCode::NXRRSet => 8,
+            ResponseCode::NotAuth => 9,
+            ResponseCode::NotZone => 10,
+            ResponseCode::DsoTypeNotImplemented => 11,
+            ResponseCode::BadVersion | ResponseCode::BadSignature => 16,
+            ResponseCode::BadKey => 17,
+            ResponseCode::BadTime => 18,
+            ResponseCode::BadMode => 19,
+            ResponseCode::BadName => 20,
+            ResponseCode::BadAlgorithm => 21,
+            ResponseCode::BadTruncation => 22,
+            ResponseCode::BadCookie => 23,
+            ResponseCode::Unknown(value) => value,
         }
     }
 }
@@ -242,16 +346,16 @@ impl From<ResponseCode> for u8 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use rand::Rng;
 
     #[test]
     fn deserialize() {
-        let mut query = [
+        let buf = [
             144, 200, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 10, 100, 117, 99, 107, 100, 117, 99, 107, 103,
             111, 3, 99, 111, 109, 0, 0, 1, 0, 1,
-        ]
-        .iter()
-        .peekable();
-        let header = Header::try_from(&mut query).unwrap();
+        ];
+        let mut query = Cursor::new(&buf);
+        let header = Header::decode(&mut query).unwrap();
 
         assert_eq!(
             header,
@@ -263,7 +367,8 @@ mod test {
                 truncated: false,
                 recursion_desired: true,
                 recursion_available: false,
-                z: 0,
+                authentic_data: false,
+                checking_disabled: false,
                 r_code: ResponseCode::NoError,
                 qd_count: 1,
                 an_count: 0,
@@ -272,14 +377,98 @@ mod test {
             }
         );
 
-        let question = crate::sections::Question::try_from(&mut query).unwrap();
+        let question = crate::sections::QuestionRef::parse(&mut query)
+            .unwrap()
+            .to_question();
         assert_eq!(
             question,
             crate::sections::Question {
-                qname: "duckduckgo.com".to_string(),
+                qname: "duckduckgo.com".parse().unwrap(),
                 qtype: crate::sections::QType::A,
                 qclass: crate::sections::QClass::IN,
             }
         );
     }
+
+    // Generate a header with random values for all fields the header can carry by itself.
+    fn random_header(rng: &mut impl Rng) -> Header {
+        Header {
+            id: rng.gen(),
+            message_type: [Type::Query, Type::Reply][rng.gen_range(0..2)].clone(),
+            op_code: OpCode::from(rng.gen_range(0..16)),
+            authoritive_answer: rng.gen(),
+            truncated: rng.gen(),
+            recursion_desired: rng.gen(),
+            recursion_available: rng.gen(),
+            authentic_data: rng.gen(),
+            checking_disabled: rng.gen(),
+            r_code: ResponseCode::from(rng.gen_range(0..16)),
+            qd_count: rng.gen(),
+            an_count: rng.gen(),
+            ns_count: rng.gen(),
+            ar_count: rng.gen(),
+        }
+    }
+
+    #[test]
+    fn test_serialization_and_deserialization_are_symmetric() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10_000 {
+            let header = random_header(&mut rng);
+            let bytes = header.clone().into_bytes();
+
+            assert_eq!(bytes.len(), 12);
+            assert_eq!(Header::try_from(bytes.as_slice()).unwrap(), header);
+        }
+
+        // Every sequence of 12 bytes with the reserved Z bit cleared is a header, and serializes
+        // back to the same bytes.
+        for _ in 0..10_000 {
+            let mut bytes: [u8; 12] = rng.gen();
+            bytes[3] &= 0b1011_1111;
+            assert_eq!(
+                Header::try_from(bytes.as_slice()).unwrap().into_bytes(),
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn test_response_codes() {
+        for value in 0..=u16::MAX {
+            assert_eq!(u16::from(ResponseCode::from(value)), value);
+        }
+
+        assert_eq!(ResponseCode::from(3), ResponseCode::NameError);
+        assert_eq!(u16::from(ResponseCode::NameError), 3);
+        assert_eq!(u16::from(ResponseCode::NotImplemented), 4);
+        assert_eq!(u16::from(ResponseCode::Refused), 5);
+        assert_eq!(ResponseCode::from(16), ResponseCode::BadVersion);
+        assert_eq!(u16::from(ResponseCode::BadSignature), 16);
+        assert_eq!(ResponseCode::from(23), ResponseCode::BadCookie);
+        assert_eq!(ResponseCode::from(24), ResponseCode::Unknown(24));
+
+        // Only the lower 4 bits of the response code are stored in the header.
+        let mut header = Header::try_from([0; 12].as_slice()).unwrap();
+        header.r_code = ResponseCode::BadCookie;
+        assert_eq!(header.into_bytes()[3], 7);
+    }
+
+    #[test]
+    fn test_opcodes_and_dnssec_flags() {
+        // A NOTIFY with the AD and CD bits set.
+        let header =
+            Header::try_from([0, 1, 0b0010_0000, 0b0011_0000, 0, 1, 0, 0, 0, 0, 0, 0].as_slice())
+                .unwrap();
+        assert_eq!(header.op_code, OpCode::Notify);
+        assert!(header.authentic_data);
+        assert!(header.checking_disabled);
+
+        assert_eq!(OpCode::from(5), OpCode::Update);
+        assert_eq!(OpCode::from(6), OpCode::Dso);
+        assert_eq!(OpCode::from(15), OpCode::Unknown(15));
+        for value in 0..=u8::MAX {
+            assert_eq!(u8::from(OpCode::from(value)), value);
+        }
+    }
 }
@@ -6,35 +6,175 @@
 //!
 //! [`RFC 1035`]: https://datatracker.ietf.org/doc/html/rfc1035
 #![warn(missing_docs)]
-mod domain_name;
+pub mod caa;
+pub mod cookie;
+mod cursor;
+pub mod dane;
+pub mod domain_name;
+pub mod edns;
 pub mod header;
 pub mod message;
+pub mod rdata;
 pub mod resource_record;
 pub mod sections;
+pub mod server;
+pub mod service;
+pub mod svcb;
+pub mod tcp;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod zone;
 
-pub use message::Message;
+pub use domain_name::DomainName;
+pub use message::{Message, MessageBuilder, MessageRef};
 
+use crate::sections::Section;
 use std::error::Error;
 use std::fmt::Display;
 
-#[derive(Debug, Clone)]
-pub enum DecodeError {
+/// Error returned when bytes can't be deserialized.
+///
+/// Besides the reason, the error records where in the message decoding failed: the offset of
+/// the offending byte and the section and record being parsed. Errors that don't stem from
+/// decoding a message, like parsing a `DomainName` from a string, carry no location.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DecodeError {
+    /// The reason decoding failed.
+    pub kind: DecodeErrorKind,
+
+    /// Offset in bytes, relative to the start of the message, at which the error was detected.
+    pub offset: Option<usize>,
+
+    /// The section of the message that was being parsed.
+    pub section: Option<Section>,
+
+    /// The index of the question or resource record within `section` that was being parsed.
+    pub index: Option<usize>,
+}
+
+/// The reason decoding failed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecodeErrorKind {
     /// Error that indicates byte stream is too short.
     NotEnoughBytes,
 
+    /// A label of a domain name starts with a byte that indicates neither a label nor a pointer.
+    InvalidLabelType,
+
+    /// A compression pointer doesn't point to a prior occurrence of a domain name.
+    InvalidPointer,
+
+    /// A domain name is expressed using more compression pointers than allowed.
+    TooManyPointers,
+
+    /// A label of a domain name is empty or longer than 63 bytes.
+    InvalidLabelLength,
+
+    /// A domain name is longer than 255 bytes.
+    NameTooLong,
+
+    /// The rdata of a resource record is shorter or longer than its rdlength.
+    RDataLengthMismatch,
+
+    /// The number of entries in a section doesn't match the count in the header. Only reported
+    /// by `MessageRef::parse_strict()`.
+    CountMismatch,
+
     /// Error indicating the (series of) bytes represent an value that's not allowed.
     IllegalValue(String),
 }
 
+impl DecodeError {
+    // Record the offset at which the error was detected, unless it's already known.
+    pub(crate) fn at(mut self, offset: usize) -> Self {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    // Record the section and the index of the question or record that was being parsed.
+    pub(crate) fn within(mut self, section: Section, index: Option<usize>) -> Self {
+        self.section = Some(section);
+        self.index = index;
+        self
+    }
+}
+
+impl From<DecodeErrorKind> for DecodeError {
+    fn from(kind: DecodeErrorKind) -> Self {
+        Self {
+            kind,
+            offset: None,
+            section: None,
+            index: None,
+        }
+    }
+}
+
+impl Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotEnoughBytes => write!(f, "not enough bytes"),
+            Self::InvalidLabelType => write!(f, "label is neither a label nor a pointer"),
+            Self::InvalidPointer => write!(
+                f,
+                "compression pointer doesn't point to a prior occurrence of a name"
+            ),
+            Self::TooManyPointers => write!(f, "domain name contains too many pointers"),
+            Self::InvalidLabelLength => write!(f, "label is empty or exceeds 63 bytes"),
+            Self::NameTooLong => write!(f, "domain name exceeds 255 bytes"),
+            Self::RDataLengthMismatch => write!(f, "length of rdata doesn't match rdlength"),
+            Self::CountMismatch => write!(f, "section count doesn't match the entries"),
+            Self::IllegalValue(value) => write!(f, "{}", value),
+        }
+    }
+}
+
 impl Display for DecodeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let reason: String = match self {
-            Self::NotEnoughBytes => "not enough bytes".into(),
-            Self::IllegalValue(value) => value.to_string(),
-        };
+        write!(f, "failed to decode bytes")?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        match (&self.section, self.index) {
+            (Some(section), Some(index)) => write!(f, " in {} {}", section, index)?,
+            (Some(section), None) => write!(f, " in {}", section)?,
+            _ => {}
+        }
 
-        write!(f, "failed to decode bytes: {}", reason)
+        write!(f, ": {}", self.kind)
     }
 }
 
 impl Error for DecodeError {}
+
+/// Error returned when a value can't be serialized.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EncodeError {
+    /// A value is longer than its length field can express, like rdata of more than 65535
+    /// bytes.
+    TooLong,
+
+    /// Error indicating a value that's not allowed in the wire format.
+    IllegalValue(String),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to encode value: ")?;
+        match self {
+            Self::TooLong => write!(f, "value exceeds the maximum length of its field"),
+            Self::IllegalValue(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+// Write the length of the bytes following the 2-byte length field at `position` of `output`
+// into that field.
+pub(crate) fn write_length(output: &mut [u8], position: usize) -> Result<(), EncodeError> {
+    let length = u16::try_from(output.len() - position - 2).map_err(|_| EncodeError::TooLong)?;
+    output[position..position + 2].copy_from_slice(&length.to_be_bytes());
+
+    Ok(())
+}
@@ -1,80 +1,99 @@
-use dns::header::{Header, OpCode, ResponseCode, Type as MessageType};
+use dns::cookie::ServerCookies;
+use dns::edns::{Cookie, ExtendedError, InfoCode, DEFAULT_UDP_PAYLOAD_SIZE};
+use dns::header::ResponseCode;
 use dns::message::Message;
-use dns::resource_record::{Class, ResourceRecord, Type};
 use dns::sections::Question;
-use rand::random;
+use dns::server::{Request, RequestHandler, Server};
+use dns::tcp::Connection;
 use std::net::ToSocketAddrs;
 use std::net::UdpSocket;
+use std::time::Duration;
 
-fn look_up<A: ToSocketAddrs>(query: &Question, address: A) -> std::io::Result<()> {
+// Whether queries over UDP with a client cookie must hold a valid server cookie to be answered.
+const REQUIRE_VALID_COOKIES: bool = true;
+
+// The identifier of this server, returned to queries holding an NSID option.
+const NSID: &[u8] = b"dns";
+
+fn look_up<A: ToSocketAddrs>(query: &Question, address: A) -> std::io::Result<Message> {
     let socket = UdpSocket::bind("0.0.0.0:0")?;
-    let message = Message {
-        header: Header {
-            id: random::<u16>(),
-            message_type: MessageType::Query,
-            op_code: OpCode::Query,
-            authoritive_answer: false,
-            truncated: false,
-            recursion_desired: false,
-            recursion_available: false,
-            z: 0,
-            r_code: ResponseCode::NoError,
-            qd_count: 1,
-            an_count: 0,
-            ns_count: 0,
-            ar_count: 0,
-        },
-        questions: vec![query.clone()],
-        answers: vec![],
-        authority: vec![],
-        additional: vec![],
-    };
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let message = Message::query(query.qname.clone(), query.qtype.clone())
+        .recursion_desired(false)
+        .cookie(Cookie::new(rand::random()))
+        .build();
 
-    socket.send_to(&message.into_bytes(), address)?;
-    let mut buf = [0; 512];
+    let bytes = message
+        .clone()
+        .into_bytes()
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+    socket.send_to(&bytes, address)?;
+    let mut buf = [0; DEFAULT_UDP_PAYLOAD_SIZE as usize];
     let (amt, src) = socket.recv_from(&mut buf)?;
-    dbg!(&amt);
-    let message = Message::try_from(&mut buf.iter().peekable()).unwrap();
+    let mut response = Message::try_from(&buf[..amt])
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
 
-    dbg!(message);
+    // The response didn't fit in a UDP packet, so ask again over TCP.
+    if response.header.truncated {
+        response = Connection::connect(src, Duration::from_secs(5))?.query(message)?;
+    }
 
-    Ok(())
+    for error in response.extended_errors() {
+        eprintln!("{}: {}", query.qname, error);
+    }
+
+    Ok(response)
 }
 
-fn main() -> std::io::Result<()> {
-    let socket = UdpSocket::bind("localhost:1337")?;
+// Answers queries with the response of a root server.
+struct Resolver;
 
-    // Maximum size of UDP packet. See section 2.3.4 of the RFC.
-    let mut buf = [0; 512];
-    let (amt, src) = socket.recv_from(&mut buf)?;
+impl RequestHandler for Resolver {
+    fn handle(&self, request: &Request) -> Message {
+        let message = &request.message;
 
-    let message = Message::try_from(&mut buf.iter().peekable()).unwrap();
-    if message.header.message_type == MessageType::Query {
-        look_up(&message.questions[0], "198.41.0.4:53")?
-    }
+        let mut response = message.response();
+        if message.nsid().is_some() {
+            response = response.nsid(NSID.to_vec());
+        }
+
+        let question = match message.questions.first() {
+            Some(question) => question,
+            None => return response.rcode(ResponseCode::FormatError).build(),
+        };
 
-    let mut response_header = message.header.clone();
-    response_header.message_type = MessageType::Reply;
-    response_header.authoritive_answer = false;
-    response_header.an_count = 1;
-    response_header.r_code = ResponseCode::NoError;
+        match look_up(question, "198.41.0.4:53") {
+            Ok(upstream) => {
+                let mut response = response.rcode(upstream.header.r_code);
+                for record in upstream.answers {
+                    response = response.answer(record);
+                }
+                for record in upstream.authority {
+                    response = response.authority(record);
+                }
+                for record in upstream.additional {
+                    response = response.additional(record);
+                }
 
-    let response = Message {
-        header: response_header,
-        questions: message.questions.clone(),
-        answers: vec![ResourceRecord {
-            name: vec![0xc0, 0x0c],
-            r#type: Type::A,
-            class: Class::IN,
-            ttl: 300,
-            rdlength: 4,
-            rdata: vec![185, 24, 223, 10],
-        }],
-        authority: vec![],
-        additional: vec![],
-    };
+                response.build()
+            }
+            // Tell the client why the query failed, see RFC 8914.
+            Err(error) => response
+                .rcode(ResponseCode::ServerFailure)
+                .extended_error(ExtendedError::new(
+                    InfoCode::NoReachableAuthority,
+                    error.to_string(),
+                ))
+                .build(),
+        }
+    }
+}
 
-    socket.send_to(&response.into_bytes(), src)?;
+fn main() -> std::io::Result<()> {
+    Server::bind("localhost:1337", Resolver)?
+        .cookies(ServerCookies::new(rand::random()))
+        .require_valid_cookies(REQUIRE_VALID_COOKIES)
+        .run();
 
     Ok(())
 }
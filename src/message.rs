@@ -1,10 +1,17 @@
 //! Serialize and deserialize `Message`s.
-use crate::header::Header;
-use crate::resource_record::ResourceRecord;
-use crate::sections::Question;
-use crate::DecodeError;
-use std::iter::Peekable;
-use std::slice::Iter;
+use crate::cursor::Cursor;
+use crate::domain_name::{Compressor, DomainName};
+use crate::edns::{
+    self, ClientSubnet, Cookie, Edns, EdnsOption, ExtendedError, PaddingPolicy,
+    MINIMUM_UDP_PAYLOAD_SIZE,
+};
+use crate::header::{Header, OpCode, ResponseCode, Type};
+use crate::resource_record::{self, RecordRef, ResourceRecord};
+use crate::sections::{QClass, QType, Question, QuestionRef, Section};
+use crate::{DecodeError, DecodeErrorKind, EncodeError};
+use rand::random;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
 
 /// `Message` models a DNS message as documented in [`4. Messages`] of RFC 1035.
 /// Any message, whether it's a query or a reply, follows the same format.
@@ -18,14 +25,15 @@ use std::slice::Iter;
 /// // `dig orangetux.nl +noedns`.
 /// let buf = vec![244, 238, 1, 32, 0, 1, 0, 0, 0, 0, 0, 0, 9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0, 0, 1, 0, 1];
 ///
-/// let message: Message = Message::try_from(&mut buf.iter().peekable()).unwrap();
-/// assert_eq!("orangetux.nl".to_string(), message.questions[0].qname);
-/// assert_eq!(buf, message.into_bytes());
+/// let message: Message = Message::try_from(buf.as_slice()).unwrap();
+/// assert_eq!("orangetux.nl", message.questions[0].qname.to_string());
+/// assert_eq!(buf, message.into_bytes().unwrap());
 ///
 /// ```
 /// [`4. Message`]: https://www.rfc-editor.org/rfc/rfc1035#section-4
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Message {
+    /// The header of the message.
     pub header: Header,
 
     /// A list of questions.
@@ -39,85 +47,836 @@ pub struct Message {
     pub authority: Vec<ResourceRecord>,
 
     /// A list with records holding additional information. The list might be empty.
+    ///
+    /// The OPT record isn't part of the list, it's kept in `edns`.
     pub additional: Vec<ResourceRecord>,
+
+    /// The EDNS information of the message, carried by the OPT record in the additional section.
+    /// `None` if the message has no OPT record.
+    pub edns: Option<Edns>,
 }
 
-impl TryFrom<&mut Peekable<Iter<'_, u8>>> for Message {
+impl TryFrom<&[u8]> for Message {
     type Error = DecodeError;
 
-    fn try_from(value: &mut Peekable<Iter<u8>>) -> Result<Self, Self::Error> {
-        let header = Header::try_from(&mut *value)?;
-        let mut questions = Vec::with_capacity(header.qd_count.into());
-        let mut answers = Vec::with_capacity(header.an_count.into());
-        let mut authority = Vec::with_capacity(header.ns_count.into());
-        let mut additional = Vec::with_capacity(header.ar_count.into());
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        MessageRef::parse(value)?.to_message()
+    }
+}
+
+impl Message {
+    /// Serialize the instance into bytes.
+    ///
+    /// The section counts of the header are derived from the sections, whatever their values in
+    /// `header`. A section holds at most 65535 entries; further entries are not serialized.
+    ///
+    /// The EDNS information is serialized as an OPT record at the end of the additional section.
+    /// It holds the upper 8 bits of a response code above 15. Without EDNS information, such a
+    /// response code can't be carried, and SERVFAIL is sent instead: an OPT record must not be
+    /// added to a response to a query without one, see section 7 of RFC 6891.
+    ///
+    /// Domain names are compressed: when (a suffix of) a name has been written before, it's
+    /// replaced with a pointer to the earlier occurrence.
+    ///
+    /// Fails if a value doesn't fit the wire format, like rdata of more than 65535 bytes.
+    pub fn into_bytes(self) -> Result<Vec<u8>, EncodeError> {
+        Ok(self.encode()?.output)
+    }
 
-        for _ in 0..header.qd_count {
-            questions.push(Question::try_from(&mut *value)?);
+    /// Serialize the instance into bytes like `Message::into_bytes()`, padded following
+    /// `policy`.
+    ///
+    /// The padding is computed once the rest of the message has been serialized: a Padding
+    /// option is appended to the OPT record, rounding the length of the message up to a multiple
+    /// of the block length. Padding options in `edns` are left out. A message without EDNS
+    /// information isn't padded.
+    ///
+    /// A response must only be padded if its query was, see section 4 of RFC 7830.
+    pub fn into_padded_bytes(mut self, policy: PaddingPolicy) -> Result<Vec<u8>, EncodeError> {
+        let block_length = policy.block_length(&self.header.message_type);
+        if let Some(edns) = &mut self.edns {
+            edns.options
+                .retain(|option| !matches!(option, EdnsOption::Padding(_)));
         }
-        for _ in 0..header.an_count {
-            answers.push(ResourceRecord::try_from(&mut *value)?);
+
+        let Encoded {
+            mut output,
+            opt_position,
+            ..
+        } = self.encode()?;
+        if let (Some(opt_position), Some(block_length)) = (opt_position, block_length) {
+            edns::pad(&mut output, opt_position, block_length);
         }
-        for _ in 0..header.ns_count {
-            authority.push(ResourceRecord::try_from(&mut *value)?);
+
+        Ok(output)
+    }
+
+    /// Serialize the instance into bytes like `Message::into_bytes()`, taking at most
+    /// `max_size` bytes.
+    ///
+    /// When the message doesn't fit, whole RRsets are left out, starting with the last RRset of
+    /// the additional section, then of the authority section and then of the answer section,
+    /// until it does. The TC bit is set when any RRset has been left out, so the client knows
+    /// to retry over TCP. The questions and the OPT record are always serialized; if they don't
+    /// fit, the result exceeds `max_size`. The records of an RRset that are apart in a section
+    /// are serialized together when the message is truncated.
+    ///
+    /// The message is serialized once, whatever the number of RRsets left out.
+    ///
+    /// The maximum size of a response over UDP is given by `Message::udp_payload_size()` of the
+    /// query.
+    pub fn into_truncated_bytes(mut self, max_size: usize) -> Result<Vec<u8>, EncodeError> {
+        // RRsets are cut off the end of the serialized message, which requires the records of
+        // each RRset to be adjacent. Records are only reordered if the message doesn't fit.
+        let sections = [&self.answers, &self.authority, &self.additional];
+        if !sections
+            .into_iter()
+            .all(|records| rrsets_are_adjacent(records))
+        {
+            let output = self.clone().into_bytes()?;
+            if output.len() <= max_size {
+                return Ok(output);
+            }
+            for records in [&mut self.answers, &mut self.authority, &mut self.additional] {
+                group_rrsets(records);
+            }
         }
-        for _ in 0..header.ar_count {
-            additional.push(ResourceRecord::try_from(&mut *value)?);
+
+        // Whether each record is the last of its RRset.
+        let rrset_ends = [&self.answers, &self.authority, &self.additional].map(|records| {
+            (0..records.len())
+                .map(|index| {
+                    records
+                        .get(index + 1)
+                        .is_none_or(|next| rrset_key(next) != rrset_key(&records[index]))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let encoded = self.encode()?;
+        if encoded.output.len() <= max_size {
+            return Ok(encoded.output);
         }
 
-        Ok(Message {
+        let opt = encoded
+            .opt_position
+            .map_or(vec![], |position| encoded.output[position..].to_vec());
+
+        // Keep the records up to the end of the last RRset that fits: all records of the
+        // sections before `section`, and `count` records of `section`.
+        let (mut end, mut section, mut count) = (encoded.questions_end, 0, 0);
+        'sections: for (current, record_ends) in encoded.record_ends.iter().enumerate() {
+            // Records are numbered from 1. Sections hold at most 65535 records.
+            for (number, record_end) in (1..=u16::MAX).zip(record_ends) {
+                if *record_end + opt.len() > max_size {
+                    break 'sections;
+                }
+                if rrset_ends[current][usize::from(number - 1)] {
+                    (end, section, count) = (*record_end, current, number);
+                }
+            }
+        }
+
+        let mut output = encoded.output;
+        output.truncate(end);
+        output.extend(opt);
+
+        // Set the TC bit, and the counts of the answer, authority and additional sections from
+        // `section` on. The additional section keeps the OPT record.
+        output[2] |= 0b10;
+        let opt_count = u16::from(encoded.opt_position.is_some());
+        for other in section..3 {
+            let mut kept = if other == section { count } else { 0 };
+            if other == 2 {
+                kept += opt_count;
+            }
+            let position = 6 + 2 * other;
+            output[position..position + 2].copy_from_slice(&kept.to_be_bytes());
+        }
+
+        Ok(output)
+    }
+
+    /// The size of the largest UDP response the sender of this query accepts: the UDP payload
+    /// size of the EDNS information, or 512 bytes without EDNS information. A UDP payload size
+    /// below 512 bytes is taken to be 512 bytes, see section 6.2.5 of RFC 6891.
+    pub fn udp_payload_size(&self) -> u16 {
+        self.edns
+            .as_ref()
+            .map_or(MINIMUM_UDP_PAYLOAD_SIZE, |edns| edns.udp_payload_size)
+            .max(MINIMUM_UDP_PAYLOAD_SIZE)
+    }
+
+    // Serialize the instance.
+    fn encode(mut self) -> Result<Encoded, EncodeError> {
+        let mut extended_rcode = (u16::from(self.header.r_code.clone()) >> 4) as u8;
+        if extended_rcode > 0 && self.edns.is_none() {
+            self.header.r_code = ResponseCode::ServerFailure;
+            extended_rcode = 0;
+        }
+        self.sync_counts();
+
+        let mut output = self.header.clone().into_bytes();
+        let mut compressor = Compressor::default();
+
+        for question in self.questions.into_iter().take(self.header.qd_count.into()) {
+            question.encode(&mut output, &mut compressor);
+        }
+        let questions_end = output.len();
+
+        let opt_count = u16::from(self.edns.is_some());
+        let mut record_ends: [Vec<usize>; 3] = Default::default();
+        for ((records, count), ends) in [
+            (self.answers, self.header.an_count),
+            (self.authority, self.header.ns_count),
+            (self.additional, self.header.ar_count - opt_count),
+        ]
+        .into_iter()
+        .zip(&mut record_ends)
+        {
+            for record in records.into_iter().take(count.into()) {
+                record.encode(&mut output, &mut compressor)?;
+                ends.push(output.len());
+            }
+        }
+
+        let opt_position = match self.edns {
+            Some(edns) => {
+                let position = output.len();
+                edns.encode(extended_rcode, &mut output)?;
+                Some(position)
+            }
+            None => None,
+        };
+
+        Ok(Encoded {
+            output,
+            questions_end,
+            record_ends,
+            opt_position,
+        })
+    }
+
+    /// The Client Subnet option of the EDNS information, if any.
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.edns.as_ref()?.client_subnet()
+    }
+
+    /// The Cookie option of the EDNS information, if any.
+    pub fn cookie(&self) -> Option<&Cookie> {
+        self.edns.as_ref()?.cookie()
+    }
+
+    /// Whether the EDNS information holds a Padding option.
+    pub fn is_padded(&self) -> bool {
+        self.edns
+            .iter()
+            .flat_map(|edns| &edns.options)
+            .any(|option| matches!(option, EdnsOption::Padding(_)))
+    }
+
+    /// The Extended DNS Error options of the EDNS information, in the order they appear in the
+    /// message.
+    pub fn extended_errors(&self) -> impl Iterator<Item = &ExtendedError> {
+        self.edns.iter().flat_map(|edns| edns.extended_errors())
+    }
+
+    /// The data of the NSID option of the EDNS information, if any. It's empty in queries.
+    pub fn nsid(&self) -> Option<&[u8]> {
+        self.edns.as_ref()?.nsid()
+    }
+
+    // Set the Cookie option, see `MessageBuilder::cookie()`.
+    pub(crate) fn set_cookie(&mut self, cookie: Cookie) {
+        let edns = self.edns.get_or_insert_with(Edns::default);
+        edns.options
+            .retain(|option| !matches!(option, EdnsOption::Cookie(_)));
+        edns.options.push(EdnsOption::Cookie(cookie));
+        self.sync_counts();
+    }
+
+    // Set the section counts of the header to the number of entries in the sections.
+    fn sync_counts(&mut self) {
+        let count = |length: usize| u16::try_from(length).unwrap_or(u16::MAX);
+        self.header.qd_count = count(self.questions.len());
+        self.header.an_count = count(self.answers.len());
+        self.header.ns_count = count(self.authority.len());
+        self.header.ar_count = count(self.additional.len() + usize::from(self.edns.is_some()));
+    }
+}
+
+impl Message {
+    /// Start building a query for records of type `qtype` in class IN at `name`.
+    ///
+    /// The query gets a random ID, asks for recursion and advertises EDNS with default values.
+    ///
+    /// ```
+    /// use dns::sections::QType;
+    /// use dns::Message;
+    ///
+    /// let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+    /// assert!(query.header.recursion_desired);
+    /// assert_eq!(query.header.qd_count, 1);
+    /// ```
+    pub fn query(name: DomainName, qtype: QType) -> MessageBuilder {
+        MessageBuilder::new(Message {
+            header: Header {
+                id: random(),
+                message_type: Type::Query,
+                op_code: OpCode::Query,
+                authoritive_answer: false,
+                truncated: false,
+                recursion_desired: true,
+                recursion_available: false,
+                authentic_data: false,
+                checking_disabled: false,
+                r_code: ResponseCode::NoError,
+                qd_count: 0,
+                an_count: 0,
+                ns_count: 0,
+                ar_count: 0,
+            },
+            questions: vec![Question {
+                qname: name,
+                qtype,
+                qclass: QClass::IN,
+            }],
+            answers: vec![],
+            authority: vec![],
+            additional: vec![],
+            edns: Some(Edns::default()),
+        })
+    }
+
+    /// Start building a response to this message.
+    ///
+    /// The response copies the ID, the opcode, the RD and CD flags and the questions of this
+    /// message.
+    ///
+    /// If this message has EDNS information, so does the response. It copies the DO bit and the
+    /// Client Subnet option, with a scope prefix length of 0; see
+    /// `MessageBuilder::client_subnet_scope()`. When this message uses a version of EDNS this
+    /// crate doesn't implement, the response code is BADVERS, as required by section 6.1.3 of
+    /// RFC 6891.
+    pub fn response(&self) -> MessageBuilder {
+        let r_code = match &self.edns {
+            Some(edns) if edns.version > edns::VERSION => ResponseCode::BadVersion,
+            _ => ResponseCode::NoError,
+        };
+        let edns = self.edns.as_ref().map(|edns| Edns {
+            dnssec_ok: edns.dnssec_ok,
+            options: edns
+                .client_subnet()
+                .and_then(|subnet| subnet.clone().with_scope_prefix_length(0).ok())
+                .map(EdnsOption::ClientSubnet)
+                .into_iter()
+                .collect(),
+            ..Edns::default()
+        });
+
+        MessageBuilder::new(Message {
+            header: Header {
+                id: self.header.id,
+                message_type: Type::Reply,
+                op_code: self.header.op_code.clone(),
+                authoritive_answer: false,
+                truncated: false,
+                recursion_desired: self.header.recursion_desired,
+                recursion_available: false,
+                authentic_data: false,
+                checking_disabled: self.header.checking_disabled,
+                r_code,
+                qd_count: 0,
+                an_count: 0,
+                ns_count: 0,
+                ar_count: 0,
+            },
+            questions: self.questions.clone(),
+            answers: vec![],
+            authority: vec![],
+            additional: vec![],
+            edns,
+        })
+    }
+}
+
+/// Builds a `Message`, keeping the section counts of the header in sync with the sections.
+///
+/// Created by [`Message::query()`] and [`Message::response()`].
+///
+/// ```
+/// use dns::rdata::RData;
+/// use dns::resource_record::{Class, ResourceRecord};
+/// use dns::sections::QType;
+/// use dns::Message;
+/// use std::net::Ipv4Addr;
+///
+/// let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+/// let response = query
+///     .response()
+///     .answer(ResourceRecord {
+///         name: "orangetux.nl".parse().unwrap(),
+///         class: Class::IN,
+///         ttl: 300,
+///         rdata: RData::A(Ipv4Addr::new(185, 24, 223, 10)),
+///     })
+///     .build();
+///
+/// assert_eq!(response.header.id, query.header.id);
+/// assert_eq!(response.header.an_count, 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+    message: Message,
+}
+
+impl MessageBuilder {
+    fn new(message: Message) -> Self {
+        let mut builder = Self { message };
+        builder.message.sync_counts();
+
+        builder
+    }
+
+    /// Set the ID of the message.
+    pub fn id(mut self, id: u16) -> Self {
+        self.message.header.id = id;
+        self
+    }
+
+    /// Set whether recursion is desired.
+    pub fn recursion_desired(mut self, recursion_desired: bool) -> Self {
+        self.message.header.recursion_desired = recursion_desired;
+        self
+    }
+
+    /// Set whether recursion is available.
+    pub fn recursion_available(mut self, recursion_available: bool) -> Self {
+        self.message.header.recursion_available = recursion_available;
+        self
+    }
+
+    /// Set whether the answer is authoritative.
+    pub fn authoritative(mut self, authoritative: bool) -> Self {
+        self.message.header.authoritive_answer = authoritative;
+        self
+    }
+
+    /// Set the response code.
+    pub fn rcode(mut self, rcode: ResponseCode) -> Self {
+        self.message.header.r_code = rcode;
+        self
+    }
+
+    /// Add a record to the answer section.
+    pub fn answer(mut self, record: ResourceRecord) -> Self {
+        self.message.answers.push(record);
+        self.message.sync_counts();
+        self
+    }
+
+    /// Add a record to the authority section.
+    pub fn authority(mut self, record: ResourceRecord) -> Self {
+        self.message.authority.push(record);
+        self.message.sync_counts();
+        self
+    }
+
+    /// Add a record to the additional section.
+    pub fn additional(mut self, record: ResourceRecord) -> Self {
+        self.message.additional.push(record);
+        self.message.sync_counts();
+        self
+    }
+
+    /// Set the EDNS information. `None` leaves out the OPT record.
+    pub fn edns(mut self, edns: Option<Edns>) -> Self {
+        self.message.edns = edns;
+        self.message.sync_counts();
+        self
+    }
+
+    /// Set the scope prefix length of the Client Subnet option, if the message has one. The
+    /// length is capped at the length of the address.
+    pub fn client_subnet_scope(mut self, scope_prefix_length: u8) -> Self {
+        let options = self
+            .message
+            .edns
+            .iter_mut()
+            .flat_map(|edns| &mut edns.options);
+        for option in options {
+            if let EdnsOption::ClientSubnet(subnet) = option {
+                let maximum = if subnet.family() == 1 { 32 } else { 128 };
+                if let Ok(scoped) = subnet
+                    .clone()
+                    .with_scope_prefix_length(scope_prefix_length.min(maximum))
+                {
+                    *subnet = scoped;
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Set the Cookie option, replacing any cookie the message had. A message without EDNS
+    /// information gets the default EDNS information.
+    pub fn cookie(mut self, cookie: Cookie) -> Self {
+        self.message.set_cookie(cookie);
+        self
+    }
+
+    /// Add an Extended DNS Error option, explaining the response code.
+    ///
+    /// The option is left out if the message has no EDNS information: a response to a query
+    /// without EDNS information must not have an OPT record, see section 7 of RFC 6891.
+    pub fn extended_error(mut self, error: ExtendedError) -> Self {
+        if let Some(edns) = &mut self.message.edns {
+            edns.options.push(EdnsOption::ExtendedError(error));
+        }
+
+        self
+    }
+
+    /// Set the NSID option to `identifier`, the identifier of the name server. Servers should
+    /// only do so in response to a query holding an NSID option, see `Message::nsid()`.
+    ///
+    /// Like `MessageBuilder::extended_error()`, the option is left out if the message has no EDNS
+    /// information.
+    pub fn nsid(mut self, identifier: Vec<u8>) -> Self {
+        if let Some(edns) = &mut self.message.edns {
+            edns.options
+                .retain(|option| !matches!(option, EdnsOption::Nsid(_)));
+            edns.options.push(EdnsOption::Nsid(identifier));
+        }
+
+        self
+    }
+
+    /// Return the message.
+    pub fn build(self) -> Message {
+        self.message
+    }
+}
+
+// A serialized message, with the positions needed to pad or truncate it.
+struct Encoded {
+    output: Vec<u8>,
+    // Where the questions end.
+    questions_end: usize,
+    // Where each record of the answer, authority and additional sections ends.
+    record_ends: [Vec<usize>; 3],
+    // Where the OPT record starts, if any.
+    opt_position: Option<usize>,
+}
+
+// The owner name, class and type that identify the RRset of `record`.
+fn rrset_key(
+    record: &ResourceRecord,
+) -> (&DomainName, &resource_record::Class, resource_record::Type) {
+    (&record.name, &record.class, record.r#type())
+}
+
+// Whether the records of each RRset in `records` are adjacent.
+fn rrsets_are_adjacent(records: &[ResourceRecord]) -> bool {
+    let mut seen = BTreeSet::new();
+    records.iter().enumerate().all(|(index, record)| {
+        let key = rrset_key(record);
+        (index > 0 && rrset_key(&records[index - 1]) == key) || seen.insert(key)
+    })
+}
+
+// Order `records` so the records of each RRset are adjacent, with the RRsets in order of their
+// last record. Cutting RRsets off the end then leaves out the same RRsets as repeatedly
+// removing the RRset of the last record.
+fn group_rrsets(records: &mut [ResourceRecord]) {
+    let last: BTreeMap<_, _> = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| (rrset_key(record), index))
+        .collect();
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    order.sort_by_key(|index| last[&rrset_key(&records[*index])]);
+
+    let mut grouped: Vec<_> = order
+        .into_iter()
+        .map(|index| records[index].clone())
+        .collect();
+    records.swap_with_slice(&mut grouped);
+}
+
+/// A message borrowed from a buffer.
+///
+/// Parsing validates the structure of the message: the header, the questions and the owner
+/// names, types, classes and TTLs of the records. Rdata is only checked to fit in the message;
+/// [`RecordRef::rdata()`] decodes it. Nothing is copied, so a `MessageRef` is cheap to inspect.
+/// [`MessageRef::to_message()`] converts it into an owned [`Message`].
+///
+/// ```
+/// use dns::MessageRef;
+/// // A response to a query for the A record of orangetux.nl.
+/// let buf = vec![
+///     244, 238, 129, 128, 0, 1, 0, 1, 0, 0, 0, 0, 9, 111, 114, 97, 110, 103, 101, 116, 117, 120,
+///     2, 110, 108, 0, 0, 1, 0, 1, 192, 12, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 185, 24, 223, 10,
+/// ];
+///
+/// let message = MessageRef::parse(&buf).unwrap();
+/// let answer = message.answers().next().unwrap();
+/// assert_eq!(answer.name().to_string(), "orangetux.nl");
+/// assert_eq!(answer.rdata_bytes(), [185, 24, 223, 10]);
+///
+/// let message = message.to_message().unwrap();
+/// assert_eq!(message.answers[0].rdata.to_string(), "185.24.223.10");
+/// ```
+#[derive(Clone)]
+pub struct MessageRef<'a> {
+    header: Header,
+    // Cursors positioned at the start of every section.
+    questions: Cursor<'a>,
+    answers: Cursor<'a>,
+    authority: Cursor<'a>,
+    additional: Cursor<'a>,
+    // The OPT record of the additional section, if any.
+    opt: Option<RecordRef<'a>>,
+}
+
+impl<'a> MessageRef<'a> {
+    /// Parse the message in `value`. Bytes following the last record are ignored.
+    pub fn parse(value: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::parse_with(value, false)
+    }
+
+    /// Parse the message in `value`, validating that the sections hold exactly as many entries
+    /// as the counts in the header claim.
+    ///
+    /// A message that ends where the header promises another entry, or that continues after the
+    /// last entry, is rejected with `DecodeErrorKind::CountMismatch`.
+    pub fn parse_strict(value: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::parse_with(value, true)
+    }
+
+    fn parse_with(value: &'a [u8], strict: bool) -> Result<Self, DecodeError> {
+        let mut cursor = Cursor::new(value);
+        let header =
+            Header::decode(&mut cursor).map_err(|error| error.within(Section::Header, None))?;
+
+        // In strict mode, a message that ends at the start of an entry lacks entries.
+        let check_count = |cursor: &Cursor, section: Section, index: usize| {
+            if strict && cursor.remaining() == 0 {
+                return Err(DecodeError::from(DecodeErrorKind::CountMismatch)
+                    .at(cursor.position())
+                    .within(section, Some(index)));
+            }
+
+            Ok(())
+        };
+
+        let questions = cursor;
+        for index in 0..header.qd_count.into() {
+            check_count(&cursor, Section::Question, index)?;
+            QuestionRef::parse(&mut cursor)
+                .map_err(|error| error.within(Section::Question, Some(index)))?;
+        }
+
+        let mut skip_records = |section: Section, count: u16| {
+            let start = cursor;
+            for index in 0..count.into() {
+                check_count(&cursor, section, index)?;
+                RecordRef::parse(&mut cursor)
+                    .map_err(|error| error.within(section, Some(index)))?;
+            }
+
+            Ok::<_, DecodeError>(start)
+        };
+        let answers = skip_records(Section::Answer, header.an_count)?;
+        let authority = skip_records(Section::Authority, header.ns_count)?;
+        let additional = skip_records(Section::Additional, header.ar_count)?;
+
+        // In strict mode, bytes following the last record are taken to be uncounted records.
+        if strict && cursor.remaining() > 0 {
+            return Err(DecodeError::from(DecodeErrorKind::CountMismatch)
+                .at(cursor.position())
+                .within(Section::Additional, Some(header.ar_count.into())));
+        }
+
+        let mut message = MessageRef {
             header,
             questions,
             answers,
             authority,
             additional,
+            opt: None,
+        };
+
+        // A message has at most one OPT record. See section 6.1.1 of RFC 6891.
+        for (index, record) in message.additional().enumerate() {
+            if record.r#type() != resource_record::Type::OPT {
+                continue;
+            }
+
+            let locate = |error: DecodeError| error.within(Section::Additional, Some(index));
+            if message.opt.is_some() {
+                return Err(locate(
+                    DecodeError::from(DecodeErrorKind::IllegalValue(
+                        "failed to parse message: more than one OPT record".to_string(),
+                    ))
+                    .at(record.name().offset()),
+                ));
+            }
+
+            Edns::decode(&record).map_err(locate)?;
+            message.opt = Some(record);
+        }
+
+        // The OPT record holds the upper 8 bits of an extended response code in the first byte
+        // of its TTL. See section 6.1.3 of RFC 6891.
+        if let Some(opt) = message.opt {
+            let upper = u16::from((opt.ttl() >> 24) as u8);
+            let lower = u16::from(message.header.r_code.clone());
+            message.header.r_code = ResponseCode::from(upper << 4 | lower);
+        }
+
+        Ok(message)
+    }
+
+    /// The header of the message.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Iterate over the questions.
+    pub fn questions(&self) -> Questions<'a> {
+        Questions {
+            cursor: self.questions,
+            count: self.header.qd_count,
+        }
+    }
+
+    /// Iterate over the records in the answer section.
+    pub fn answers(&self) -> Records<'a> {
+        Records {
+            cursor: self.answers,
+            count: self.header.an_count,
+        }
+    }
+
+    /// Iterate over the records in the authority section.
+    pub fn authority(&self) -> Records<'a> {
+        Records {
+            cursor: self.authority,
+            count: self.header.ns_count,
+        }
+    }
+
+    /// Iterate over the records in the additional section.
+    ///
+    /// Unlike `Message::additional`, this includes the OPT record.
+    pub fn additional(&self) -> Records<'a> {
+        Records {
+            cursor: self.additional,
+            count: self.header.ar_count,
+        }
+    }
+
+    /// The EDNS information of the message. `None` if the message has no OPT record.
+    pub fn edns(&self) -> Option<Edns> {
+        // The OPT record has been validated by `MessageRef::parse()`.
+        self.opt.as_ref().and_then(|opt| Edns::decode(opt).ok())
+    }
+
+    /// Copy the message into a `Message`, decoding the rdata of all records.
+    pub fn to_message(&self) -> Result<Message, DecodeError> {
+        let to_records = |section: Section, records: Records<'a>| {
+            records
+                .enumerate()
+                .filter(|(_, record)| {
+                    section != Section::Additional || record.r#type() != resource_record::Type::OPT
+                })
+                .map(|(index, record)| {
+                    record
+                        .to_record()
+                        .map_err(|error| error.within(section, Some(index)))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Message {
+            header: self.header.clone(),
+            questions: self
+                .questions()
+                .map(|question| question.to_question())
+                .collect(),
+            answers: to_records(Section::Answer, self.answers())?,
+            authority: to_records(Section::Authority, self.authority())?,
+            additional: to_records(Section::Additional, self.additional())?,
+            edns: self.edns(),
         })
     }
 }
 
-impl Message {
-    /// Serialize the instance into bytes.
-    pub fn into_bytes(self) -> Vec<u8> {
-        let mut output = self.header.into_bytes();
-        output.append(
-            &mut self
-                .questions
-                .into_iter()
-                .flat_map(|e| e.into_bytes())
-                .collect(),
-        );
+impl Debug for MessageRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageRef")
+            .field("header", &self.header)
+            .field("questions", &self.questions().collect::<Vec<_>>())
+            .field("answers", &self.answers().collect::<Vec<_>>())
+            .field("authority", &self.authority().collect::<Vec<_>>())
+            .field("additional", &self.additional().collect::<Vec<_>>())
+            .field("edns", &self.edns())
+            .finish()
+    }
+}
 
-        output.append(
-            &mut self
-                .answers
-                .into_iter()
-                .flat_map(|e| e.into_bytes())
-                .collect(),
-        );
-        output.append(
-            &mut self
-                .authority
-                .into_iter()
-                .flat_map(|e| e.into_bytes())
-                .collect(),
-        );
-        output.append(
-            &mut self
-                .additional
-                .into_iter()
-                .flat_map(|e| e.into_bytes())
-                .collect(),
-        );
+/// An iterator over the questions of a [`MessageRef`].
+#[derive(Clone)]
+pub struct Questions<'a> {
+    cursor: Cursor<'a>,
+    count: u16,
+}
 
-        output
+impl<'a> Iterator for Questions<'a> {
+    type Item = QuestionRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.count = self.count.checked_sub(1)?;
+
+        // The questions have been validated by `MessageRef::parse()`.
+        QuestionRef::parse(&mut self.cursor).ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.count.into(), Some(self.count.into()))
+    }
+}
+
+/// An iterator over the records of a section of a [`MessageRef`].
+#[derive(Clone)]
+pub struct Records<'a> {
+    cursor: Cursor<'a>,
+    count: u16,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = RecordRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.count = self.count.checked_sub(1)?;
+
+        // The records have been validated by `MessageRef::parse()`.
+        RecordRef::parse(&mut self.cursor).ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.count.into(), Some(self.count.into()))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::header::{OpCode, ResponseCode, Type};
-    use crate::sections::{QClass, QType};
+    use crate::edns::{EdnsOption, InfoCode};
+    use crate::rdata::RData;
+    use crate::resource_record::Class;
 
     #[test]
     fn test_serialization_and_deserialization() {
@@ -130,7 +889,8 @@ mod test {
                 truncated: false,
                 recursion_desired: false,
                 recursion_available: false,
-                z: 0,
+                authentic_data: false,
+                checking_disabled: false,
                 r_code: ResponseCode::NoError,
                 qd_count: 1,
                 an_count: 0,
@@ -138,21 +898,646 @@ mod test {
                 ar_count: 0,
             },
             questions: vec![crate::sections::Question {
-                qname: "orangetux.nl".to_string(),
+                qname: "orangetux.nl".parse().unwrap(),
                 qtype: QType::A,
                 qclass: QClass::IN,
             }],
             answers: vec![],
             authority: vec![],
             additional: vec![],
+            edns: None,
         };
 
         // Serialize the message to bytes...
-        let bytes = message.clone().into_bytes();
+        let bytes = message.clone().into_bytes().unwrap();
 
         // ...than deseralize the bytes back to a `Message`.
-        let copy_of_message = Message::try_from(&mut bytes.iter().peekable()).unwrap();
+        let copy_of_message = Message::try_from(bytes.as_slice()).unwrap();
 
         assert_eq!(message, copy_of_message);
     }
+
+    #[test]
+    fn test_deserialization_expands_compressed_names() {
+        let buf = vec![
+            0, 1, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0,
+            // Question for orangetux.nl at offset 12.
+            9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0, 0, 5, 0, 1,
+            // CNAME record pointing to the qname with rdata "www" + pointer to the qname.
+            0xc0, 0x0c, 0, 5, 0, 1, 0, 0, 1, 44, 0, 6, 3, 119, 119, 119, 0xc0, 0x0c,
+        ];
+
+        let message = Message::try_from(buf.as_slice()).unwrap();
+
+        let answer = &message.answers[0];
+        assert_eq!(answer.name, "orangetux.nl".parse().unwrap());
+        assert_eq!(
+            answer.rdata,
+            RData::CNAME("www.orangetux.nl".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_deserialization_reports_location_of_errors() {
+        let buf = vec![
+            0, 1, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0,
+            // Question for orangetux.nl at offset 12.
+            9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0, 0, 1, 0, 1,
+            // A record for the qname.
+            0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 185, 24, 223, 10,
+            // A record with a pointer to itself at offset 46.
+            0xc0, 46, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 185, 24, 223, 10,
+        ];
+
+        let error = Message::try_from(buf.as_slice()).unwrap_err();
+        assert_eq!(
+            error,
+            DecodeError {
+                kind: DecodeErrorKind::InvalidPointer,
+                offset: Some(46),
+                section: Some(Section::Answer),
+                index: Some(1),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "failed to decode bytes at offset 46 in answer 1: compression pointer doesn't point to a prior occurrence of a name"
+        );
+
+        // Truncate the message halfway the rdata of the first answer. The rdata at offset 42
+        // claims more bytes than are left.
+        let error = Message::try_from(&buf[..44]).unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::NotEnoughBytes);
+        assert_eq!(error.offset, Some(42));
+        assert_eq!(error.section, Some(Section::Answer));
+        assert_eq!(error.index, Some(0));
+
+        // Rdata of A records must be 4 bytes long. Keep just the first answer, so the message
+        // is well-formed apart from the rdata.
+        let mut buf = buf[..45].to_vec();
+        buf[7] = 1;
+        buf[41] = 3;
+        let error = Message::try_from(buf.as_slice()).unwrap_err();
+        assert_eq!(error.kind, DecodeErrorKind::RDataLengthMismatch);
+        assert_eq!(error.offset, Some(42));
+    }
+
+    #[test]
+    fn test_message_ref_decodes_rdata_on_demand() {
+        let buf = vec![
+            0, 1, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0,
+            // Question for orangetux.nl at offset 12.
+            9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0, 0, 5, 0, 1,
+            // CNAME record with rdata consisting of a pointer to itself at offset 42.
+            0xc0, 0x0c, 0, 5, 0, 1, 0, 0, 1, 44, 0, 2, 0xc0, 42,
+        ];
+
+        let message = MessageRef::parse(&buf).unwrap();
+        assert_eq!(message.questions().count(), 1);
+
+        let answer = message.answers().next().unwrap();
+        assert_eq!(answer.name().offset(), 30);
+        assert_eq!(answer.name().to_string(), "orangetux.nl");
+        assert_eq!(answer.rdata_bytes(), [0xc0, 42]);
+        assert_eq!(
+            answer.rdata().unwrap_err().kind,
+            DecodeErrorKind::InvalidPointer
+        );
+
+        let error = message.to_message().unwrap_err();
+        assert_eq!(error.offset, Some(42));
+        assert_eq!(error.section, Some(Section::Answer));
+        assert_eq!(error.index, Some(0));
+    }
+
+    #[test]
+    fn test_serialization_compresses_names() {
+        let message = Message {
+            header: Header {
+                id: 1337,
+                message_type: Type::Reply,
+                op_code: OpCode::Query,
+                authoritive_answer: false,
+                truncated: false,
+                recursion_desired: false,
+                recursion_available: false,
+                authentic_data: false,
+                checking_disabled: false,
+                r_code: ResponseCode::NoError,
+                qd_count: 1,
+                an_count: 1,
+                ns_count: 0,
+                ar_count: 0,
+            },
+            questions: vec![crate::sections::Question {
+                qname: "orangetux.nl".parse().unwrap(),
+                qtype: QType::CNAME,
+                qclass: QClass::IN,
+            }],
+            answers: vec![ResourceRecord {
+                name: "orangetux.nl".parse().unwrap(),
+                class: Class::IN,
+                ttl: 300,
+                rdata: RData::CNAME("www.orangetux.nl".parse().unwrap()),
+            }],
+            authority: vec![],
+            additional: vec![],
+            edns: None,
+        };
+
+        let bytes = message.clone().into_bytes().unwrap();
+
+        // The owner name and the suffix of the canonical name both point to the qname at
+        // offset 12.
+        assert_eq!(
+            bytes[30..],
+            [0xc0, 0x0c, 0, 5, 0, 1, 0, 0, 1, 44, 0, 6, 3, 119, 119, 119, 0xc0, 0x0c]
+        );
+        assert_eq!(message, Message::try_from(bytes.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_serialization_derives_section_counts() {
+        let buf = [
+            vec![0, 1, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0],
+            vec![
+                9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0, 0, 1, 0, 1,
+            ],
+        ]
+        .concat();
+        let mut message = Message::try_from(buf.as_slice()).unwrap();
+
+        // The header lies about the number of entries...
+        message.header.qd_count = 3;
+        message.header.ar_count = 2;
+        message.answers.push(ResourceRecord {
+            name: "orangetux.nl".parse().unwrap(),
+            class: Class::IN,
+            ttl: 300,
+            rdata: RData::A([185, 24, 223, 10].into()),
+        });
+
+        // ...but the serialized header doesn't.
+        let bytes = message.into_bytes().unwrap();
+        assert_eq!(bytes[4..12], [0, 1, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(
+            MessageRef::parse_strict(&bytes).unwrap().answers().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_strict_parsing_flags_count_mismatches() {
+        let buf = [
+            vec![0, 1, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0],
+            vec![
+                9, 111, 114, 97, 110, 103, 101, 116, 117, 120, 2, 110, 108, 0, 0, 1, 0, 1,
+            ],
+            vec![0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 185, 24, 223, 10],
+        ]
+        .concat();
+        assert!(MessageRef::parse_strict(&buf).is_ok());
+
+        // The header claims an authority record that isn't there.
+        let mut missing = buf.clone();
+        missing[9] = 1;
+        assert_eq!(
+            MessageRef::parse(&missing).unwrap_err().kind,
+            DecodeErrorKind::NotEnoughBytes
+        );
+        assert_eq!(
+            MessageRef::parse_strict(&missing).unwrap_err(),
+            DecodeError {
+                kind: DecodeErrorKind::CountMismatch,
+                offset: Some(46),
+                section: Some(Section::Authority),
+                index: Some(0),
+            }
+        );
+
+        // The header doesn't count the answer.
+        let mut uncounted = buf.clone();
+        uncounted[7] = 0;
+        assert!(MessageRef::parse(&uncounted).is_ok());
+        assert_eq!(
+            MessageRef::parse_strict(&uncounted).unwrap_err(),
+            DecodeError {
+                kind: DecodeErrorKind::CountMismatch,
+                offset: Some(30),
+                section: Some(Section::Additional),
+                index: Some(0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_builder_keeps_counts_in_sync() {
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::MX)
+            .id(1337)
+            .build();
+        assert_eq!(query.header.id, 1337);
+        assert_eq!(query.header.message_type, Type::Query);
+        assert!(query.header.recursion_desired);
+        assert_eq!(query.questions[0].qclass, QClass::IN);
+        assert_eq!(query.header.qd_count, 1);
+
+        let record = ResourceRecord {
+            name: "orangetux.nl".parse().unwrap(),
+            class: Class::IN,
+            ttl: 300,
+            rdata: RData::NS("ns1.orangetux.nl".parse().unwrap()),
+        };
+        let response = query
+            .response()
+            .authoritative(true)
+            .rcode(ResponseCode::NameError)
+            .authority(record.clone())
+            .additional(record.clone())
+            .additional(record)
+            .build();
+
+        assert_eq!(response.header.id, 1337);
+        assert_eq!(response.header.message_type, Type::Reply);
+        assert_eq!(response.header.op_code, OpCode::Query);
+        assert!(response.header.recursion_desired);
+        assert!(response.header.authoritive_answer);
+        assert_eq!(response.header.r_code, ResponseCode::NameError);
+        assert_eq!(response.questions, query.questions);
+        assert_eq!(
+            (
+                response.header.qd_count,
+                response.header.an_count,
+                response.header.ns_count,
+                response.header.ar_count
+            ),
+            // The OPT record counts as an additional record.
+            (1, 0, 1, 3)
+        );
+    }
+
+    #[test]
+    fn test_extended_response_codes() {
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+
+        // The OPT record carries the upper bits of the response code.
+        let bytes = query
+            .response()
+            .rcode(ResponseCode::BadCookie)
+            .edns(Some(Edns::default()))
+            .build()
+            .into_bytes()
+            .unwrap();
+        assert_eq!(bytes[3] & 0b1111, 7);
+        assert_eq!(bytes[10..12], [0, 1]);
+        assert_eq!(
+            bytes[bytes.len() - 11..],
+            [0, 0, 41, 4, 208, 1, 0, 0, 0, 0, 0]
+        );
+
+        let message = Message::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(message.header.r_code, ResponseCode::BadCookie);
+        assert_eq!(
+            MessageRef::parse(&bytes).unwrap().header().r_code,
+            ResponseCode::BadCookie
+        );
+
+        // Every 12-bit response code survives a round trip, and so does the rest of the OPT TTL.
+        let edns = Edns {
+            udp_payload_size: 4096,
+            dnssec_ok: true,
+            ..Edns::default()
+        };
+        for value in 0..4096 {
+            let message = query
+                .response()
+                .rcode(ResponseCode::from(value))
+                .edns(Some(edns.clone()))
+                .build();
+            let decoded = Message::try_from(message.into_bytes().unwrap().as_slice()).unwrap();
+
+            assert_eq!(decoded.header.r_code, ResponseCode::from(value));
+            assert_eq!(decoded.edns, Some(edns.clone()));
+        }
+
+        // Without EDNS information, no OPT record is added: an extended response code becomes
+        // SERVFAIL.
+        let bytes = query
+            .response()
+            .edns(None)
+            .rcode(ResponseCode::BadCookie)
+            .build()
+            .into_bytes()
+            .unwrap();
+        assert_eq!(bytes[3] & 0b1111, 2);
+        assert_eq!(bytes[10..12], [0, 0]);
+        let message = Message::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(message.header.r_code, ResponseCode::ServerFailure);
+        assert_eq!(message.edns, None);
+
+        // Without an OPT record, small response codes are stored in the header only.
+        let message = query
+            .response()
+            .edns(None)
+            .rcode(ResponseCode::Refused)
+            .build();
+        assert!(message.into_bytes().unwrap()[12..].ends_with(&[0, 1, 0, 1]));
+    }
+
+    #[test]
+    fn test_edns_is_taken_from_additional_section() {
+        let record = ResourceRecord {
+            name: "orangetux.nl".parse().unwrap(),
+            class: Class::IN,
+            ttl: 300,
+            rdata: RData::A([185, 24, 223, 10].into()),
+        };
+        let edns = Edns {
+            udp_payload_size: 4096,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![EdnsOption::Unknown {
+                code: 65001,
+                data: vec![1, 2, 3],
+            }],
+        };
+        let message = Message::query("orangetux.nl".parse().unwrap(), QType::A)
+            .additional(record.clone())
+            .edns(Some(edns.clone()))
+            .build();
+        assert_eq!(message.header.ar_count, 2);
+
+        let bytes = message.clone().into_bytes().unwrap();
+        assert_eq!(
+            bytes[bytes.len() - 18..],
+            [0, 0, 41, 16, 0, 0, 0, 128, 0, 0, 7, 253, 233, 0, 3, 1, 2, 3]
+        );
+
+        let decoded = Message::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.additional, vec![record]);
+        assert_eq!(decoded.edns, Some(edns));
+        assert_eq!(decoded, message);
+
+        // The borrowed view shows the OPT record as it appears on the wire.
+        let view = MessageRef::parse(&bytes).unwrap();
+        assert_eq!(view.additional().count(), 2);
+        assert_eq!(view.edns(), decoded.edns);
+
+        // Messages with more than one OPT record are malformed.
+        let mut bytes = bytes[..bytes.len() - 7].to_vec();
+        bytes[11] = 3;
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let error = Message::try_from(&bytes[..]).unwrap_err();
+        assert_eq!(error.section, Some(Section::Additional));
+        assert_eq!(error.index, Some(2));
+
+        // The OPT record must be owned by the root.
+        let mut bytes = message.into_bytes().unwrap();
+        let opt = bytes.len() - 18;
+        bytes.splice(opt..opt + 1, [0xc0, 0x0c]);
+        assert!(MessageRef::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_response_to_unsupported_edns_version_is_badvers() {
+        let mut query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        let response = query.response().build();
+        assert_eq!(response.header.r_code, ResponseCode::NoError);
+        assert_eq!(response.edns, Some(Edns::default()));
+
+        query.edns = Some(Edns {
+            version: 1,
+            dnssec_ok: true,
+            ..Edns::default()
+        });
+        let response = query.response().build();
+        assert_eq!(response.header.r_code, ResponseCode::BadVersion);
+        let edns = response.edns.clone().unwrap();
+        assert_eq!(edns.version, 0);
+        assert!(edns.dnssec_ok);
+
+        // BADVERS is 16, so the OPT record carries its upper bits.
+        let bytes = response.into_bytes().unwrap();
+        assert_eq!(bytes[3] & 0b1111, 0);
+        assert_eq!(
+            Message::try_from(bytes.as_slice()).unwrap().header.r_code,
+            ResponseCode::BadVersion
+        );
+
+        // Queries without EDNS get responses without EDNS.
+        query.edns = None;
+        assert_eq!(query.response().build().edns, None);
+    }
+
+    #[test]
+    fn test_response_sets_client_subnet_scope() {
+        let subnet = ClientSubnet::new("192.0.2.123".parse().unwrap(), 24).unwrap();
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A)
+            .edns(Some(Edns {
+                options: vec![EdnsOption::ClientSubnet(subnet.clone())],
+                ..Edns::default()
+            }))
+            .build();
+        let query = Message::try_from(query.into_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(query.client_subnet(), Some(&subnet));
+
+        let response = query.response().client_subnet_scope(16).build();
+        let scoped = response.client_subnet().unwrap();
+        assert_eq!(scoped.address(), subnet.address());
+        assert_eq!(scoped.source_prefix_length(), 24);
+        assert_eq!(scoped.scope_prefix_length(), 16);
+
+        // The scope is capped at the length of the address.
+        let response = query.response().client_subnet_scope(255).build();
+        assert_eq!(response.client_subnet().unwrap().scope_prefix_length(), 32);
+
+        // Without the option in the query, there's nothing to scope.
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        let response = query.response().client_subnet_scope(16).build();
+        assert_eq!(response.client_subnet(), None);
+    }
+
+    #[test]
+    fn test_response_explains_errors() {
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A)
+            .edns(Some(Edns {
+                options: vec![EdnsOption::Nsid(vec![])],
+                ..Edns::default()
+            }))
+            .build();
+        let query = Message::try_from(query.into_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(query.nsid(), Some([].as_slice()));
+
+        let response = query
+            .response()
+            .rcode(ResponseCode::ServerFailure)
+            .extended_error(ExtendedError::new(InfoCode::DnssecBogus, ""))
+            .extended_error(ExtendedError::new(InfoCode::NetworkError, "timed out"))
+            .nsid(b"ns1".to_vec())
+            .build();
+        let response = Message::try_from(response.into_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(response.nsid(), Some(b"ns1".as_slice()));
+        assert_eq!(
+            response
+                .extended_errors()
+                .map(|error| error.info_code.clone())
+                .collect::<Vec<_>>(),
+            [InfoCode::DnssecBogus, InfoCode::NetworkError]
+        );
+
+        // Responses to queries without EDNS can't carry options.
+        let mut query = query;
+        query.edns = None;
+        let response = query
+            .response()
+            .extended_error(ExtendedError::new(InfoCode::Blocked, ""))
+            .nsid(b"ns1".to_vec())
+            .build();
+        assert_eq!(response.edns, None);
+        assert_eq!(response.extended_errors().count(), 0);
+    }
+
+    #[test]
+    fn test_padding_rounds_up_to_block_length() {
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        assert!(!query.is_padded());
+        assert_eq!(query.clone().into_bytes().unwrap().len(), 41);
+
+        let bytes = query
+            .clone()
+            .into_padded_bytes(PaddingPolicy::Recommended)
+            .unwrap();
+        assert_eq!(bytes.len(), 128);
+        let padded = Message::try_from(bytes.as_slice()).unwrap();
+        assert!(padded.is_padded());
+
+        // Padding replaces earlier padding.
+        let response = padded.response().build();
+        let bytes = response
+            .into_padded_bytes(PaddingPolicy::Recommended)
+            .unwrap();
+        assert_eq!(bytes.len(), 468);
+        let bytes = padded
+            .into_padded_bytes(PaddingPolicy::BlockLength(64))
+            .unwrap();
+        assert_eq!(bytes.len(), 64);
+
+        // A message that fills a block exactly gets an empty Padding option.
+        let bytes = query
+            .clone()
+            .into_padded_bytes(PaddingPolicy::BlockLength(45))
+            .unwrap();
+        assert_eq!(bytes.len(), 45);
+        assert_eq!(bytes[41..], [0, 12, 0, 0]);
+
+        // Without EDNS information, there's no OPT record to pad.
+        let mut query = query;
+        query.edns = None;
+        let bytes = query.into_padded_bytes(PaddingPolicy::Recommended).unwrap();
+        assert_eq!(bytes.len(), 30);
+    }
+
+    #[test]
+    fn test_truncation_drops_whole_rrsets() {
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        assert_eq!(query.udp_payload_size(), 1232);
+
+        let record = |name: &str, address: [u8; 4]| ResourceRecord {
+            name: name.parse().unwrap(),
+            class: Class::IN,
+            ttl: 300,
+            rdata: RData::A(address.into()),
+        };
+        let response = query
+            .response()
+            .answer(record("orangetux.nl", [192, 0, 2, 1]))
+            .answer(record("orangetux.nl", [192, 0, 2, 2]))
+            .authority(record("ns1.orangetux.nl", [192, 0, 2, 3]))
+            .additional(record("ns2.orangetux.nl", [192, 0, 2, 4]))
+            .additional(record("ns3.orangetux.nl", [192, 0, 2, 5]))
+            .additional(record("ns2.orangetux.nl", [192, 0, 2, 6]))
+            .build();
+
+        let bytes = response.clone().into_bytes().unwrap();
+        assert_eq!(
+            response.clone().into_truncated_bytes(bytes.len()).unwrap(),
+            bytes
+        );
+
+        // The RRset of ns2.orangetux.nl goes first, and both of its records go.
+        let bytes = response
+            .clone()
+            .into_truncated_bytes(bytes.len() - 1)
+            .unwrap();
+        let truncated = MessageRef::parse_strict(&bytes)
+            .unwrap()
+            .to_message()
+            .unwrap();
+        assert!(truncated.header.truncated);
+        assert_eq!(
+            truncated.additional,
+            [record("ns3.orangetux.nl", [192, 0, 2, 5])]
+        );
+        assert_eq!(truncated.authority.len(), 1);
+        assert_eq!(truncated.answers.len(), 2);
+        assert!(truncated.edns.is_some());
+
+        // Then the rest of the additional section, the authority section and the answer section.
+        let bytes = response.clone().into_truncated_bytes(0).unwrap();
+        let truncated = MessageRef::parse_strict(&bytes)
+            .unwrap()
+            .to_message()
+            .unwrap();
+        assert!(truncated.header.truncated);
+        assert_eq!(
+            (
+                truncated.answers.len(),
+                truncated.authority.len(),
+                truncated.additional.len()
+            ),
+            (0, 0, 0)
+        );
+        assert_eq!(truncated.questions, response.questions);
+        assert!(truncated.edns.is_some());
+
+        // Without EDNS information, clients accept 512 bytes.
+        let mut query = query;
+        query.edns = None;
+        assert_eq!(query.udp_payload_size(), 512);
+    }
+
+    #[test]
+    fn test_truncation_of_large_responses() {
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        let mut response = query.response();
+        for index in 0..1000u16 {
+            let [high, low] = index.to_be_bytes();
+            for address in [[192, 0, high, low], [198, 51, high, low]] {
+                response = response.additional(ResourceRecord {
+                    name: format!("ns{}.orangetux.nl", index).parse().unwrap(),
+                    class: Class::IN,
+                    ttl: 300,
+                    rdata: RData::A(address.into()),
+                });
+            }
+        }
+        let response = response.build();
+
+        let bytes = response.clone().into_truncated_bytes(1232).unwrap();
+        assert!(bytes.len() <= 1232);
+        let truncated = MessageRef::parse_strict(&bytes)
+            .unwrap()
+            .to_message()
+            .unwrap();
+        assert!(truncated.header.truncated);
+        assert!(truncated.edns.is_some());
+
+        // The first RRsets are kept, whole, and the next one doesn't fit.
+        let kept = truncated.additional.len();
+        assert!(kept > 0 && kept.is_multiple_of(2));
+        assert_eq!(truncated.additional, response.additional[..kept]);
+
+        let mut more = truncated;
+        more.additional = response.additional[..kept + 2].to_vec();
+        assert!(more.into_bytes().unwrap().len() > 1232);
+    }
 }
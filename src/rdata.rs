@@ -0,0 +1,1649 @@
+//! Serialize and deserialize the rdata of `ResourceRecord`s.
+use crate::cursor::Cursor;
+use crate::dane::{CertificateUsage, FingerprintType, MatchingType, Selector, SshfpAlgorithm};
+use crate::domain_name::{take_name, Compressor, DomainName};
+use crate::resource_record::Type;
+use crate::svcb::{SvcParam, SvcParams};
+use crate::{DecodeError, DecodeErrorKind, EncodeError};
+use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The rdata of a `ResourceRecord`, decoded according to the type of the record.
+///
+/// The formats are documented in section [`3.3. Standard RRs`] and [`3.4. Internet specific
+/// RRs`] of RFC 1035, in section 2.2 of RFC 3596 for AAAA, in RFC 2782 for SRV, in section 4.1
+/// of RFC 3403 for NAPTR, in section 2.1 of RFC 6672 for DNAME, in section 3.1 of RFC 4255 for
+/// SSHFP, in section 2.1 of RFC 6698 for TLSA, in section 2.1 of RFC 7929 for OPENPGPKEY, in
+/// section 4 of RFC 7553 for URI, in section 2 of RFC 9460 for SVCB and HTTPS and in section 4.1
+/// of RFC 8659 for CAA. The rdata of types this library doesn't know about is kept as is, see
+/// [`RFC 3597`].
+///
+/// `RData` is printed in, and parsed from, presentation format with `RData::from_presentation()`.
+/// Rdata of unknown types, and of NULL records, is printed in the generic `\# <length> <hex>`
+/// format of RFC 3597:
+///
+/// ```
+/// use dns::rdata::RData;
+/// use dns::resource_record::Type;
+///
+/// let rdata = RData::from_generic(&Type::Unknown(65280), r"\# 4 0A000001").unwrap();
+/// assert_eq!(rdata, RData::Unknown { r#type: 65280, data: vec![10, 0, 0, 1] });
+/// assert_eq!(rdata.to_string(), r"\# 4 0a000001");
+///
+/// // The generic format can be used for known types too.
+/// let rdata = RData::from_generic(&Type::A, r"\# 4 0A000001").unwrap();
+/// assert_eq!(rdata.to_string(), "10.0.0.1");
+/// ```
+///
+/// [`3.3. Standard RRs`]: https://www.rfc-editor.org/rfc/rfc1035#section-3.3
+/// [`3.4. Internet specific RRs`]: https://www.rfc-editor.org/rfc/rfc1035#section-3.4
+/// [`RFC 3597`]: https://www.rfc-editor.org/rfc/rfc3597
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum RData {
+    /// A host address.
+    A(Ipv4Addr),
+
+    /// A host that should be authoritative for the domain.
+    NS(DomainName),
+
+    /// A host which has a mail agent for the domain (obsolete - use MX).
+    MD(DomainName),
+
+    /// A host which has a mail agent for the domain (obsolete - use MX).
+    MF(DomainName),
+
+    /// The canonical or primary name for the owner.
+    CNAME(DomainName),
+
+    /// The start of a zone of authority.
+    SOA {
+        /// The name server that was the original or primary source of data for this zone.
+        mname: DomainName,
+        /// The mailbox of the person responsible for this zone.
+        rname: DomainName,
+        /// The version number of the original copy of the zone.
+        serial: u32,
+        /// Interval in seconds before the zone should be refreshed.
+        refresh: u32,
+        /// Interval in seconds that should elapse before a failed refresh should be retried.
+        retry: u32,
+        /// Upper limit in seconds on the time interval that can elapse before the zone is no
+        /// longer authoritative.
+        expire: u32,
+        /// The minimum TTL that should be exported with any RR from this zone.
+        minimum: u32,
+    },
+
+    /// A host which has the specified mailbox.
+    MB(DomainName),
+
+    /// A mailbox which is a member of the mail group.
+    MG(DomainName),
+
+    /// A mailbox which is the proper rename of the specified mailbox.
+    MR(DomainName),
+
+    /// Anything at all, as long as it's 65535 bytes or less.
+    Null(Vec<u8>),
+
+    /// A well known service description.
+    WKS {
+        /// The address of the host.
+        address: Ipv4Addr,
+        /// An IP protocol number.
+        protocol: u8,
+        /// A bit map with one bit per port of the protocol.
+        bitmap: Vec<u8>,
+    },
+
+    /// A pointer to some location in the domain name space.
+    PTR(DomainName),
+
+    /// Host information.
+    HINFO {
+        /// The CPU type.
+        cpu: CharacterString,
+        /// The operating system type.
+        os: CharacterString,
+    },
+
+    /// Mailbox or mail list information.
+    MINFO {
+        /// A mailbox which is responsible for the mailing list or mailbox.
+        rmailbx: DomainName,
+        /// A mailbox which is to receive error messages related to the mailing list or mailbox.
+        emailbx: DomainName,
+    },
+
+    /// Mail exchange.
+    MX {
+        /// The preference given to this RR among others at the same owner. Lower values are
+        /// preferred.
+        preference: u16,
+        /// A host willing to act as a mail exchange for the owner name.
+        exchange: DomainName,
+    },
+
+    /// One or more character-strings.
+    TXT(Vec<CharacterString>),
+
+    /// An IPv6 host address.
+    AAAA(Ipv6Addr),
+
+    /// The location of a service.
+    SRV {
+        /// The priority of the target. Clients try targets with lower values first.
+        priority: u16,
+        /// The relative weight of targets with the same priority. Clients pick targets with
+        /// higher values more often.
+        weight: u16,
+        /// The port of the service on the target.
+        port: u16,
+        /// The host providing the service. The root means the service isn't available.
+        target: DomainName,
+    },
+
+    /// A rule rewriting a string into a domain name or URI, see RFC 3403.
+    NAPTR {
+        /// The order in which rules must be processed. Lower values come first.
+        order: u16,
+        /// The order in which rules with the same `order` should be processed. Lower values
+        /// come first.
+        preference: u16,
+        /// Flags controlling the rewriting, like "S", "A", "U" or "P".
+        flags: CharacterString,
+        /// The services available down this rewrite path, like "SIP+D2U".
+        services: CharacterString,
+        /// A substitution expression applied to the original string. Empty if
+        /// `replacement` is used.
+        regexp: CharacterString,
+        /// The next domain name to query. The root if `regexp` is used.
+        replacement: DomainName,
+    },
+
+    /// The name replacing the owner in names below it, see RFC 6672 and the `zone` module.
+    DNAME(DomainName),
+
+    /// The fingerprint of an SSH host key, see the `dane` module.
+    SSHFP {
+        /// The algorithm of the key.
+        algorithm: SshfpAlgorithm,
+        /// The hash function that computed the fingerprint.
+        fingerprint_type: FingerprintType,
+        /// The fingerprint. Printed in hexadecimal.
+        fingerprint: Vec<u8>,
+    },
+
+    /// The certificate, or public key, of a TLS server, see the `dane` module.
+    TLSA {
+        /// How the record constrains the certificate of the server.
+        usage: CertificateUsage,
+        /// The part of the certificate that `data` matches.
+        selector: Selector,
+        /// How `data` is compared with the selected part of the certificate.
+        matching_type: MatchingType,
+        /// The certificate association data. Printed in hexadecimal.
+        data: Vec<u8>,
+    },
+
+    /// An OpenPGP public key, in the binary format of RFC 4880. Printed in base64.
+    OPENPGPKEY(Vec<u8>),
+
+    /// The endpoint of a service and its parameters, see RFC 9460 and the `svcb` module.
+    SVCB {
+        /// The priority of the record. 0 means the record is an alias for `target`, in which
+        /// case there are no parameters. Clients try records with lower values first otherwise.
+        priority: u16,
+        /// The host providing the service, or the alias. The root means the owner of the
+        /// record, or, for an alias, that the service isn't available.
+        target: DomainName,
+        /// The parameters of the service, like its port.
+        params: SvcParams,
+    },
+
+    /// The endpoint of an HTTPS service and its parameters. Like SVCB, for HTTP origins.
+    HTTPS {
+        /// The priority of the record. 0 means the record is an alias for `target`, in which
+        /// case there are no parameters. Clients try records with lower values first otherwise.
+        priority: u16,
+        /// The host providing the service, or the alias. The root means the owner of the
+        /// record, or, for an alias, that the service isn't available.
+        target: DomainName,
+        /// The parameters of the service, like its port.
+        params: SvcParams,
+    },
+
+    /// A URI.
+    URI {
+        /// The priority of the target. Clients try targets with lower values first.
+        priority: u16,
+        /// The relative weight of targets with the same priority. Clients pick targets with
+        /// higher values more often.
+        weight: u16,
+        /// The URI. Unlike a character-string, it's not limited to 255 bytes.
+        target: Vec<u8>,
+    },
+
+    /// A property of the certification authorities that may issue certificates for the owner,
+    /// see the `caa` module.
+    CAA {
+        /// Flags, like `caa::ISSUER_CRITICAL`.
+        flags: u8,
+        /// The property, like "issue". 1 to 15 letters and digits; encoding fails otherwise.
+        tag: String,
+        /// The value of the property. Unlike a character-string, it's not limited to 255 bytes.
+        value: Vec<u8>,
+    },
+
+    /// Rdata of a type this library doesn't know about. Domain names in it are not expanded.
+    Unknown {
+        /// The value of the type of the record.
+        r#type: u16,
+        /// The rdata.
+        data: Vec<u8>,
+    },
+}
+
+impl RData {
+    /// The type of `ResourceRecord` this rdata belongs to.
+    pub fn r#type(&self) -> Type {
+        match self {
+            Self::A(_) => Type::A,
+            Self::NS(_) => Type::NS,
+            Self::MD(_) => Type::MD,
+            Self::MF(_) => Type::MF,
+            Self::CNAME(_) => Type::CNAME,
+            Self::SOA { .. } => Type::SOA,
+            Self::MB(_) => Type::MB,
+            Self::MG(_) => Type::MG,
+            Self::MR(_) => Type::MR,
+            Self::Null(_) => Type::Null,
+            Self::WKS { .. } => Type::WKS,
+            Self::PTR(_) => Type::PTR,
+            Self::HINFO { .. } => Type::HINFO,
+            Self::MINFO { .. } => Type::MINFO,
+            Self::MX { .. } => Type::MX,
+            Self::TXT(_) => Type::TXT,
+            Self::AAAA(_) => Type::AAAA,
+            Self::SRV { .. } => Type::SRV,
+            Self::NAPTR { .. } => Type::NAPTR,
+            Self::DNAME(_) => Type::DNAME,
+            Self::SSHFP { .. } => Type::SSHFP,
+            Self::TLSA { .. } => Type::TLSA,
+            Self::OPENPGPKEY(_) => Type::OPENPGPKEY,
+            Self::SVCB { .. } => Type::SVCB,
+            Self::HTTPS { .. } => Type::HTTPS,
+            Self::URI { .. } => Type::URI,
+            Self::CAA { .. } => Type::CAA,
+            Self::Unknown { r#type, .. } => Type::from(*r#type),
+        }
+    }
+
+    /// Parse rdata of the given `type` in the generic format of section 5 of RFC 3597:
+    /// `\#`, followed by the length of the rdata and the rdata in hexadecimal.
+    pub fn from_generic(r#type: &Type, value: &str) -> Result<Self, DecodeError> {
+        let illegal_value = || {
+            DecodeError::from(DecodeErrorKind::IllegalValue(format!(
+                "failed to parse value as generic rdata: {} is not a valid value",
+                value
+            )))
+        };
+
+        let mut tokens = value.split_whitespace();
+        if tokens.next() != Some("\\#") {
+            return Err(illegal_value());
+        }
+
+        let rdlength: u16 = tokens
+            .next()
+            .and_then(|length| length.parse().ok())
+            .ok_or_else(illegal_value)?;
+        let data = from_hex(&tokens.collect::<String>()).ok_or_else(illegal_value)?;
+        if data.len() != usize::from(rdlength) {
+            return Err(illegal_value());
+        }
+
+        Self::decode(&mut Cursor::new(&data), r#type, rdlength)
+    }
+
+    /// Parse rdata of the given `type` in presentation format, like `10 orangetux.nl.` for MX.
+    ///
+    /// Character-strings may be quoted, and may contain the escapes `\X` and `\DDD` of section
+    /// 5.1 of RFC 1035. Domain names are taken as fully qualified, with or without trailing
+    /// dot. Rdata of any type can be given in the generic format, see
+    /// `RData::from_generic()`, which is the only format for NULL records and unknown types.
+    ///
+    /// ```
+    /// use dns::rdata::RData;
+    /// use dns::resource_record::Type;
+    ///
+    /// let rdata = RData::from_presentation(&Type::SRV, "10 60 5060 bigbox.example.com.").unwrap();
+    /// assert_eq!(
+    ///     rdata,
+    ///     RData::SRV {
+    ///         priority: 10,
+    ///         weight: 60,
+    ///         port: 5060,
+    ///         target: "bigbox.example.com".parse().unwrap(),
+    ///     }
+    /// );
+    ///
+    /// let rdata = RData::from_presentation(&Type::TXT, r#"v=spf1 "a \"b" c"#).unwrap();
+    /// assert_eq!(rdata.to_string(), r#""v=spf1" "a \"b" "c""#);
+    /// ```
+    pub fn from_presentation(r#type: &Type, value: &str) -> Result<Self, DecodeError> {
+        if value.split_whitespace().next() == Some("\\#") {
+            return Self::from_generic(r#type, value);
+        }
+
+        let illegal_value = || {
+            DecodeError::from(DecodeErrorKind::IllegalValue(format!(
+                "failed to parse value as {} rdata: {} is not a valid value",
+                r#type, value
+            )))
+        };
+
+        let mut tokens = Tokens::new(value).ok_or_else(illegal_value)?;
+        let rdata = Self::parse(&mut tokens, r#type).ok_or_else(illegal_value)?;
+        if !tokens.is_empty() {
+            return Err(illegal_value());
+        }
+
+        Ok(rdata)
+    }
+
+    // Parse rdata of the given `type` from `tokens` in presentation format.
+    fn parse(tokens: &mut Tokens<'_>, r#type: &Type) -> Option<Self> {
+        let rdata = match r#type {
+            Type::A => Self::A(tokens.number()?),
+            Type::NS => Self::NS(tokens.name()?),
+            Type::MD => Self::MD(tokens.name()?),
+            Type::MF => Self::MF(tokens.name()?),
+            Type::CNAME => Self::CNAME(tokens.name()?),
+            Type::SOA => Self::SOA {
+                mname: tokens.name()?,
+                rname: tokens.name()?,
+                serial: tokens.number()?,
+                refresh: tokens.number()?,
+                retry: tokens.number()?,
+                expire: tokens.number()?,
+                minimum: tokens.number()?,
+            },
+            Type::MB => Self::MB(tokens.name()?),
+            Type::MG => Self::MG(tokens.name()?),
+            Type::MR => Self::MR(tokens.name()?),
+            Type::WKS => {
+                let address = tokens.number()?;
+                let protocol = tokens.number()?;
+                let mut bitmap = vec![];
+                while !tokens.is_empty() {
+                    let port: u16 = tokens.number()?;
+                    let index = usize::from(port / 8);
+                    if bitmap.len() <= index {
+                        bitmap.resize(index + 1, 0);
+                    }
+                    bitmap[index] |= 0b1000_0000 >> (port % 8);
+                }
+                Self::WKS {
+                    address,
+                    protocol,
+                    bitmap,
+                }
+            }
+            Type::PTR => Self::PTR(tokens.name()?),
+            Type::HINFO => Self::HINFO {
+                cpu: tokens.character_string()?,
+                os: tokens.character_string()?,
+            },
+            Type::MINFO => Self::MINFO {
+                rmailbx: tokens.name()?,
+                emailbx: tokens.name()?,
+            },
+            Type::MX => Self::MX {
+                preference: tokens.number()?,
+                exchange: tokens.name()?,
+            },
+            Type::TXT => {
+                let mut strings = vec![];
+                while !tokens.is_empty() {
+                    strings.push(tokens.character_string()?);
+                }
+                Self::TXT(strings)
+            }
+            Type::AAAA => Self::AAAA(tokens.number()?),
+            Type::SRV => Self::SRV {
+                priority: tokens.number()?,
+                weight: tokens.number()?,
+                port: tokens.number()?,
+                target: tokens.name()?,
+            },
+            Type::NAPTR => Self::NAPTR {
+                order: tokens.number()?,
+                preference: tokens.number()?,
+                flags: tokens.character_string()?,
+                services: tokens.character_string()?,
+                regexp: tokens.character_string()?,
+                replacement: tokens.name()?,
+            },
+            Type::DNAME => Self::DNAME(tokens.name()?),
+            Type::SSHFP => Self::SSHFP {
+                algorithm: tokens.number::<u8>()?.into(),
+                fingerprint_type: tokens.number::<u8>()?.into(),
+                fingerprint: tokens.hex()?,
+            },
+            Type::TLSA => Self::TLSA {
+                usage: tokens.number::<u8>()?.into(),
+                selector: tokens.number::<u8>()?.into(),
+                matching_type: tokens.number::<u8>()?.into(),
+                data: tokens.hex()?,
+            },
+            Type::OPENPGPKEY => Self::OPENPGPKEY(tokens.base64()?),
+            Type::SVCB => Self::SVCB {
+                priority: tokens.number()?,
+                target: tokens.name()?,
+                params: tokens.svc_params()?,
+            },
+            Type::HTTPS => Self::HTTPS {
+                priority: tokens.number()?,
+                target: tokens.name()?,
+                params: tokens.svc_params()?,
+            },
+            Type::URI => Self::URI {
+                priority: tokens.number()?,
+                weight: tokens.number()?,
+                target: tokens.bytes().filter(|target| !target.is_empty())?,
+            },
+            Type::CAA => Self::CAA {
+                flags: tokens.number()?,
+                tag: tokens.token().filter(|tag| is_caa_tag(tag))?.to_string(),
+                value: tokens.bytes()?,
+            },
+            // These only have the generic format.
+            Type::Null | Type::OPT | Type::Unknown(_) => return None,
+        };
+
+        Some(rdata)
+    }
+
+    /// Serialize the instance into bytes, without compression.
+    ///
+    /// Fails if the rdata holds a value that can't be serialized, like a parameter of an SVCB
+    /// record longer than 65535 bytes.
+    pub fn into_bytes(self) -> Result<Vec<u8>, EncodeError> {
+        let mut output = vec![];
+        self.write(&mut output, None)?;
+
+        Ok(output)
+    }
+
+    /// Serialize the instance into `output`, compressing domain names using `compressor`.
+    ///
+    /// Only the names in the rdata of NS, CNAME, SOA, MX and PTR records are compressed. Names
+    /// in the rdata of later types, like SRV, must not be compressed, see section 4 of RFC 3597.
+    pub(crate) fn encode(
+        self,
+        output: &mut Vec<u8>,
+        compressor: &mut Compressor,
+    ) -> Result<(), EncodeError> {
+        self.write(output, Some(compressor))
+    }
+
+    fn write(
+        self,
+        output: &mut Vec<u8>,
+        mut compressor: Option<&mut Compressor>,
+    ) -> Result<(), EncodeError> {
+        // Writes a name that may be compressed.
+        let mut write_name = |name: &DomainName, output: &mut Vec<u8>| match compressor {
+            Some(ref mut compressor) => compressor.write_name(name, output),
+            None => output.append(&mut name.clone().into_bytes()),
+        };
+
+        match self {
+            Self::A(address) => output.extend_from_slice(&address.octets()),
+            Self::NS(name) | Self::CNAME(name) | Self::PTR(name) => write_name(&name, output),
+            Self::MD(name)
+            | Self::MF(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::DNAME(name) => output.append(&mut name.into_bytes()),
+            Self::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                write_name(&mname, output);
+                write_name(&rname, output);
+                for value in [serial, refresh, retry, expire, minimum] {
+                    output.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            Self::Null(data) => output.extend(data),
+            Self::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                output.extend_from_slice(&address.octets());
+                output.push(protocol);
+                output.extend(bitmap);
+            }
+            Self::HINFO { cpu, os } => {
+                output.append(&mut cpu.into_bytes());
+                output.append(&mut os.into_bytes());
+            }
+            Self::MINFO { rmailbx, emailbx } => {
+                output.append(&mut rmailbx.into_bytes());
+                output.append(&mut emailbx.into_bytes());
+            }
+            Self::MX {
+                preference,
+                exchange,
+            } => {
+                output.extend_from_slice(&preference.to_be_bytes());
+                write_name(&exchange, output);
+            }
+            Self::TXT(strings) => {
+                for string in strings {
+                    output.append(&mut string.into_bytes());
+                }
+            }
+            Self::AAAA(address) => output.extend_from_slice(&address.octets()),
+            Self::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                for value in [priority, weight, port] {
+                    output.extend_from_slice(&value.to_be_bytes());
+                }
+                output.append(&mut target.into_bytes());
+            }
+            Self::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                output.extend_from_slice(&order.to_be_bytes());
+                output.extend_from_slice(&preference.to_be_bytes());
+                for string in [flags, services, regexp] {
+                    output.append(&mut string.into_bytes());
+                }
+                output.append(&mut replacement.into_bytes());
+            }
+            Self::SSHFP {
+                algorithm,
+                fingerprint_type,
+                fingerprint,
+            } => {
+                output.push(algorithm.into());
+                output.push(fingerprint_type.into());
+                output.extend(fingerprint);
+            }
+            Self::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => {
+                output.extend_from_slice(&[usage.into(), selector.into(), matching_type.into()]);
+                output.extend(data);
+            }
+            Self::OPENPGPKEY(key) => output.extend(key),
+            Self::SVCB {
+                priority,
+                target,
+                params,
+            }
+            | Self::HTTPS {
+                priority,
+                target,
+                params,
+            } => {
+                output.extend_from_slice(&priority.to_be_bytes());
+                output.append(&mut target.into_bytes());
+                params.encode(output)?;
+            }
+            Self::URI {
+                priority,
+                weight,
+                target,
+            } => {
+                output.extend_from_slice(&priority.to_be_bytes());
+                output.extend_from_slice(&weight.to_be_bytes());
+                output.extend(target);
+            }
+            Self::CAA { flags, tag, value } => {
+                if !is_caa_tag(&tag) {
+                    return Err(EncodeError::IllegalValue(
+                        "failed to encode CAA rdata: tag must be 1 to 15 letters and digits"
+                            .to_string(),
+                    ));
+                }
+                output.push(flags);
+                // The tag is at most 15 bytes.
+                output.push(tag.len() as u8);
+                output.extend(tag.into_bytes());
+                output.extend(value);
+            }
+            Self::Unknown { data, .. } => output.extend(data),
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize rdata of the given `type` that's `rdlength` bytes long at the position of
+    /// `cursor`.
+    ///
+    /// The rdata must be exactly `rdlength` bytes long. Compressed domain names are expanded
+    /// using the message `cursor` reads from.
+    pub(crate) fn decode(
+        cursor: &mut Cursor<'_>,
+        r#type: &Type,
+        rdlength: u16,
+    ) -> Result<Self, DecodeError> {
+        // Offset of the rdata relative to the start of the message.
+        let start = cursor.position();
+        let mut value = cursor.split(rdlength.into())?;
+
+        // Running out of rdata means the rdata is shorter than its type requires.
+        let rdlength_mismatch = |error: DecodeError| match error.kind {
+            DecodeErrorKind::NotEnoughBytes => {
+                DecodeError::from(DecodeErrorKind::RDataLengthMismatch).at(start)
+            }
+            _ => error,
+        };
+
+        let rdata = Self::read(&mut value, r#type).map_err(rdlength_mismatch)?;
+        if value.remaining() != 0 {
+            return Err(DecodeError::from(DecodeErrorKind::RDataLengthMismatch).at(start));
+        }
+
+        Ok(rdata)
+    }
+
+    // Read rdata of the given `type` from `value`, which is limited to the rdata.
+    fn read(value: &mut Cursor<'_>, r#type: &Type) -> Result<Self, DecodeError> {
+        let rdata = match r#type {
+            Type::A => Self::A(value.read_array::<4>()?.into()),
+            Type::NS => Self::NS(take_name(value)?),
+            Type::MD => Self::MD(take_name(value)?),
+            Type::MF => Self::MF(take_name(value)?),
+            Type::CNAME => Self::CNAME(take_name(value)?),
+            Type::SOA => Self::SOA {
+                mname: take_name(value)?,
+                rname: take_name(value)?,
+                serial: value.read_u32()?,
+                refresh: value.read_u32()?,
+                retry: value.read_u32()?,
+                expire: value.read_u32()?,
+                minimum: value.read_u32()?,
+            },
+            Type::MB => Self::MB(take_name(value)?),
+            Type::MG => Self::MG(take_name(value)?),
+            Type::MR => Self::MR(take_name(value)?),
+            Type::Null => Self::Null(value.read_bytes(value.remaining())?.to_vec()),
+            Type::WKS => Self::WKS {
+                address: value.read_array::<4>()?.into(),
+                protocol: value.read_u8()?,
+                bitmap: value.read_bytes(value.remaining())?.to_vec(),
+            },
+            Type::PTR => Self::PTR(take_name(value)?),
+            Type::HINFO => Self::HINFO {
+                cpu: CharacterString::take(value)?,
+                os: CharacterString::take(value)?,
+            },
+            Type::MINFO => Self::MINFO {
+                rmailbx: take_name(value)?,
+                emailbx: take_name(value)?,
+            },
+            Type::MX => Self::MX {
+                preference: value.read_u16()?,
+                exchange: take_name(value)?,
+            },
+            Type::TXT => {
+                let mut strings = vec![];
+                while value.remaining() > 0 {
+                    strings.push(CharacterString::take(value)?);
+                }
+                Self::TXT(strings)
+            }
+            Type::AAAA => Self::AAAA(value.read_array::<16>()?.into()),
+            Type::SRV => Self::SRV {
+                priority: value.read_u16()?,
+                weight: value.read_u16()?,
+                port: value.read_u16()?,
+                target: take_name(value)?,
+            },
+            Type::NAPTR => Self::NAPTR {
+                order: value.read_u16()?,
+                preference: value.read_u16()?,
+                flags: CharacterString::take(value)?,
+                services: CharacterString::take(value)?,
+                regexp: CharacterString::take(value)?,
+                replacement: take_name(value)?,
+            },
+            Type::DNAME => Self::DNAME(take_name(value)?),
+            Type::SSHFP => Self::SSHFP {
+                algorithm: value.read_u8()?.into(),
+                fingerprint_type: value.read_u8()?.into(),
+                fingerprint: value.read_bytes(value.remaining())?.to_vec(),
+            },
+            Type::TLSA => Self::TLSA {
+                usage: value.read_u8()?.into(),
+                selector: value.read_u8()?.into(),
+                matching_type: value.read_u8()?.into(),
+                data: value.read_bytes(value.remaining())?.to_vec(),
+            },
+            Type::OPENPGPKEY => Self::OPENPGPKEY(value.read_bytes(value.remaining())?.to_vec()),
+            Type::SVCB => Self::SVCB {
+                priority: value.read_u16()?,
+                target: take_name(value)?,
+                params: SvcParams::decode(value)?,
+            },
+            Type::HTTPS => Self::HTTPS {
+                priority: value.read_u16()?,
+                target: take_name(value)?,
+                params: SvcParams::decode(value)?,
+            },
+            Type::URI => Self::URI {
+                priority: value.read_u16()?,
+                weight: value.read_u16()?,
+                // The target can't be empty, see section 4.5 of RFC 7553.
+                target: match value.remaining() {
+                    0 => return Err(DecodeErrorKind::NotEnoughBytes.into()),
+                    remaining => value.read_bytes(remaining)?.to_vec(),
+                },
+            },
+            Type::CAA => {
+                let flags = value.read_u8()?;
+                let length = value.read_u8()?;
+                let tag = std::str::from_utf8(value.read_bytes(length.into())?)
+                    .ok()
+                    .filter(|tag| is_caa_tag(tag))
+                    .ok_or_else(|| {
+                        DecodeErrorKind::IllegalValue(
+                            "failed to parse CAA rdata: tag must be 1 to 15 letters and digits"
+                                .to_string(),
+                        )
+                    })?;
+                Self::CAA {
+                    flags,
+                    tag: tag.to_string(),
+                    value: value.read_bytes(value.remaining())?.to_vec(),
+                }
+            }
+            // OPT records are decoded by `Edns`. Keep them opaque here.
+            Type::OPT | Type::Unknown(_) => Self::Unknown {
+                r#type: r#type.clone().into(),
+                data: value.read_bytes(value.remaining())?.to_vec(),
+            },
+        };
+
+        Ok(rdata)
+    }
+}
+
+impl Display for RData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::A(address) => write!(f, "{}", address),
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name)
+            | Self::DNAME(name) => write!(f, "{}", Fqdn(name)),
+            Self::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                Fqdn(mname),
+                Fqdn(rname),
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum
+            ),
+            Self::WKS {
+                address,
+                protocol,
+                bitmap,
+            } => {
+                write!(f, "{} {}", address, protocol)?;
+                // The most significant bit of the first byte represents port 0.
+                for (index, byte) in bitmap.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0b1000_0000 >> bit) != 0 {
+                            write!(f, " {}", index * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Self::HINFO { cpu, os } => write!(f, "{} {}", cpu, os),
+            Self::MINFO { rmailbx, emailbx } => {
+                write!(f, "{} {}", Fqdn(rmailbx), Fqdn(emailbx))
+            }
+            Self::MX {
+                preference,
+                exchange,
+            } => write!(f, "{} {}", preference, Fqdn(exchange)),
+            Self::TXT(strings) => {
+                for (index, string) in strings.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", string)?;
+                }
+                Ok(())
+            }
+            Self::AAAA(address) => write!(f, "{}", address),
+            Self::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{} {} {} {}", priority, weight, port, Fqdn(target)),
+            Self::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => write!(
+                f,
+                "{} {} {} {} {} {}",
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                Fqdn(replacement)
+            ),
+            Self::SSHFP {
+                algorithm,
+                fingerprint_type,
+                fingerprint,
+            } => write!(
+                f,
+                "{} {} {}",
+                u8::from(*algorithm),
+                u8::from(*fingerprint_type),
+                to_hex(fingerprint)
+            ),
+            Self::TLSA {
+                usage,
+                selector,
+                matching_type,
+                data,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                u8::from(*usage),
+                u8::from(*selector),
+                u8::from(*matching_type),
+                to_hex(data)
+            ),
+            Self::OPENPGPKEY(key) => write!(f, "{}", to_base64(key)),
+            Self::SVCB {
+                priority,
+                target,
+                params,
+            }
+            | Self::HTTPS {
+                priority,
+                target,
+                params,
+            } => {
+                write!(f, "{} {}", priority, Fqdn(target))?;
+                if !params.is_empty() {
+                    write!(f, " {}", params)?;
+                }
+                Ok(())
+            }
+            Self::URI {
+                priority,
+                weight,
+                target,
+            } => {
+                write!(f, "{} {} ", priority, weight)?;
+                write_quoted(f, target)
+            }
+            Self::CAA { flags, tag, value } => {
+                write!(f, "{} {} ", flags, tag)?;
+                write_quoted(f, value)
+            }
+            // NULL has no presentation format of its own.
+            Self::Null(data) | Self::Unknown { data, .. } => {
+                write!(f, "\\# {}", data.len())?;
+                if !data.is_empty() {
+                    write!(f, " {}", to_hex(data))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Prints a domain name as fully qualified name, with trailing dot.
+struct Fqdn<'a>(&'a DomainName);
+
+impl Display for Fqdn<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_root() {
+            return write!(f, ".");
+        }
+
+        write!(f, "{}.", self.0)
+    }
+}
+
+/// A character-string: a sequence of at most 255 bytes.
+///
+/// See section [`3.3. Standard RRs`] of RFC 1035.
+///
+/// [`3.3. Standard RRs`]: https://www.rfc-editor.org/rfc/rfc1035#section-3.3
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct CharacterString(Vec<u8>);
+
+impl CharacterString {
+    /// Create a `CharacterString`. Fails if `value` is longer than 255 bytes.
+    pub fn new(value: Vec<u8>) -> Result<Self, DecodeError> {
+        if value.len() > usize::from(u8::MAX) {
+            return Err(DecodeErrorKind::IllegalValue(format!(
+                "failed to parse value as character-string: {} bytes exceeds the maximum of 255",
+                value.len()
+            ))
+            .into());
+        }
+
+        Ok(Self(value))
+    }
+
+    /// The bytes of the character-string, excluding the length byte.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Serialize the instance into bytes, prefixed by its length.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.0.len() + 1);
+        // `new()` and `take()` ensure the string is at most 255 bytes.
+        output.push(self.0.len() as u8);
+        output.extend(self.0);
+
+        output
+    }
+
+    pub(crate) fn take(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let length = value.read_u8()?;
+        Ok(Self(value.read_bytes(length.into())?.to_vec()))
+    }
+}
+
+impl Display for CharacterString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_quoted(f, &self.0)
+    }
+}
+
+impl TryFrom<&str> for CharacterString {
+    type Error = DecodeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value.as_bytes().to_vec())
+    }
+}
+
+// Print `bytes` between quotes. Quotes and backslashes are escaped, as are non-printable bytes.
+// See section 5.1 of RFC 1035.
+pub(crate) fn write_quoted(f: &mut std::fmt::Formatter<'_>, bytes: &[u8]) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", *byte as char)?,
+            0x20..=0x7e => write!(f, "{}", *byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// The tokens of rdata in presentation format, separated by whitespace. A token between quotes
+// may contain whitespace, as may the quoted value of a `key="value"` token.
+struct Tokens<'a> {
+    tokens: std::vec::IntoIter<&'a str>,
+}
+
+impl<'a> Tokens<'a> {
+    // Split `value` into tokens. Fails if a quote isn't closed. Escapes are kept, but an escaped
+    // quote or whitespace doesn't end a token. The quotes around a token are removed, those
+    // around the value of a `key="value"` token are kept.
+    fn new(value: &'a str) -> Option<Self> {
+        let mut tokens = vec![];
+        let mut rest = value.trim_start();
+        while !rest.is_empty() {
+            let quoted = rest.starts_with('"');
+            let start = usize::from(quoted);
+
+            let mut end = None;
+            let mut in_quotes = quoted;
+            let mut escaped = false;
+            let mut previous = None;
+            for (index, character) in rest.char_indices().skip(start) {
+                match character {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' if quoted => {
+                        end = Some(index);
+                        break;
+                    }
+                    '"' if in_quotes => in_quotes = false,
+                    '"' if previous == Some('=') => in_quotes = true,
+                    _ if !in_quotes && character.is_whitespace() => {
+                        end = Some(index);
+                        break;
+                    }
+                    _ => (),
+                }
+                previous = Some(character);
+            }
+
+            let end = match end {
+                Some(end) => end,
+                None if !in_quotes => rest.len(),
+                None => return None,
+            };
+            tokens.push(&rest[start..end]);
+            // Skip the closing quote.
+            rest = rest[end + start..].trim_start();
+        }
+
+        Some(Self {
+            tokens: tokens.into_iter(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.len() == 0
+    }
+
+    // The next token as is.
+    fn token(&mut self) -> Option<&'a str> {
+        self.tokens.next()
+    }
+
+    // Parse the next token as number, or as anything else that has no escapes, like an IP
+    // address.
+    fn number<T: std::str::FromStr>(&mut self) -> Option<T> {
+        self.tokens.next()?.parse().ok()
+    }
+
+    fn name(&mut self) -> Option<DomainName> {
+        self.tokens.next()?.parse().ok()
+    }
+
+    fn character_string(&mut self) -> Option<CharacterString> {
+        CharacterString::new(self.bytes()?).ok()
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        unescape(self.tokens.next()?)
+    }
+
+    // Parse the remaining tokens as hexadecimal. Long values may be split over several tokens.
+    fn hex(&mut self) -> Option<Vec<u8>> {
+        from_hex(&self.rest()?)
+    }
+
+    // Parse the remaining tokens as base64. Long values may be split over several tokens.
+    fn base64(&mut self) -> Option<Vec<u8>> {
+        from_base64(&self.rest()?)
+    }
+
+    // The remaining tokens, joined. `None` if there are none.
+    fn rest(&mut self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        Some(self.tokens.by_ref().collect())
+    }
+
+    // Parse the remaining `key=value` tokens as SvcParams. Values may be quoted.
+    fn svc_params(&mut self) -> Option<SvcParams> {
+        let mut params = SvcParams::new();
+        while let Some(token) = self.token() {
+            let (key, value) = match token.split_once('=') {
+                Some((key, value)) => {
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .unwrap_or(value);
+                    (key, Some(unescape(value)?))
+                }
+                None => (token, None),
+            };
+
+            // Each key may appear once.
+            if params
+                .insert(SvcParam::from_presentation(key, value)?)
+                .is_some()
+            {
+                return None;
+            }
+        }
+
+        params.check().ok()?;
+        Some(params)
+    }
+}
+
+// Whether `tag` is a valid tag of a CAA record, see section 4.1.1 of RFC 8659.
+fn is_caa_tag(tag: &str) -> bool {
+    (1..=15).contains(&tag.len()) && tag.bytes().all(|byte| byte.is_ascii_alphanumeric())
+}
+
+// Replace the escapes `\X` and `\DDD` in `value` by the bytes they represent.
+fn unescape(value: &str) -> Option<Vec<u8>> {
+    let mut output = vec![];
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            output.push(byte);
+            continue;
+        }
+
+        let escaped = bytes.next()?;
+        if !escaped.is_ascii_digit() {
+            output.push(escaped);
+            continue;
+        }
+
+        let mut decimal = u16::from(escaped - b'0');
+        for _ in 0..2 {
+            let digit = bytes.next().filter(u8::is_ascii_digit)?;
+            decimal = decimal * 10 + u16::from(digit - b'0');
+        }
+        output.push(u8::try_from(decimal).ok()?);
+    }
+
+    Some(output)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Decode hexadecimal digits, in upper or lower case. Unlike `u8::from_str_radix()`, a sign
+// isn't accepted.
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    let digits = value
+        .chars()
+        .map(|digit| digit.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Encode `bytes` in base64 with padding, see section 4 of RFC 4648.
+pub(crate) fn to_base64(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (u32::from(*byte) << (16 - 8 * index))
+        });
+
+        // 3 bytes give 4 characters; a chunk of fewer bytes is padded with "=".
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                output.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+// Decode base64 with padding, see section 4 of RFC 4648.
+pub(crate) fn from_base64(value: &str) -> Option<Vec<u8>> {
+    let value = value.as_bytes();
+    if !value.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(value.len() / 4 * 3);
+    let mut chunks = value.chunks(4).peekable();
+    while let Some(chunk) = chunks.next() {
+        // Only the last chunk may be padded.
+        let padding = chunk.iter().rev().take_while(|byte| **byte == b'=').count();
+        if padding > 2 || (padding > 0 && chunks.peek().is_some()) {
+            return None;
+        }
+
+        let mut group = 0u32;
+        for byte in &chunk[..4 - padding] {
+            let sextet = BASE64_ALPHABET.iter().position(|other| other == byte)?;
+            group = (group << 6) | sextet as u32;
+        }
+        group <<= 6 * padding;
+        output.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+
+    Some(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(rdata: RData) {
+        let r#type = rdata.r#type();
+        let bytes = rdata.clone().into_bytes().unwrap();
+        let decoded = RData::decode(&mut Cursor::new(&bytes), &r#type, bytes.len() as u16).unwrap();
+
+        assert_eq!(rdata, decoded);
+    }
+
+    #[test]
+    fn test_serialization_and_deserialization() {
+        let name: DomainName = "orangetux.nl".parse().unwrap();
+
+        round_trip(RData::A(Ipv4Addr::new(185, 24, 223, 10)));
+        round_trip(RData::AAAA(Ipv6Addr::LOCALHOST));
+        round_trip(RData::NS(name.clone()));
+        round_trip(RData::SOA {
+            mname: name.clone(),
+            rname: "hostmaster.orangetux.nl".parse().unwrap(),
+            serial: 2023010101,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum: 300,
+        });
+        round_trip(RData::MX {
+            preference: 10,
+            exchange: name.clone(),
+        });
+        round_trip(RData::TXT(vec![
+            "v=spf1 -all".try_into().unwrap(),
+            CharacterString::default(),
+        ]));
+        round_trip(RData::HINFO {
+            cpu: "x86_64".try_into().unwrap(),
+            os: "Linux".try_into().unwrap(),
+        });
+        round_trip(RData::MINFO {
+            rmailbx: name.clone(),
+            emailbx: name,
+        });
+        round_trip(RData::WKS {
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            protocol: 6,
+            bitmap: vec![0, 0, 0, 1],
+        });
+        round_trip(RData::SRV {
+            priority: 10,
+            weight: 60,
+            port: 5060,
+            target: "bigbox.example.com".parse().unwrap(),
+        });
+        round_trip(RData::NAPTR {
+            order: 100,
+            preference: 10,
+            flags: "S".try_into().unwrap(),
+            services: "SIP+D2U".try_into().unwrap(),
+            regexp: CharacterString::default(),
+            replacement: "_sip._udp.example.com".parse().unwrap(),
+        });
+        round_trip(RData::DNAME("example.net".parse().unwrap()));
+        round_trip(RData::SSHFP {
+            algorithm: SshfpAlgorithm::Dsa,
+            fingerprint_type: FingerprintType::Sha1,
+            fingerprint: vec![0x12; 20],
+        });
+        round_trip(RData::TLSA {
+            usage: CertificateUsage::DaneEe,
+            selector: Selector::SubjectPublicKeyInfo,
+            matching_type: MatchingType::Unknown(255),
+            data: vec![0xd2; 32],
+        });
+        round_trip(RData::OPENPGPKEY(vec![0x98, 0x33, 0x04]));
+        round_trip(RData::URI {
+            priority: 10,
+            weight: 1,
+            target: b"ftp://ftp1.example.com/public".to_vec(),
+        });
+        round_trip(RData::HTTPS {
+            priority: 1,
+            target: DomainName::root(),
+            params: [SvcParam::Port(8443), SvcParam::NoDefaultAlpn]
+                .into_iter()
+                .collect(),
+        });
+        round_trip(RData::SVCB {
+            priority: 0,
+            target: "svc.example.com".parse().unwrap(),
+            params: SvcParams::new(),
+        });
+        round_trip(RData::CAA {
+            flags: 0,
+            tag: "issuewild".to_string(),
+            value: b"ca.example.net".to_vec(),
+        });
+        round_trip(RData::Null(vec![1, 2, 3]));
+        round_trip(RData::Unknown {
+            r#type: 65280,
+            data: vec![1, 2, 3],
+        });
+    }
+
+    #[test]
+    fn test_presentation_format() {
+        let name: DomainName = "orangetux.nl".parse().unwrap();
+
+        assert_eq!(
+            RData::SOA {
+                mname: name.clone(),
+                rname: "hostmaster.orangetux.nl".parse().unwrap(),
+                serial: 1,
+                refresh: 7200,
+                retry: 3600,
+                expire: 1209600,
+                minimum: 300,
+            }
+            .to_string(),
+            "orangetux.nl. hostmaster.orangetux.nl. 1 7200 3600 1209600 300"
+        );
+        assert_eq!(
+            RData::MX {
+                preference: 10,
+                exchange: name
+            }
+            .to_string(),
+            "10 orangetux.nl."
+        );
+        assert_eq!(
+            RData::TXT(vec![
+                "v=spf1 -all".try_into().unwrap(),
+                CharacterString::new(b"\"\\\x07".to_vec()).unwrap()
+            ])
+            .to_string(),
+            r#""v=spf1 -all" "\"\\\007""#
+        );
+        assert_eq!(
+            RData::WKS {
+                address: Ipv4Addr::new(10, 0, 0, 1),
+                protocol: 6,
+                bitmap: vec![0, 0, 0b0100_0000, 0, 0, 0, 0, 0, 0, 0, 0b1000_0000]
+            }
+            .to_string(),
+            "10.0.0.1 6 17 80"
+        );
+        assert_eq!(
+            RData::NAPTR {
+                order: 100,
+                preference: 10,
+                flags: "S".try_into().unwrap(),
+                services: "SIP+D2U".try_into().unwrap(),
+                regexp: CharacterString::default(),
+                replacement: "_sip._udp.example.com".parse().unwrap(),
+            }
+            .to_string(),
+            r#"100 10 "S" "SIP+D2U" "" _sip._udp.example.com."#
+        );
+        assert_eq!(
+            RData::URI {
+                priority: 10,
+                weight: 1,
+                target: b"ftp://ftp1.example.com/public".to_vec(),
+            }
+            .to_string(),
+            r#"10 1 "ftp://ftp1.example.com/public""#
+        );
+        assert_eq!(RData::Null(vec![]).to_string(), r"\# 0");
+        assert_eq!(
+            RData::Unknown {
+                r#type: 65280,
+                data: vec![1, 2, 0xff]
+            }
+            .to_string(),
+            r"\# 3 0102ff"
+        );
+    }
+
+    #[test]
+    fn test_generic_format() {
+        let r#type = Type::Unknown(65280);
+        assert_eq!(
+            RData::from_generic(&r#type, r"\# 3 01 02FF").unwrap(),
+            RData::Unknown {
+                r#type: 65280,
+                data: vec![1, 2, 0xff]
+            }
+        );
+        assert_eq!(
+            RData::from_generic(&r#type, r"\# 0").unwrap(),
+            RData::Unknown {
+                r#type: 65280,
+                data: vec![]
+            }
+        );
+        assert_eq!(
+            RData::from_generic(&Type::NS, r"\# 3 016100").unwrap(),
+            RData::NS("a".parse().unwrap())
+        );
+
+        // Length doesn't match the data.
+        assert!(RData::from_generic(&r#type, r"\# 2 010203").is_err());
+        // Not hexadecimal.
+        assert!(RData::from_generic(&r#type, r"\# 1 0g").is_err());
+        assert!(RData::from_generic(&r#type, r"\# 2 +f+f").is_err());
+        assert!(RData::from_generic(&r#type, r"\# 1 -1").is_err());
+        assert!(RData::from_generic(&r#type, "01").is_err());
+        // Valid generic data, but not a valid A record.
+        assert!(RData::from_generic(&Type::A, r"\# 1 01").is_err());
+    }
+
+    #[test]
+    fn test_parse_presentation_format() {
+        // Parsing and printing again gives the same value.
+        let values = [
+            (Type::A, "10.0.0.1"),
+            (Type::AAAA, "2001:db8::1"),
+            (Type::NS, "orangetux.nl."),
+            (
+                Type::SOA,
+                "orangetux.nl. hostmaster.orangetux.nl. 1 7200 3600 1209600 300",
+            ),
+            (Type::WKS, "10.0.0.1 6 17 80"),
+            (Type::HINFO, r#""x86_64" "Linux""#),
+            (Type::MINFO, "a.orangetux.nl. b.orangetux.nl."),
+            (Type::MX, "10 orangetux.nl."),
+            (Type::TXT, r#""v=spf1 -all" "\"\\\007""#),
+            (Type::SRV, "0 0 0 ."),
+            (
+                Type::NAPTR,
+                r#"100 10 "S" "SIP+D2U" "" _sip._udp.example.com."#,
+            ),
+            (
+                Type::NAPTR,
+                r#"100 50 "U" "E2U+sip" "!^.*$!sip:info@example.com!" ."#,
+            ),
+            (Type::DNAME, "example.net."),
+            (Type::SSHFP, "2 1 123456789abcdef67890123456789abcdef67890"),
+            (
+                Type::TLSA,
+                "0 0 1 d2abde240d7cd3ee6b4b28c54df034b97983a1d16e8a410e4561cb106618e971",
+            ),
+            (Type::OPENPGPKEY, "mDMEXEcE6RYJKwYBBAHaRw8BAQdA"),
+            (Type::URI, r#"10 1 "ftp://ftp1.example.com/public""#),
+            (Type::CAA, r#"0 issue "ca.example.net; account=230123""#),
+            (Type::CAA, r#"128 tbs "Unknown""#),
+            (Type::Null, r"\# 3 010203"),
+        ];
+        for (r#type, value) in values {
+            let rdata = RData::from_presentation(&r#type, value).unwrap();
+            assert_eq!(rdata.r#type(), r#type);
+            assert_eq!(rdata.to_string(), value);
+        }
+
+        // Quotes are optional, whitespace between tokens doesn't matter and escapes are
+        // replaced.
+        assert_eq!(
+            RData::from_presentation(&Type::TXT, r#"  a\ b \065\\   "c d" "#).unwrap(),
+            RData::TXT(vec![
+                "a b".try_into().unwrap(),
+                "A\\".try_into().unwrap(),
+                "c d".try_into().unwrap()
+            ])
+        );
+        assert_eq!(
+            RData::from_presentation(&Type::URI, "1 2 https://orangetux.nl").unwrap(),
+            RData::URI {
+                priority: 1,
+                weight: 2,
+                target: b"https://orangetux.nl".to_vec()
+            }
+        );
+
+        // Hexadecimal and base64 values may be split over several tokens.
+        assert_eq!(
+            RData::from_presentation(&Type::TLSA, "3 1 2 0102 0304")
+                .unwrap()
+                .to_string(),
+            "3 1 2 01020304"
+        );
+        assert_eq!(
+            RData::from_presentation(&Type::OPENPGPKEY, "AQID BA==").unwrap(),
+            RData::OPENPGPKEY(vec![1, 2, 3, 4])
+        );
+
+        // Missing, extra or invalid tokens.
+        assert!(RData::from_presentation(&Type::MX, "10").is_err());
+        assert!(RData::from_presentation(&Type::MX, "10 a. b.").is_err());
+        assert!(RData::from_presentation(&Type::SRV, "10 60 65536 a.").is_err());
+        assert!(RData::from_presentation(&Type::A, "10.0.0.256").is_err());
+        assert!(RData::from_presentation(&Type::TXT, r#""unterminated"#).is_err());
+        assert!(RData::from_presentation(&Type::TXT, r"\256").is_err());
+        assert!(RData::from_presentation(&Type::TXT, &"a".repeat(256)).is_err());
+        assert!(RData::from_presentation(&Type::URI, r#"10 1 """#).is_err());
+        assert!(RData::from_presentation(&Type::CAA, r#"0 is-sue "ca.example.net""#).is_err());
+        assert!(RData::from_presentation(&Type::CAA, r#"0 "" "ca.example.net""#).is_err());
+        assert!(RData::from_presentation(&Type::SSHFP, "4 2").is_err());
+        assert!(RData::from_presentation(&Type::SSHFP, "4 2 123").is_err());
+        assert!(RData::from_presentation(&Type::TLSA, "3 1 256 00").is_err());
+        assert!(RData::from_presentation(&Type::OPENPGPKEY, "AQID!A==").is_err());
+        assert!(RData::from_presentation(&Type::Null, "").is_err());
+        assert!(RData::from_presentation(&Type::Unknown(65280), "01").is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        // The test vectors of section 10 of RFC 4648.
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(to_base64(bytes.as_bytes()), encoded);
+            assert_eq!(from_base64(encoded).unwrap(), bytes.as_bytes());
+        }
+
+        assert_eq!(from_base64("Zg"), None);
+        assert_eq!(from_base64("Zg=a"), None);
+        assert_eq!(from_base64("Zg==Zg=="), None);
+        assert_eq!(from_base64("Z==="), None);
+        assert_eq!(from_base64("Zm9*"), None);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(from_hex("00ff7Fa0").unwrap(), [0, 0xff, 0x7f, 0xa0]);
+        assert_eq!(from_hex("").unwrap(), []);
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("+a"), None);
+        assert_eq!(from_hex("0x"), None);
+        assert_eq!(from_hex("éé"), None);
+
+        assert!(RData::from_presentation(&Type::SSHFP, "4 2 +a+b").is_err());
+    }
+
+    #[test]
+    fn test_deserialization_checks_rdlength() {
+        // An A record must be exactly 4 bytes long.
+        let bytes = [185, 24, 223, 10, 0];
+        assert!(RData::decode(&mut Cursor::new(&bytes), &Type::A, 5).is_err());
+
+        // The character-string claims to be longer than the rdata.
+        let bytes = [5, 104, 105];
+        assert!(RData::decode(&mut Cursor::new(&bytes), &Type::TXT, 3).is_err());
+
+        // The target of a URI record can't be empty.
+        let bytes = [0, 10, 0, 1];
+        assert!(RData::decode(&mut Cursor::new(&bytes), &Type::URI, 4).is_err());
+
+        // The tag of a CAA record can't be empty.
+        let bytes = [0, 0, b'c', b'a'];
+        assert!(RData::decode(&mut Cursor::new(&bytes), &Type::CAA, 4).is_err());
+
+        assert!(CharacterString::new(vec![0; 256]).is_err());
+    }
+
+    #[test]
+    fn test_serialization_checks_caa_tags() {
+        let caa = |tag: &str| RData::CAA {
+            flags: 0,
+            tag: tag.to_string(),
+            value: b"ca.example.net".to_vec(),
+        };
+
+        assert!(caa("issue").into_bytes().is_ok());
+        for tag in ["", "is-sue", "issue issuewild", &"a".repeat(256)] {
+            assert!(matches!(
+                caa(tag).into_bytes(),
+                Err(EncodeError::IllegalValue(_))
+            ));
+        }
+    }
+}
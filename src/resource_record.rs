@@ -1,15 +1,20 @@
-use crate::domain_name::{into_string, take_name};
-use crate::DecodeError;
-use std::iter::Peekable;
-use std::slice::Iter;
+//! Serialize and deserialize `ResourceRecord`s.
+use crate::cursor::Cursor;
+use crate::domain_name::{Compressor, DomainName, NameRef};
+use crate::rdata::RData;
+use crate::{write_length, DecodeError, DecodeErrorKind, EncodeError};
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
 
+/// A resource record as documented in section [`4.1.3. Resource record format`] of RFC 1035.
+///
+/// The type of the record and the length of the rdata are derived from `rdata`.
+///
+/// [`4.1.3. Resource record format`]: https://www.rfc-editor.org/rfc/rfc1035#section-4.1.3
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct ResourceRecord {
     /// The domain name to which this record relates to.
-    pub name: Vec<u8>,
-
-    /// The type of ResourceRecord.
-    pub r#type: Type,
+    pub name: DomainName,
 
     /// The class of ResourceRecord.
     pub class: Class,
@@ -17,103 +22,219 @@ pub struct ResourceRecord {
     /// The time in seconds for how much the information in this ResourceRecord is valid for.
     pub ttl: u32,
 
-    /// The lenght of rdata in number of bytes.
-    pub rdlength: u16,
-
     /// The actual information describing the resource. It's format depends on type and class.
-    pub rdata: Vec<u8>,
+    pub rdata: RData,
 }
 
 impl ResourceRecord {
-    pub fn into_bytes(self) -> Vec<u8> {
+    /// The type of ResourceRecord.
+    pub fn r#type(&self) -> Type {
+        self.rdata.r#type()
+    }
+
+    /// Serialize the instance into bytes.
+    ///
+    /// Fails if the rdata is longer than 65535 bytes, or holds a value that can't be serialized.
+    pub fn into_bytes(self) -> Result<Vec<u8>, EncodeError> {
         let mut output = vec![];
-        output.append(&mut self.name.clone());
-        output.append(&mut self.r#type.into_bytes());
+        output.append(&mut self.name.into_bytes());
+        output.append(&mut self.rdata.r#type().into_bytes());
         output.append(&mut self.class.into_bytes());
         output.append(&mut Vec::from(self.ttl.to_be_bytes()));
-        output.append(&mut Vec::from(self.rdlength.to_be_bytes()));
-        output.append(&mut self.rdata.clone());
-
-        output
-    }
-}
-
-impl TryFrom<&mut Peekable<Iter<'_, u8>>> for ResourceRecord {
-    type Error = DecodeError;
-
-    fn try_from(value: &mut Peekable<Iter<'_, u8>>) -> Result<Self, Self::Error> {
-        let name = take_name(value)?.0;
-
-        let r#type: Type = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ])
-        .try_into()
-        .unwrap();
-        //let r#type = Type::NS;
-        let class: Class = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ])
-        .try_into()
-        .unwrap();
-
-        let ttl = u32::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
-        let rdlength = u16::from_be_bytes([
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-        ]);
-
-        let mut rdata: Vec<u8> = Vec::with_capacity(rdlength.into());
-        for _ in 0..rdlength {
-            rdata.push(*value.next().ok_or(DecodeError::NotEnoughBytes)?);
-        }
 
-        Ok(ResourceRecord {
+        let mut rdata = self.rdata.into_bytes()?;
+        let rdlength = u16::try_from(rdata.len()).map_err(|_| EncodeError::TooLong)?;
+        output.append(&mut Vec::from(rdlength.to_be_bytes()));
+        output.append(&mut rdata);
+
+        Ok(output)
+    }
+
+    /// Serialize the instance into `output`, compressing domain names using `compressor`.
+    ///
+    /// Besides the name of the record, the names in the rdata of NS, CNAME, SOA, MX and PTR
+    /// records are compressed. The rdlength is derived from the compressed rdata.
+    pub(crate) fn encode(
+        self,
+        output: &mut Vec<u8>,
+        compressor: &mut Compressor,
+    ) -> Result<(), EncodeError> {
+        compressor.write_name(&self.name, output);
+        output.append(&mut self.rdata.r#type().into_bytes());
+        output.append(&mut self.class.into_bytes());
+        output.extend_from_slice(&self.ttl.to_be_bytes());
+
+        let rdlength_position = output.len();
+        output.extend_from_slice(&[0, 0]);
+        self.rdata.encode(output, compressor)?;
+
+        write_length(output, rdlength_position)
+    }
+}
+
+/// A resource record borrowed from a message. See [`ResourceRecord`].
+///
+/// The rdata is kept as a slice of the message. [`RecordRef::rdata()`] decodes it on demand.
+#[derive(Clone, Copy)]
+pub struct RecordRef<'a> {
+    name: NameRef<'a>,
+    r#type: u16,
+    class: u16,
+    ttl: u32,
+    // Limited to the rdata, but pointers in the rdata can still be followed.
+    rdata: Cursor<'a>,
+}
+
+impl<'a> RecordRef<'a> {
+    /// Parse the record at the position of `cursor` and move the cursor past it.
+    ///
+    /// The rdata isn't decoded, but it must fit in the message.
+    pub(crate) fn parse(cursor: &mut Cursor<'a>) -> Result<Self, DecodeError> {
+        let name = NameRef::parse(cursor)?;
+        let r#type = cursor.read_u16()?;
+        let class = cursor.read_u16()?;
+        let ttl = cursor.read_u32()?;
+        let rdlength = cursor.read_u16()?;
+        let rdata = cursor.split(rdlength.into())?;
+
+        Ok(RecordRef {
             name,
             r#type,
             class,
             ttl,
-            rdlength,
             rdata,
         })
     }
+
+    /// The domain name to which this record relates to.
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    /// The type of the record.
+    pub fn r#type(&self) -> Type {
+        self.r#type.into()
+    }
+
+    /// The class of the record.
+    pub fn class(&self) -> Class {
+        self.class.into()
+    }
+
+    /// The time in seconds for how much the information in this record is valid for.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// A cursor limited to the rdata.
+    pub(crate) fn rdata_cursor(&self) -> Cursor<'a> {
+        self.rdata
+    }
+
+    /// The rdata as it appears in the message. Domain names in the rdata might be compressed.
+    pub fn rdata_bytes(&self) -> &'a [u8] {
+        self.rdata.as_bytes()
+    }
+
+    /// Decode the rdata.
+    pub fn rdata(&self) -> Result<RData, DecodeError> {
+        let mut cursor = self.rdata;
+        let rdlength = cursor.remaining() as u16;
+
+        RData::decode(&mut cursor, &self.r#type(), rdlength)
+    }
+
+    /// Copy the record into a `ResourceRecord`, decoding the rdata.
+    pub fn to_record(&self) -> Result<ResourceRecord, DecodeError> {
+        Ok(ResourceRecord {
+            name: self.name.to_domain_name(),
+            class: self.class(),
+            ttl: self.ttl,
+            rdata: self.rdata()?,
+        })
+    }
+}
+
+impl Debug for RecordRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordRef")
+            .field("name", &self.name)
+            .field("type", &self.r#type())
+            .field("class", &self.class())
+            .field("ttl", &self.ttl)
+            .field("rdata", &self.rdata_bytes())
+            .finish()
+    }
 }
 
 /// Types used in ResourceRecords.
 /// See section 3.2.2 of RFC 1035.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Type {
+    /// A host address.
     A,
+    /// An authoritative name server.
     NS,
+    /// A mail destination (obsolete - use MX).
     MD,
+    /// A mail forwarder (obsolete - use MX).
     MF,
+    /// The canonical name for an alias.
     CNAME,
+    /// Marks the start of a zone of authority.
     SOA,
+    /// A mailbox domain name (experimental).
     MB,
+    /// A mail group member (experimental).
     MG,
+    /// A mail rename domain name (experimental).
     MR,
+    /// A null RR (experimental).
     Null,
+    /// A well known service description.
     WKS,
+    /// A domain name pointer.
     PTR,
+    /// Host information.
     HINFO,
+    /// Mailbox or mail list information.
     MINFO,
+    /// Mail exchange.
     MX,
+    /// Text strings.
     TXT,
     /// IPv6 host address as defined in RFC 3596 DNS Extensions to Support IP Version 6.
     AAAA,
+    /// The location of a service. See RFC 2782.
+    SRV,
+    /// A rule rewriting a string into a domain name or URI. See RFC 3403.
+    NAPTR,
+    /// An alias for a subtree of the domain name space. See RFC 6672.
+    DNAME,
+    /// The EDNS pseudo-record of RFC 6891. Messages hold it as `Message::edns`, it's not
+    /// meant to be used as a regular record.
+    OPT,
+    /// The fingerprint of an SSH host key. See RFC 4255.
+    SSHFP,
+    /// The certificate or public key of a TLS server. See RFC 6698.
+    TLSA,
+    /// The OpenPGP public key of an email address. See RFC 7929.
+    OPENPGPKEY,
+    /// The endpoint of a service and its parameters. See RFC 9460.
+    SVCB,
+    /// The endpoint of an HTTPS service and its parameters. See RFC 9460.
+    HTTPS,
+    /// A URI. See RFC 7553.
+    URI,
+    /// The certification authorities that may issue certificates for the owner. See RFC 8659.
+    CAA,
+    /// A type this library doesn't know about. See RFC 3597.
+    Unknown(u16),
 }
 
-impl TryFrom<u16> for Type {
-    type Error = String;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Ok(match value {
+impl From<u16> for Type {
+    fn from(value: u16) -> Self {
+        match value {
             1 => Self::A,
             2 => Self::NS,
             3 => Self::MD,
@@ -131,38 +252,150 @@ impl TryFrom<u16> for Type {
             15 => Self::MX,
             16 => Self::TXT,
             28 => Self::AAAA,
-            _ => {
-                return Err(format!(
-                    "failed to parse value as Type: {} is not a valid value",
-                    value
-                ))
-            }
-        })
+            33 => Self::SRV,
+            35 => Self::NAPTR,
+            39 => Self::DNAME,
+            41 => Self::OPT,
+            44 => Self::SSHFP,
+            52 => Self::TLSA,
+            61 => Self::OPENPGPKEY,
+            64 => Self::SVCB,
+            65 => Self::HTTPS,
+            256 => Self::URI,
+            257 => Self::CAA,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Type> for u16 {
+    fn from(val: Type) -> Self {
+        match val {
+            Type::A => 1,
+            Type::NS => 2,
+            Type::MD => 3,
+            Type::MF => 4,
+            Type::CNAME => 5,
+            Type::SOA => 6,
+            Type::MB => 7,
+            Type::MG => 8,
+            Type::MR => 9,
+            Type::Null => 10,
+            Type::WKS => 11,
+            Type::PTR => 12,
+            Type::HINFO => 13,
+            Type::MINFO => 14,
+            Type::MX => 15,
+            Type::TXT => 16,
+            Type::AAAA => 28,
+            Type::SRV => 33,
+            Type::NAPTR => 35,
+            Type::DNAME => 39,
+            Type::OPT => 41,
+            Type::SSHFP => 44,
+            Type::TLSA => 52,
+            Type::OPENPGPKEY => 61,
+            Type::SVCB => 64,
+            Type::HTTPS => 65,
+            Type::URI => 256,
+            Type::CAA => 257,
+            Type::Unknown(value) => value,
+        }
     }
 }
 
 impl Type {
     fn into_bytes(self) -> Vec<u8> {
-        let low_byte: u8 = match self {
-            Self::A => 1,
-            Self::NS => 2,
-            Self::MD => 3,
-            Self::MF => 4,
-            Self::CNAME => 5,
-            Self::SOA => 6,
-            Self::MB => 7,
-            Self::MG => 8,
-            Self::MR => 9,
-            Self::Null => 10,
-            Self::WKS => 11,
-            Self::PTR => 12,
-            Self::HINFO => 13,
-            Self::MINFO => 14,
-            Self::MX => 15,
-            Self::TXT => 16,
-            Self::AAAA => 28,
+        Into::<u16>::into(self).to_be_bytes().to_vec()
+    }
+}
+
+impl Display for Type {
+    // Types without a mnemonic are printed as "TYPE" followed by their value.
+    // See section 5 of RFC 3597.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = match self {
+            Self::A => "A",
+            Self::NS => "NS",
+            Self::MD => "MD",
+            Self::MF => "MF",
+            Self::CNAME => "CNAME",
+            Self::SOA => "SOA",
+            Self::MB => "MB",
+            Self::MG => "MG",
+            Self::MR => "MR",
+            Self::Null => "NULL",
+            Self::WKS => "WKS",
+            Self::PTR => "PTR",
+            Self::HINFO => "HINFO",
+            Self::MINFO => "MINFO",
+            Self::MX => "MX",
+            Self::TXT => "TXT",
+            Self::AAAA => "AAAA",
+            Self::SRV => "SRV",
+            Self::NAPTR => "NAPTR",
+            Self::DNAME => "DNAME",
+            Self::OPT => "OPT",
+            Self::SSHFP => "SSHFP",
+            Self::TLSA => "TLSA",
+            Self::OPENPGPKEY => "OPENPGPKEY",
+            Self::SVCB => "SVCB",
+            Self::HTTPS => "HTTPS",
+            Self::URI => "URI",
+            Self::CAA => "CAA",
+            Self::Unknown(value) => return write!(f, "TYPE{}", value),
+        };
+
+        write!(f, "{}", mnemonic)
+    }
+}
+
+impl FromStr for Type {
+    type Err = DecodeError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let r#type = match value.to_ascii_uppercase().as_str() {
+            "A" => Self::A,
+            "NS" => Self::NS,
+            "MD" => Self::MD,
+            "MF" => Self::MF,
+            "CNAME" => Self::CNAME,
+            "SOA" => Self::SOA,
+            "MB" => Self::MB,
+            "MG" => Self::MG,
+            "MR" => Self::MR,
+            "NULL" => Self::Null,
+            "WKS" => Self::WKS,
+            "PTR" => Self::PTR,
+            "HINFO" => Self::HINFO,
+            "MINFO" => Self::MINFO,
+            "MX" => Self::MX,
+            "TXT" => Self::TXT,
+            "AAAA" => Self::AAAA,
+            "SRV" => Self::SRV,
+            "NAPTR" => Self::NAPTR,
+            "DNAME" => Self::DNAME,
+            "OPT" => Self::OPT,
+            "SSHFP" => Self::SSHFP,
+            "TLSA" => Self::TLSA,
+            "OPENPGPKEY" => Self::OPENPGPKEY,
+            "SVCB" => Self::SVCB,
+            "HTTPS" => Self::HTTPS,
+            "URI" => Self::URI,
+            "CAA" => Self::CAA,
+            other => other
+                .strip_prefix("TYPE")
+                .and_then(|number| number.parse::<u16>().ok())
+                .map(Self::from)
+                .ok_or_else(|| {
+                    DecodeErrorKind::IllegalValue(format!(
+                        "failed to parse value as Type: {} is not a valid value",
+                        value
+                    ))
+                })?,
         };
-        vec![0, low_byte]
+
+        Ok(r#type)
     }
 }
 
@@ -170,40 +403,143 @@ impl Type {
 /// See section 3.2.4 of RFC 1035.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Class {
-    // The Internet.
+    /// The Internet.
     IN,
+    /// The CSNET class (obsolete).
     CS,
+    /// The CHAOS class.
     CH,
+    /// Hesiod.
     HS,
+    /// A class this library doesn't know about. See RFC 3597.
+    Unknown(u16),
 }
 
 impl Class {
     fn into_bytes(self) -> Vec<u8> {
-        let low_byte: u8 = match self {
-            Self::IN => 1,
-            Self::CS => 2,
-            Self::CH => 3,
-            Self::HS => 4,
-        };
-        vec![0, low_byte]
+        Into::<u16>::into(self).to_be_bytes().to_vec()
     }
 }
 
-impl TryFrom<u16> for Class {
-    type Error = DecodeError;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Ok(match value {
+impl From<u16> for Class {
+    fn from(value: u16) -> Self {
+        match value {
             1 => Self::IN,
             2 => Self::CS,
             3 => Self::CH,
             4 => Self::HS,
-            _ => {
-                return Err(DecodeError::IllegalValue(format!(
-                    "failed to parse value as Class: {} is not a valid value",
-                    value
-                )))
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Class> for u16 {
+    fn from(val: Class) -> Self {
+        match val {
+            Class::IN => 1,
+            Class::CS => 2,
+            Class::CH => 3,
+            Class::HS => 4,
+            Class::Unknown(value) => value,
+        }
+    }
+}
+
+impl Display for Class {
+    // Classes without a mnemonic are printed as "CLASS" followed by their value.
+    // See section 5 of RFC 3597.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IN => write!(f, "IN"),
+            Self::CS => write!(f, "CS"),
+            Self::CH => write!(f, "CH"),
+            Self::HS => write!(f, "HS"),
+            Self::Unknown(value) => write!(f, "CLASS{}", value),
+        }
+    }
+}
+
+impl FromStr for Class {
+    type Err = DecodeError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let class = match value.to_ascii_uppercase().as_str() {
+            "IN" => Self::IN,
+            "CS" => Self::CS,
+            "CH" => Self::CH,
+            "HS" => Self::HS,
+            other => other
+                .strip_prefix("CLASS")
+                .and_then(|number| number.parse::<u16>().ok())
+                .map(Self::from)
+                .ok_or_else(|| {
+                    DecodeErrorKind::IllegalValue(format!(
+                        "failed to parse value as Class: {} is not a valid value",
+                        value
+                    ))
+                })?,
+        };
+
+        Ok(class)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unknown_types_and_classes() {
+        assert_eq!(Type::from(65280), Type::Unknown(65280));
+        assert_eq!(u16::from(Type::Unknown(65280)), 65280);
+        assert_eq!(Type::Unknown(65280).to_string(), "TYPE65280");
+        assert_eq!("type65280".parse::<Type>().unwrap(), Type::Unknown(65280));
+        assert_eq!("TYPE1".parse::<Type>().unwrap(), Type::A);
+        assert!("TYPE65536".parse::<Type>().is_err());
+
+        assert_eq!(Class::from(1), Class::IN);
+        assert_eq!(Class::Unknown(32769).to_string(), "CLASS32769");
+        assert_eq!("CLASS1".parse::<Class>().unwrap(), Class::IN);
+    }
+
+    #[test]
+    fn test_records_with_unknown_type_round_trip() {
+        let bytes = [
+            // Owner name "a", type 65280, class 32769, ttl 300 and 3 bytes of rdata.
+            1, 97, 0, 255, 0, 128, 1, 0, 0, 1, 44, 0, 3, 0xc0, 0x00, 1,
+        ];
+        let record = RecordRef::parse(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(record.rdata_bytes(), [0xc0, 0x00, 1]);
+
+        let record = record.to_record().unwrap();
+
+        assert_eq!(record.r#type(), Type::Unknown(65280));
+        assert_eq!(record.class, Class::Unknown(32769));
+        // Pointers in the rdata of unknown types are not expanded.
+        assert_eq!(
+            record.rdata,
+            RData::Unknown {
+                r#type: 65280,
+                data: vec![0xc0, 0x00, 1]
             }
-        })
+        );
+        assert_eq!(record.into_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_rdata_longer_than_rdlength_allows() {
+        let record = |length| ResourceRecord {
+            name: "example.com".parse().unwrap(),
+            class: Class::IN,
+            ttl: 300,
+            rdata: RData::OPENPGPKEY(vec![0; length]),
+        };
+
+        assert!(record(65535).into_bytes().is_ok());
+        assert_eq!(record(65536).into_bytes(), Err(EncodeError::TooLong));
+
+        let mut output = vec![];
+        let result = record(65536).encode(&mut output, &mut Compressor::default());
+        assert_eq!(result, Err(EncodeError::TooLong));
     }
 }
@@ -1,87 +1,174 @@
-use crate::domain_name::{into_string, take_name};
+//! Serialize and deserialize the sections of a `Message`.
+use crate::cursor::Cursor;
+use crate::domain_name::{Compressor, DomainName, NameRef};
 use crate::DecodeError;
-use std::iter::Peekable;
-use std::slice::Iter;
+use std::fmt::Display;
+
+/// The sections of a `Message`. See section 4.1 of RFC 1035.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Section {
+    /// The header.
+    Header,
+    /// The question section.
+    Question,
+    /// The answer section.
+    Answer,
+    /// The authority section.
+    Authority,
+    /// The additional section.
+    Additional,
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Header => "header",
+            Self::Question => "question",
+            Self::Answer => "answer",
+            Self::Authority => "authority",
+            Self::Additional => "additional",
+        };
+
+        write!(f, "{}", name)
+    }
+}
 
 /// See 4.1.2 of rfc
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Question {
-    pub qname: String,
+    /// The domain name being queried.
+    pub qname: DomainName,
+
+    /// The type of the query.
     pub qtype: QType,
-    pub qclass: QClass,
-}
 
-impl TryFrom<&mut Peekable<Iter<'_, u8>>> for Question {
-    type Error = DecodeError;
-
-    fn try_from(value: &mut Peekable<Iter<u8>>) -> Result<Self, Self::Error> {
-        let qname = into_string(take_name(value)?)?;
-
-        Ok(Question {
-            qname,
-            qtype: u16::from_be_bytes([
-                *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-                *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            ])
-            .try_into()
-            .unwrap(),
-            qclass: u16::from_be_bytes([
-                *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-                *value.next().ok_or(DecodeError::NotEnoughBytes)?,
-            ])
-            .try_into()
-            .unwrap(),
-        })
-    }
+    /// The class of the query.
+    pub qclass: QClass,
 }
 
 impl Question {
+    /// Serialize the instance into bytes.
     pub fn into_bytes(self) -> Vec<u8> {
-        let mut name: Vec<u8> = self
-            .qname
-            .split('.')
-            .flat_map(|part| {
-                let mut x = part.to_owned().into_bytes();
-                x.insert(0, x.len().try_into().unwrap());
-                x
-            })
-            .collect();
-        name.push(0);
-
+        let mut name = self.qname.into_bytes();
         name.append(&mut Into::<u16>::into(self.qtype).to_be_bytes().to_vec());
         name.append(&mut Into::<u16>::into(self.qclass).to_be_bytes().to_vec());
         name
     }
+
+    /// Serialize the instance into `output`, compressing the qname using `compressor`.
+    pub(crate) fn encode(self, output: &mut Vec<u8>, compressor: &mut Compressor) {
+        compressor.write_name(&self.qname, output);
+        output.append(&mut Into::<u16>::into(self.qtype).to_be_bytes().to_vec());
+        output.append(&mut Into::<u16>::into(self.qclass).to_be_bytes().to_vec());
+    }
+}
+
+/// A question borrowed from a message. See [`Question`].
+#[derive(Clone, Debug)]
+pub struct QuestionRef<'a> {
+    /// The domain name being queried.
+    pub qname: NameRef<'a>,
+
+    /// The type of the query.
+    pub qtype: QType,
+
+    /// The class of the query.
+    pub qclass: QClass,
+}
+
+impl<'a> QuestionRef<'a> {
+    /// Parse the question at the position of `cursor` and move the cursor past it.
+    pub(crate) fn parse(cursor: &mut Cursor<'a>) -> Result<Self, DecodeError> {
+        Ok(QuestionRef {
+            qname: NameRef::parse(cursor)?,
+            qtype: cursor.read_u16()?.into(),
+            qclass: cursor.read_u16()?.into(),
+        })
+    }
+
+    /// Copy the question into a `Question`.
+    pub fn to_question(&self) -> Question {
+        Question {
+            qname: self.qname.to_domain_name(),
+            qtype: self.qtype.clone(),
+            qclass: self.qclass.clone(),
+        }
+    }
 }
 
+/// Types used in questions. They're a superset of the types used in `ResourceRecord`s.
+/// See section 3.2.3 of RFC 1035.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum QType {
+    /// A host address.
     A,
+    /// An authoritative name server.
     NS,
+    /// A mail destination (obsolete - use MX).
     MD,
+    /// A mail forwarder (obsolete - use MX).
     MF,
+    /// The canonical name for an alias.
     CNAME,
+    /// Marks the start of a zone of authority.
     SOA,
+    /// A mailbox domain name (experimental).
     MB,
+    /// A mail group member (experimental).
     MG,
+    /// A mail rename domain name (experimental).
     MR,
+    /// A null RR (experimental).
     Null,
+    /// A well known service description.
     WKS,
+    /// A domain name pointer.
     PTR,
+    /// Host information.
     HINFO,
+    /// Mailbox or mail list information.
     MINFO,
+    /// Mail exchange.
     MX,
+    /// Text strings.
     TXT,
+    /// IPv6 host address as defined in RFC 3596 DNS Extensions to Support IP Version 6.
+    AAAA,
+    /// The location of a service. See RFC 2782.
+    SRV,
+    /// A rule rewriting a string into a domain name or URI. See RFC 3403.
+    NAPTR,
+    /// An alias for a subtree of the domain name space. See RFC 6672.
+    DNAME,
+    /// The fingerprint of an SSH host key. See RFC 4255.
+    SSHFP,
+    /// The certificate or public key of a TLS server. See RFC 6698.
+    TLSA,
+    /// The OpenPGP public key of an email address. See RFC 7929.
+    OPENPGPKEY,
+    /// The endpoint of a service and its parameters. See RFC 9460.
+    SVCB,
+    /// The endpoint of an HTTPS service and its parameters. See RFC 9460.
+    HTTPS,
+    /// A request for a transfer of an entire zone.
     AXFR,
+    /// A request for mailbox-related records (MB, MG or MR).
     MAILB,
+    /// A request for mail agent RRs (obsolete - see MX).
     MAILA,
+    /// A request for all records.
+    Any,
+    /// A URI. See RFC 7553.
+    URI,
+    /// The certification authorities that may issue certificates for the owner. See RFC 8659.
+    CAA,
+    /// A type this library doesn't know about. See RFC 3597.
+    Unknown(u16),
 }
 
-impl TryFrom<u16> for QType {
-    type Error = String;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Ok(match value {
+impl From<u16> for QType {
+    fn from(value: u16) -> Self {
+        match value {
             1 => Self::A,
             2 => Self::NS,
             3 => Self::MD,
@@ -98,16 +185,23 @@ impl TryFrom<u16> for QType {
             14 => Self::MINFO,
             15 => Self::MX,
             16 => Self::TXT,
+            28 => Self::AAAA,
+            33 => Self::SRV,
+            35 => Self::NAPTR,
+            39 => Self::DNAME,
+            44 => Self::SSHFP,
+            52 => Self::TLSA,
+            61 => Self::OPENPGPKEY,
+            64 => Self::SVCB,
+            65 => Self::HTTPS,
             252 => Self::AXFR,
             253 => Self::MAILB,
             254 => Self::MAILA,
-            _ => {
-                return Err(format!(
-                    "failed to parse value as QType: {} is not a valid value",
-                    value
-                ))
-            }
-        })
+            255 => Self::Any,
+            256 => Self::URI,
+            257 => Self::CAA,
+            _ => Self::Unknown(value),
+        }
     }
 }
 
@@ -130,39 +224,54 @@ impl From<QType> for u16 {
             QType::MINFO => 14,
             QType::MX => 15,
             QType::TXT => 16,
+            QType::AAAA => 28,
+            QType::SRV => 33,
+            QType::NAPTR => 35,
+            QType::DNAME => 39,
+            QType::SSHFP => 44,
+            QType::TLSA => 52,
+            QType::OPENPGPKEY => 61,
+            QType::SVCB => 64,
+            QType::HTTPS => 65,
             QType::AXFR => 252,
             QType::MAILB => 253,
             QType::MAILA => 254,
+            QType::Any => 255,
+            QType::URI => 256,
+            QType::CAA => 257,
+            QType::Unknown(value) => value,
         }
     }
 }
 
+/// Classes used in questions. They're a superset of the classes used in `ResourceRecord`s.
+/// See section 3.2.5 of RFC 1035.
 #[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum QClass {
+    /// The Internet.
     IN,
+    /// The CSNET class (obsolete).
     CS,
+    /// The CHAOS class.
     CH,
+    /// Hesiod.
     HS,
+    /// Any class.
     Any,
+    /// A class this library doesn't know about. See RFC 3597.
+    Unknown(u16),
 }
 
-impl TryFrom<u16> for QClass {
-    type Error = DecodeError;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Ok(match value {
+impl From<u16> for QClass {
+    fn from(value: u16) -> Self {
+        match value {
             1 => Self::IN,
             2 => Self::CS,
             3 => Self::CH,
             4 => Self::HS,
             255 => Self::Any,
-            _ => {
-                return Err(DecodeError::IllegalValue(format!(
-                    "failed to parse value as QClass: {} is not a valid value",
-                    value
-                )))
-            }
-        })
+            _ => Self::Unknown(value),
+        }
     }
 }
 
@@ -174,6 +283,7 @@ impl From<QClass> for u16 {
             QClass::CH => 3,
             QClass::HS => 4,
             QClass::Any => 255,
+            QClass::Unknown(value) => value,
         }
     }
 }
@@ -0,0 +1,467 @@
+//! Serve requests over UDP and TCP.
+//!
+//! A `Server` receives messages, decodes them and passes them to a `RequestHandler`, which
+//! returns the response. The server takes care of the transports: responses over UDP are
+//! truncated to what the client accepts, and undecodable messages are answered with FORMERR.
+//! Given a `ServerCookies`, it also takes care of DNS Cookies, see `Server::cookies()`.
+//!
+//! ```no_run
+//! use dns::server::{Request, Server};
+//! use dns::Message;
+//!
+//! let server = Server::bind("127.0.0.1:5353", |request: &Request| -> Message {
+//!     request.message.response().build()
+//! })
+//! .unwrap();
+//! server.run();
+//! ```
+use crate::cookie::{CookieStatus, ServerCookies};
+use crate::edns::DEFAULT_UDP_PAYLOAD_SIZE;
+use crate::header::{Header, ResponseCode, Type};
+use crate::message::Message;
+use crate::tcp::{self, ServerConfig};
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, SystemTime};
+
+/// The transport a request arrived over.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Transport {
+    /// UDP.
+    Udp,
+    /// TCP.
+    Tcp,
+}
+
+/// A message received by a `Server`.
+#[derive(Clone, Debug)]
+pub struct Request {
+    /// The message.
+    pub message: Message,
+
+    /// The address of the sender.
+    pub source: SocketAddr,
+
+    /// The transport the message arrived over.
+    pub transport: Transport,
+}
+
+/// Answers the requests received by a `Server`.
+///
+/// It's implemented for closures taking a `&Request` and returning a `Message`.
+pub trait RequestHandler: Sync {
+    /// Return the response to `request`. `Message::response()` of `request.message` is a good
+    /// start.
+    fn handle(&self, request: &Request) -> Message;
+}
+
+impl<F> RequestHandler for F
+where
+    F: Fn(&Request) -> Message + Sync,
+{
+    fn handle(&self, request: &Request) -> Message {
+        self(request)
+    }
+}
+
+/// A server listening on the same address over UDP and TCP.
+///
+/// Requests over UDP are handled one after another; connections over TCP are handled
+/// concurrently, see `tcp::serve()`. Messages that aren't queries, like responses, are
+/// ignored.
+#[derive(Debug)]
+pub struct Server<H> {
+    handler: H,
+    udp: UdpSocket,
+    tcp: TcpListener,
+    tcp_config: ServerConfig,
+    settings: Settings,
+}
+
+// The settings of a server that shape its responses, shared with `tokio::Server`.
+#[derive(Clone, Debug)]
+pub(crate) struct Settings {
+    pub(crate) udp_payload_size: u16,
+    pub(crate) cookies: Option<ServerCookies>,
+    pub(crate) require_valid_cookies: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            udp_payload_size: DEFAULT_UDP_PAYLOAD_SIZE,
+            cookies: None,
+            require_valid_cookies: false,
+        }
+    }
+}
+
+impl<H: RequestHandler> Server<H> {
+    /// Bind a UDP socket and a TCP listener to `address`, handling requests with `handler`.
+    ///
+    /// With port 0, the listener takes the port the operating system picked for the socket.
+    pub fn bind(address: impl ToSocketAddrs, handler: H) -> io::Result<Self> {
+        let udp = UdpSocket::bind(address)?;
+        let tcp = TcpListener::bind(udp.local_addr()?)?;
+
+        Ok(Self {
+            handler,
+            udp,
+            tcp,
+            tcp_config: ServerConfig::default(),
+            settings: Settings::default(),
+        })
+    }
+
+    /// Set the idle timeout and connection limit of TCP connections.
+    pub fn tcp_config(mut self, config: ServerConfig) -> Self {
+        self.tcp_config = config;
+        self
+    }
+
+    /// Set the size of the largest UDP message the server receives or sends. Responses are
+    /// truncated to the smallest of this size and the size the client accepts.
+    pub fn udp_payload_size(mut self, udp_payload_size: u16) -> Self {
+        self.settings.udp_payload_size = udp_payload_size;
+        self
+    }
+
+    /// Generate and check server cookies with `cookies`, see the `cookie` module. Responses to
+    /// requests holding a client cookie get a fresh server cookie.
+    pub fn cookies(mut self, cookies: ServerCookies) -> Self {
+        self.settings.cookies = Some(cookies);
+        self
+    }
+
+    /// Set whether requests over UDP holding a client cookie must hold a valid server cookie.
+    /// If so, other requests with a client cookie are answered with BADCOOKIE and a server
+    /// cookie to retry with, see section 5.2.3 of RFC 7873. Requests without cookies, and
+    /// requests over TCP, whose source address can't be spoofed, are always handled.
+    ///
+    /// Only applies once `Server::cookies()` is set. Off by default.
+    pub fn require_valid_cookies(mut self, require_valid_cookies: bool) -> Self {
+        self.settings.require_valid_cookies = require_valid_cookies;
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.udp.local_addr()
+    }
+
+    /// Serve requests forever.
+    ///
+    /// Failing to receive a packet or to accept a connection, for example because a client
+    /// went away or the process ran out of file descriptors, doesn't stop the server.
+    pub fn run(&self) {
+        std::thread::scope(|scope| {
+            scope.spawn(|| loop {
+                let handler = |query: &[u8], source| self.respond(query, source, Transport::Tcp);
+                if tcp::serve(&self.tcp, &self.tcp_config, &handler).is_err() {
+                    // Give the cause, like a lack of file descriptors, time to go away.
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            });
+
+            self.serve_udp();
+        });
+    }
+
+    fn serve_udp(&self) {
+        let mut buf = vec![0; self.settings.udp_payload_size.into()];
+        loop {
+            let (length, source) = match self.udp.recv_from(&mut buf) {
+                Ok(packet) => packet,
+                // Errors concern a single packet, like an ICMP error in response to an earlier
+                // packet that some platforms report.
+                Err(_) => continue,
+            };
+
+            if let Some(response) = self.respond(&buf[..length], source, Transport::Udp) {
+                // The client is gone or unreachable; there's nobody to tell.
+                let _ = self.udp.send_to(&response, source);
+            }
+        }
+    }
+
+    // Return the bytes to respond to `query` with, if any.
+    fn respond(&self, query: &[u8], source: SocketAddr, transport: Transport) -> Option<Vec<u8>> {
+        respond(&self.handler, query, source, transport, &self.settings)
+    }
+}
+
+/// Return the bytes to respond to the message `query` with, if any, as a `Server` does.
+///
+/// Responses over UDP are truncated to the size the client accepts, and at most the UDP
+/// payload size of `settings`. A response that can't be serialized is replaced with SERVFAIL.
+pub(crate) fn respond<H: RequestHandler + ?Sized>(
+    handler: &H,
+    query: &[u8],
+    source: SocketAddr,
+    transport: Transport,
+    settings: &Settings,
+) -> Option<Vec<u8>> {
+    let message = match Message::try_from(query) {
+        Ok(message) => message,
+        Err(_) => return format_error(query)?.into_bytes().ok(),
+    };
+    if message.header.message_type != Type::Query {
+        return None;
+    }
+
+    let max_size = message.udp_payload_size().min(settings.udp_payload_size);
+    let request = Request {
+        message,
+        source,
+        transport,
+    };
+    let response = match &settings.cookies {
+        Some(cookies) => {
+            handle_with_cookies(handler, &request, cookies, settings.require_valid_cookies)
+        }
+        None => handler.handle(&request),
+    };
+    let bytes = match transport {
+        Transport::Udp => response.into_truncated_bytes(max_size.into()),
+        Transport::Tcp => response.into_bytes(),
+    };
+
+    bytes
+        .or_else(|_| {
+            let response = request.message.response();
+            response
+                .rcode(ResponseCode::ServerFailure)
+                .build()
+                .into_bytes()
+        })
+        .ok()
+}
+
+// The response to `request`, with a fresh server cookie if the request holds a client cookie.
+// With `require_valid`, requests over UDP without a valid server cookie aren't passed to
+// `handler`, but answered with BADCOOKIE.
+fn handle_with_cookies<H: RequestHandler + ?Sized>(
+    handler: &H,
+    request: &Request,
+    cookies: &ServerCookies,
+    require_valid: bool,
+) -> Message {
+    let (client, now) = (request.source.ip(), SystemTime::now());
+    let status = cookies.check(&request.message, client, now);
+
+    let mut response = if require_valid
+        && request.transport == Transport::Udp
+        && matches!(status, CookieStatus::ClientOnly | CookieStatus::Invalid)
+    {
+        let response = request.message.response();
+        response.rcode(ResponseCode::BadCookie).build()
+    } else {
+        handler.handle(request)
+    };
+    if let Some(cookie) = cookies.cookie(&request.message, client, now) {
+        response.set_cookie(cookie);
+    }
+
+    response
+}
+
+// The FORMERR response to the undecodable message `query`. Without a header to take the ID
+// from, or for a message that isn't a query, there's nothing to respond.
+fn format_error(query: &[u8]) -> Option<Message> {
+    let header = Header::try_from(query).ok()?;
+    if header.message_type != Type::Query {
+        return None;
+    }
+
+    Some(Message {
+        header: Header {
+            id: header.id,
+            message_type: Type::Reply,
+            op_code: header.op_code,
+            authoritive_answer: false,
+            truncated: false,
+            recursion_desired: header.recursion_desired,
+            recursion_available: false,
+            authentic_data: false,
+            checking_disabled: false,
+            r_code: ResponseCode::FormatError,
+            qd_count: 0,
+            an_count: 0,
+            ns_count: 0,
+            ar_count: 0,
+        },
+        questions: vec![],
+        answers: vec![],
+        authority: vec![],
+        additional: vec![],
+        edns: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::edns::Cookie;
+    use crate::rdata::RData;
+    use crate::resource_record::{Class, ResourceRecord};
+    use crate::sections::QType;
+    use crate::tcp::Connection;
+
+    fn start_server() -> SocketAddr {
+        let server = Server::bind("127.0.0.1:0", |request: &Request| {
+            // Tell the client the transport by the TTL.
+            let ttl = match request.transport {
+                Transport::Udp => 1,
+                Transport::Tcp => 2,
+            };
+            let mut response = request.message.response();
+            for _ in 0..100 {
+                response = response.answer(ResourceRecord {
+                    name: request.message.questions[0].qname.clone(),
+                    class: Class::IN,
+                    ttl,
+                    rdata: RData::A([192, 0, 2, 1].into()),
+                });
+            }
+
+            response.build()
+        })
+        .unwrap();
+        let address = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+
+        address
+    }
+
+    fn query() -> Message {
+        Message::query("orangetux.nl".parse().unwrap(), QType::A)
+            .edns(None)
+            .build()
+    }
+
+    #[test]
+    fn test_serves_udp_and_tcp() {
+        let address = start_server();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // Over UDP, the 100 answers don't fit in the 512 bytes the client accepts.
+        let query = query();
+        socket
+            .send_to(&query.clone().into_bytes().unwrap(), address)
+            .unwrap();
+        let mut buf = [0; 512];
+        let (length, _) = socket.recv_from(&mut buf).unwrap();
+        let response = Message::try_from(&buf[..length]).unwrap();
+        assert_eq!(response.header.id, query.header.id);
+        assert!(response.header.truncated);
+        assert!(response.answers.is_empty());
+
+        let mut connection = Connection::connect(address, Duration::from_secs(5)).unwrap();
+        let response = connection.query(query).unwrap();
+        assert!(!response.header.truncated);
+        assert_eq!(response.answers.len(), 100);
+        assert_eq!(response.answers[0].ttl, 2);
+    }
+
+    #[test]
+    fn test_undecodable_queries_get_format_errors() {
+        let address = start_server();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // The header claims a question that isn't there.
+        let mut bytes = query().into_bytes().unwrap();
+        bytes.truncate(14);
+        socket.send_to(&bytes, address).unwrap();
+        let mut buf = [0; 512];
+        let (length, _) = socket.recv_from(&mut buf).unwrap();
+        let response = Message::try_from(&buf[..length]).unwrap();
+        assert_eq!(response.header.id, u16::from_be_bytes([bytes[0], bytes[1]]));
+        assert_eq!(response.header.message_type, Type::Reply);
+        assert_eq!(response.header.r_code, ResponseCode::FormatError);
+
+        // Without a complete header, or when it isn't a query, there's nothing to respond to.
+        assert!(format_error(&bytes[..11]).is_none());
+        bytes[2] |= 0b1000_0000;
+        assert!(format_error(&bytes).is_none());
+    }
+
+    #[test]
+    fn test_unserializable_responses_get_server_failures() {
+        let handler = |request: &Request| {
+            let record = ResourceRecord {
+                name: "example.com".parse().unwrap(),
+                class: Class::IN,
+                ttl: 300,
+                rdata: RData::OPENPGPKEY(vec![0; 65536]),
+            };
+            request.message.response().answer(record).build()
+        };
+        let query = query().into_bytes().unwrap();
+        let source = "127.0.0.1:53".parse().unwrap();
+
+        for transport in [Transport::Udp, Transport::Tcp] {
+            let response =
+                respond(&handler, &query, source, transport, &Settings::default()).unwrap();
+            let response = Message::try_from(response.as_slice()).unwrap();
+            assert_eq!(response.header.r_code, ResponseCode::ServerFailure);
+            assert!(response.answers.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_cookies() {
+        let handler = |request: &Request| request.message.response().build();
+        let source = "192.0.2.1:53".parse().unwrap();
+        let mut settings = Settings {
+            cookies: Some(ServerCookies::new([7; 16])),
+            ..Settings::default()
+        };
+        let respond = |query: Message, transport, settings: &Settings| {
+            let query = query.into_bytes().unwrap();
+            let response = respond(&handler, &query, source, transport, settings).unwrap();
+            Message::try_from(response.as_slice()).unwrap()
+        };
+        let with_cookie = |cookie| {
+            Message::query("orangetux.nl".parse().unwrap(), QType::A)
+                .cookie(cookie)
+                .build()
+        };
+
+        // Responses carry a server cookie.
+        let response = respond(with_cookie(Cookie::new([1; 8])), Transport::Udp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::NoError);
+        let cookie = response.cookie().unwrap().clone();
+        assert_eq!(cookie.client(), &[1; 8]);
+        assert!(cookie.server().is_some());
+
+        // Unless it's required, a request without a valid server cookie is handled, and so are
+        // requests without cookies.
+        let response = respond(query(), Transport::Udp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::NoError);
+        assert!(response.cookie().is_none());
+
+        settings.require_valid_cookies = true;
+        let response = respond(with_cookie(Cookie::new([1; 8])), Transport::Udp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::BadCookie);
+        assert!(response.cookie().unwrap().server().is_some());
+
+        let invalid = Cookie::with_server_cookie([1; 8], vec![0; 16]).unwrap();
+        let response = respond(with_cookie(invalid.clone()), Transport::Udp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::BadCookie);
+
+        let response = respond(with_cookie(invalid), Transport::Tcp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::NoError);
+
+        let response = respond(with_cookie(cookie), Transport::Udp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::NoError);
+
+        let response = respond(query(), Transport::Udp, &settings);
+        assert_eq!(response.header.r_code, ResponseCode::NoError);
+    }
+}
@@ -0,0 +1,183 @@
+//! Pick the hosts providing a service from its SRV records.
+//!
+//! A service, like SIP over UDP for `example.com`, is located by the SRV records of
+//! `_sip._udp.example.com`. Clients try the targets with the lowest priority first. Among
+//! targets with the same priority, they pick targets at random, favouring those with higher
+//! weights. See [`RFC 2782`].
+//!
+//! ```
+//! use dns::rdata::RData;
+//! use dns::resource_record::{Class, ResourceRecord, Type};
+//! use dns::service;
+//!
+//! let record = |value| ResourceRecord {
+//!     name: "_sip._udp.example.com".parse().unwrap(),
+//!     class: Class::IN,
+//!     ttl: 3600,
+//!     rdata: RData::from_presentation(&Type::SRV, value).unwrap(),
+//! };
+//! let records = [
+//!     record("20 0 5060 backup.example.com."),
+//!     record("10 60 5060 bigbox.example.com."),
+//!     record("10 20 5060 smallbox.example.com."),
+//! ];
+//!
+//! let targets = service::order_targets(&records);
+//! assert_eq!(targets.len(), 3);
+//! assert_eq!(targets[2].name, "backup.example.com".parse().unwrap());
+//! ```
+//!
+//! [`RFC 2782`]: https://www.rfc-editor.org/rfc/rfc2782
+use crate::domain_name::DomainName;
+use crate::rdata::RData;
+use crate::resource_record::ResourceRecord;
+use rand::Rng;
+
+/// A host providing a service.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Target {
+    /// The name of the host.
+    pub name: DomainName,
+
+    /// The port of the service on the host.
+    pub port: u16,
+}
+
+/// Order the targets of the SRV records among `records` in which clients should try them.
+/// Records of other types are ignored.
+///
+/// Returns no targets if the service isn't available, which is the case when the only SRV
+/// record has the root as target.
+pub fn order_targets(records: &[ResourceRecord]) -> Vec<Target> {
+    order_targets_with_rng(records, &mut rand::thread_rng())
+}
+
+/// Like `order_targets()`, picking targets among those with the same priority using `rng`.
+pub fn order_targets_with_rng(records: &[ResourceRecord], rng: &mut impl Rng) -> Vec<Target> {
+    let mut candidates: Vec<(u16, u16, Target)> = records
+        .iter()
+        .filter_map(|record| match &record.rdata {
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => Some((
+                *priority,
+                *weight,
+                Target {
+                    name: target.clone(),
+                    port: *port,
+                },
+            )),
+            _ => None,
+        })
+        .collect();
+
+    // A target of "." means the service isn't available, see the description of the target
+    // field in RFC 2782. Otherwise there's nothing to connect to.
+    candidates.retain(|(_, _, target)| !target.name.is_root());
+
+    // Records with weight 0 go first, so they have a small chance of being picked. The sort is
+    // stable, so records keep their order otherwise.
+    candidates.sort_by_key(|(priority, weight, _)| (*priority, *weight != 0));
+
+    let mut targets = Vec::with_capacity(candidates.len());
+    while let Some((priority, _, _)) = candidates.first() {
+        let end = candidates
+            .iter()
+            .position(|(other, _, _)| other != priority)
+            .unwrap_or(candidates.len());
+        let mut group: Vec<_> = candidates.drain(..end).collect();
+
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|(_, weight, _)| u32::from(*weight)).sum();
+            let pick = rng.gen_range(0..=total);
+
+            // Take the first record of which the running sum of weights reaches the pick.
+            let mut sum = 0;
+            let index = group
+                .iter()
+                .position(|(_, weight, _)| {
+                    sum += u32::from(*weight);
+                    sum >= pick
+                })
+                .unwrap_or(group.len() - 1);
+            targets.push(group.remove(index).2);
+        }
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resource_record::Class;
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn srv(priority: u16, weight: u16, target: &str) -> ResourceRecord {
+        ResourceRecord {
+            name: "_sip._udp.example.com".parse().unwrap(),
+            class: Class::IN,
+            ttl: 3600,
+            rdata: RData::SRV {
+                priority,
+                weight,
+                port: 5060,
+                target: target.parse().unwrap(),
+            },
+        }
+    }
+
+    fn names(targets: Vec<Target>) -> Vec<String> {
+        targets
+            .into_iter()
+            .map(|target| target.name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_order_by_priority() {
+        let records = [
+            srv(20, 10, "c"),
+            srv(10, 10, "a"),
+            ResourceRecord {
+                rdata: RData::A([192, 0, 2, 1].into()),
+                ..srv(0, 0, "a")
+            },
+            srv(10, 0, "b"),
+        ];
+
+        // Always picking 0 takes the first record of each priority, where weight 0 goes first.
+        let targets = order_targets_with_rng(&records, &mut StepRng::new(0, 0));
+        assert_eq!(names(targets), ["b", "a", "c"]);
+        assert_eq!(order_targets(&records).len(), 3);
+    }
+
+    #[test]
+    fn test_order_by_weight() {
+        let records = [srv(10, 1, "light"), srv(10, 3, "heavy"), srv(10, 0, "zero")];
+        let mut rng = StdRng::seed_from_u64(2782);
+
+        let mut heavy_first = 0;
+        for _ in 0..1000 {
+            let targets = names(order_targets_with_rng(&records, &mut rng));
+            assert_eq!(targets.len(), 3);
+            if targets[0] == "heavy" {
+                heavy_first += 1;
+            }
+        }
+
+        // "heavy" goes first 3 out of 5 times: its weight of 3 covers 3 of the picks 0 to 4.
+        assert!((500..700).contains(&heavy_first), "{}", heavy_first);
+    }
+
+    #[test]
+    fn test_service_not_available() {
+        assert!(order_targets(&[srv(0, 0, ".")]).is_empty());
+        assert!(order_targets(&[]).is_empty());
+    }
+}
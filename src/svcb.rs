@@ -0,0 +1,730 @@
+//! The parameters of SVCB and HTTPS records.
+//!
+//! An SVCB record, or an HTTPS record for HTTP origins, tells a client where a service is
+//! available and how to connect to it: the protocols it supports, its port, addresses to try
+//! before the address lookups complete and so on. These are carried as SvcParams, key-value
+//! pairs that are kept in increasing order of their key. See [`RFC 9460`].
+//!
+//! ```
+//! use dns::rdata::RData;
+//! use dns::resource_record::Type;
+//! use dns::svcb::{SvcParam, SvcParamKey};
+//!
+//! let rdata = RData::from_presentation(&Type::HTTPS, r#"1 . port=8443 alpn="h2,h3""#).unwrap();
+//! let params = match &rdata {
+//!     RData::HTTPS { params, .. } => params,
+//!     _ => unreachable!(),
+//! };
+//! assert_eq!(params.get(SvcParamKey::Port), Some(&SvcParam::Port(8443)));
+//!
+//! // Parameters are printed in order of their key.
+//! assert_eq!(rdata.to_string(), r#"1 . alpn="h2,h3" port=8443"#);
+//! ```
+//!
+//! [`RFC 9460`]: https://www.rfc-editor.org/rfc/rfc9460
+use crate::cursor::Cursor;
+use crate::rdata::{from_base64, to_base64, write_quoted};
+use crate::{write_length, DecodeError, DecodeErrorKind, EncodeError};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// The key of an SvcParam. See section 14.3.2 of RFC 9460.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SvcParamKey {
+    /// The keys a client must support to use the record.
+    Mandatory,
+    /// The protocols the service supports.
+    Alpn,
+    /// The service doesn't support the default protocol of the scheme.
+    NoDefaultAlpn,
+    /// The port of the service.
+    Port,
+    /// IPv4 addresses of the service.
+    Ipv4Hint,
+    /// The Encrypted ClientHello configuration of the service.
+    Ech,
+    /// IPv6 addresses of the service.
+    Ipv6Hint,
+    /// The URI template of a DNS over HTTPS service. See RFC 9461.
+    DohPath,
+    /// A key this library doesn't know about.
+    Unknown(u16),
+}
+
+impl From<u16> for SvcParamKey {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => Self::Mandatory,
+            1 => Self::Alpn,
+            2 => Self::NoDefaultAlpn,
+            3 => Self::Port,
+            4 => Self::Ipv4Hint,
+            5 => Self::Ech,
+            6 => Self::Ipv6Hint,
+            7 => Self::DohPath,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<SvcParamKey> for u16 {
+    fn from(key: SvcParamKey) -> Self {
+        match key {
+            SvcParamKey::Mandatory => 0,
+            SvcParamKey::Alpn => 1,
+            SvcParamKey::NoDefaultAlpn => 2,
+            SvcParamKey::Port => 3,
+            SvcParamKey::Ipv4Hint => 4,
+            SvcParamKey::Ech => 5,
+            SvcParamKey::Ipv6Hint => 6,
+            SvcParamKey::DohPath => 7,
+            SvcParamKey::Unknown(value) => value,
+        }
+    }
+}
+
+impl Display for SvcParamKey {
+    // Print the name of the key, or "keyN" for unknown keys.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Mandatory => "mandatory",
+            Self::Alpn => "alpn",
+            Self::NoDefaultAlpn => "no-default-alpn",
+            Self::Port => "port",
+            Self::Ipv4Hint => "ipv4hint",
+            Self::Ech => "ech",
+            Self::Ipv6Hint => "ipv6hint",
+            Self::DohPath => "dohpath",
+            Self::Unknown(value) => return write!(f, "key{}", value),
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SvcParamKey {
+    type Err = DecodeError;
+
+    // Parse the name of a key, or "keyN" for any key. Key 65535 is reserved as invalid key.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let key = match value {
+            "mandatory" => Self::Mandatory,
+            "alpn" => Self::Alpn,
+            "no-default-alpn" => Self::NoDefaultAlpn,
+            "port" => Self::Port,
+            "ipv4hint" => Self::Ipv4Hint,
+            "ech" => Self::Ech,
+            "ipv6hint" => Self::Ipv6Hint,
+            "dohpath" => Self::DohPath,
+            other => other
+                .strip_prefix("key")
+                .filter(|number| number.bytes().all(|byte| byte.is_ascii_digit()))
+                .and_then(|number| number.parse::<u16>().ok())
+                .filter(|number| *number != u16::MAX)
+                .map(Self::from)
+                .ok_or_else(|| {
+                    DecodeErrorKind::IllegalValue(format!(
+                        "failed to parse SvcParamKey: {} is not a valid key",
+                        value
+                    ))
+                })?,
+        };
+
+        Ok(key)
+    }
+}
+
+/// An SvcParam. See section 7 of RFC 9460.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SvcParam {
+    /// The keys a client must support to use the record, in increasing order. They must be
+    /// present in the record.
+    Mandatory(Vec<SvcParamKey>),
+    /// The protocol IDs of the protocols the service supports, like "h2" and "h3". See the
+    /// ALPN registry of IANA. There's at least one, and each is 1 to 255 bytes; encoding fails
+    /// otherwise.
+    Alpn(Vec<Vec<u8>>),
+    /// The service doesn't support the default protocol of the scheme, like "http/1.1" for
+    /// HTTPS.
+    NoDefaultAlpn,
+    /// The port of the service.
+    Port(u16),
+    /// IPv4 addresses of the service, which a client may use before its address lookups
+    /// complete.
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// An ECHConfigList, the Encrypted ClientHello configuration of the service.
+    Ech(Vec<u8>),
+    /// IPv6 addresses of the service, which a client may use before its address lookups
+    /// complete.
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// The relative URI template of a DNS over HTTPS service, like "/dns-query{?dns}". See
+    /// RFC 9461.
+    DohPath(String),
+    /// A parameter this library doesn't know about.
+    Unknown {
+        /// The key.
+        key: u16,
+        /// The value.
+        value: Vec<u8>,
+    },
+}
+
+impl SvcParam {
+    /// The key of the parameter.
+    pub fn key(&self) -> SvcParamKey {
+        match self {
+            Self::Mandatory(_) => SvcParamKey::Mandatory,
+            Self::Alpn(_) => SvcParamKey::Alpn,
+            Self::NoDefaultAlpn => SvcParamKey::NoDefaultAlpn,
+            Self::Port(_) => SvcParamKey::Port,
+            Self::Ipv4Hint(_) => SvcParamKey::Ipv4Hint,
+            Self::Ech(_) => SvcParamKey::Ech,
+            Self::Ipv6Hint(_) => SvcParamKey::Ipv6Hint,
+            Self::DohPath(_) => SvcParamKey::DohPath,
+            Self::Unknown { key, .. } => SvcParamKey::from(*key),
+        }
+    }
+
+    // Read the value of the parameter with the given `key` from `data`, which is limited to the
+    // value.
+    fn decode(key: u16, data: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let length = data.remaining();
+        let malformed = || illegal_param(key);
+
+        let param = match SvcParamKey::from(key) {
+            SvcParamKey::Mandatory => {
+                if length == 0 || !length.is_multiple_of(2) {
+                    return Err(malformed());
+                }
+                let mut keys: Vec<u16> = vec![];
+                while data.remaining() > 0 {
+                    let key = data.read_u16()?;
+                    if keys.last().is_some_and(|last| *last >= key) {
+                        return Err(malformed());
+                    }
+                    keys.push(key);
+                }
+                Self::Mandatory(keys.into_iter().map(SvcParamKey::from).collect())
+            }
+            SvcParamKey::Alpn => {
+                let mut ids = vec![];
+                while data.remaining() > 0 {
+                    let length = data.read_u8()?;
+                    if length == 0 {
+                        return Err(malformed());
+                    }
+                    ids.push(data.read_bytes(length.into())?.to_vec());
+                }
+                if ids.is_empty() {
+                    return Err(malformed());
+                }
+                Self::Alpn(ids)
+            }
+            SvcParamKey::NoDefaultAlpn if length == 0 => Self::NoDefaultAlpn,
+            SvcParamKey::Port if length == 2 => Self::Port(data.read_u16()?),
+            SvcParamKey::Ipv4Hint if length > 0 && length.is_multiple_of(4) => {
+                let mut addresses = vec![];
+                while data.remaining() > 0 {
+                    addresses.push(data.read_array::<4>()?.into());
+                }
+                Self::Ipv4Hint(addresses)
+            }
+            SvcParamKey::Ech => Self::Ech(data.read_bytes(length)?.to_vec()),
+            SvcParamKey::Ipv6Hint if length > 0 && length.is_multiple_of(16) => {
+                let mut addresses = vec![];
+                while data.remaining() > 0 {
+                    addresses.push(data.read_array::<16>()?.into());
+                }
+                Self::Ipv6Hint(addresses)
+            }
+            SvcParamKey::DohPath => Self::DohPath(
+                String::from_utf8(data.read_bytes(length)?.to_vec()).map_err(|_| malformed())?,
+            ),
+            SvcParamKey::Unknown(_) => Self::Unknown {
+                key,
+                value: data.read_bytes(length)?.to_vec(),
+            },
+            _ => return Err(malformed()),
+        };
+
+        Ok(param)
+    }
+
+    fn encode(self, output: &mut Vec<u8>) -> Result<(), EncodeError> {
+        output.extend_from_slice(&u16::from(self.key()).to_be_bytes());
+
+        let length_position = output.len();
+        output.extend_from_slice(&[0, 0]);
+        match self {
+            Self::Mandatory(keys) => {
+                for key in keys {
+                    output.extend_from_slice(&u16::from(key).to_be_bytes());
+                }
+            }
+            Self::Alpn(ids) => {
+                // Identifiers are 1 to 255 bytes, and there's at least one, see section 7.1.1 of
+                // RFC 9460.
+                if ids.is_empty() {
+                    return Err(EncodeError::IllegalValue(
+                        "failed to encode SvcParam: alpn holds no protocol identifiers".to_string(),
+                    ));
+                }
+                for id in ids {
+                    let length = u8::try_from(id.len()).ok().filter(|length| *length > 0);
+                    let Some(length) = length else {
+                        return Err(EncodeError::IllegalValue(format!(
+                            "failed to encode SvcParam: alpn identifier of {} bytes isn't 1 to \
+                             255 bytes",
+                            id.len()
+                        )));
+                    };
+                    output.push(length);
+                    output.extend(id);
+                }
+            }
+            Self::NoDefaultAlpn => (),
+            Self::Port(port) => output.extend_from_slice(&port.to_be_bytes()),
+            Self::Ipv4Hint(addresses) => {
+                for address in addresses {
+                    output.extend_from_slice(&address.octets());
+                }
+            }
+            Self::Ipv6Hint(addresses) => {
+                for address in addresses {
+                    output.extend_from_slice(&address.octets());
+                }
+            }
+            Self::DohPath(path) => output.extend(path.into_bytes()),
+            Self::Ech(value) | Self::Unknown { value, .. } => output.extend(value),
+        }
+
+        write_length(output, length_position)
+    }
+
+    // Parse the parameter `key` with `value` in presentation format. The value is given with
+    // the escapes of its character-string replaced; lists may still hold escaped commas. See
+    // appendix A of RFC 9460.
+    pub(crate) fn from_presentation(key: &str, value: Option<Vec<u8>>) -> Option<Self> {
+        let key: SvcParamKey = key.parse().ok()?;
+        let text = || String::from_utf8(value.clone()?).ok();
+
+        let param = match key {
+            SvcParamKey::Mandatory => {
+                let mut keys: Vec<SvcParamKey> = parse_list(&text()?)?;
+                let count = keys.len();
+                keys.sort_by_key(|key| u16::from(*key));
+                keys.dedup();
+                if keys.len() != count {
+                    return None;
+                }
+                Self::Mandatory(keys)
+            }
+            SvcParamKey::Alpn => {
+                let ids = split_value_list(&value?)?;
+                if ids.iter().any(|id| id.is_empty() || id.len() > 255) {
+                    return None;
+                }
+                Self::Alpn(ids)
+            }
+            SvcParamKey::NoDefaultAlpn => match value {
+                Some(value) if !value.is_empty() => return None,
+                _ => Self::NoDefaultAlpn,
+            },
+            SvcParamKey::Port => Self::Port(text()?.parse().ok()?),
+            SvcParamKey::Ipv4Hint => Self::Ipv4Hint(parse_list(&text()?)?),
+            SvcParamKey::Ech => Self::Ech(from_base64(&text()?)?),
+            SvcParamKey::Ipv6Hint => Self::Ipv6Hint(parse_list(&text()?)?),
+            SvcParamKey::DohPath => Self::DohPath(text()?),
+            SvcParamKey::Unknown(key) => Self::Unknown {
+                key,
+                value: value.unwrap_or_default(),
+            },
+        };
+
+        Some(param)
+    }
+}
+
+impl Display for SvcParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Prints a comma-separated list of values.
+        fn write_list<T: Display>(
+            f: &mut std::fmt::Formatter<'_>,
+            values: &[T],
+        ) -> std::fmt::Result {
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", value)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Self::NoDefaultAlpn => return write!(f, "{}", self.key()),
+            Self::Unknown { value, .. } if value.is_empty() => return write!(f, "{}", self.key()),
+            _ => write!(f, "{}=", self.key())?,
+        }
+
+        match self {
+            Self::Mandatory(keys) => write_list(f, keys),
+            Self::Alpn(ids) => {
+                // Commas and backslashes within an ID are escaped before the list is quoted as
+                // a whole, so they end up escaped twice.
+                let mut list = vec![];
+                for (index, id) in ids.iter().enumerate() {
+                    if index > 0 {
+                        list.push(b',');
+                    }
+                    for byte in id {
+                        if matches!(byte, b',' | b'\\') {
+                            list.push(b'\\');
+                        }
+                        list.push(*byte);
+                    }
+                }
+                write_quoted(f, &list)
+            }
+            Self::Port(port) => write!(f, "{}", port),
+            Self::Ipv4Hint(addresses) => write_list(f, addresses),
+            Self::Ech(value) => write!(f, "{}", to_base64(value)),
+            Self::Ipv6Hint(addresses) => write_list(f, addresses),
+            Self::DohPath(path) => write_quoted(f, path.as_bytes()),
+            Self::Unknown { value, .. } => write_quoted(f, value),
+            // Printed without value above.
+            Self::NoDefaultAlpn => Ok(()),
+        }
+    }
+}
+
+/// The SvcParams of an SVCB or HTTPS record, ordered by key. A record holds at most one
+/// parameter per key.
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct SvcParams(BTreeMap<u16, SvcParam>);
+
+impl SvcParams {
+    /// Create an instance without parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `param`, returning the parameter it replaces, if any.
+    pub fn insert(&mut self, param: SvcParam) -> Option<SvcParam> {
+        self.0.insert(param.key().into(), param)
+    }
+
+    /// The parameter with `key`, if present.
+    pub fn get(&self, key: SvcParamKey) -> Option<&SvcParam> {
+        self.0.get(&key.into())
+    }
+
+    /// Iterate over the parameters in order of their key.
+    pub fn iter(&self) -> impl Iterator<Item = &SvcParam> {
+        self.0.values()
+    }
+
+    /// The number of parameters.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Serialize the parameters into `output`, in order of their key.
+    pub(crate) fn encode(self, output: &mut Vec<u8>) -> Result<(), EncodeError> {
+        for param in self.0.into_values() {
+            param.encode(output)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize the parameters from the rest of `value`.
+    ///
+    /// Fails unless the keys are in strictly increasing order, or when a key that's mandatory
+    /// isn't present. See section 2.2 and 8 of RFC 9460.
+    pub(crate) fn decode(value: &mut Cursor<'_>) -> Result<Self, DecodeError> {
+        let mut params = BTreeMap::new();
+        let mut last_key = None;
+        while value.remaining() > 0 {
+            let position = value.position();
+            let key = value.read_u16()?;
+            if key == u16::MAX || last_key.is_some_and(|last| last >= key) {
+                return Err(DecodeErrorKind::IllegalValue(format!(
+                    "failed to parse SvcParams: key {} is out of order or invalid",
+                    key
+                ))
+                .into());
+            }
+            last_key = Some(key);
+
+            let length = value.read_u16()?;
+            let mut data = value.split(length.into())?;
+            // Running out of bytes within a value means the value is malformed.
+            let param =
+                SvcParam::decode(key, &mut data).map_err(|_| illegal_param(key).at(position))?;
+            params.insert(key, param);
+        }
+
+        let params = Self(params);
+        params.check()?;
+
+        Ok(params)
+    }
+
+    /// Check that the keys listed by the "mandatory" parameter are present, and that it
+    /// doesn't list itself.
+    pub(crate) fn check(&self) -> Result<(), DecodeError> {
+        let keys = match self.get(SvcParamKey::Mandatory) {
+            Some(SvcParam::Mandatory(keys)) => keys,
+            _ => return Ok(()),
+        };
+
+        match keys
+            .iter()
+            .find(|key| **key == SvcParamKey::Mandatory || self.get(**key).is_none())
+        {
+            Some(key) => Err(DecodeErrorKind::IllegalValue(format!(
+                "failed to parse SvcParams: mandatory key {} is missing",
+                key
+            ))
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromIterator<SvcParam> for SvcParams {
+    fn from_iter<T: IntoIterator<Item = SvcParam>>(iter: T) -> Self {
+        let mut params = Self::new();
+        for param in iter {
+            params.insert(param);
+        }
+
+        params
+    }
+}
+
+impl Display for SvcParams {
+    // Print the parameters separated by spaces.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, param) in self.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        Ok(())
+    }
+}
+
+// Parse a comma-separated list of values without escapes, like addresses.
+fn parse_list<T: FromStr>(value: &str) -> Option<Vec<T>> {
+    value.split(',').map(|item| item.parse().ok()).collect()
+}
+
+// Split a comma-separated list in which commas and backslashes within items are escaped with a
+// backslash. See appendix A.1 of RFC 9460.
+fn split_value_list(value: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut items = vec![vec![]];
+    let mut bytes = value.iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\\' => items.last_mut()?.push(*bytes.next()?),
+            b',' => items.push(vec![]),
+            _ => items.last_mut()?.push(*byte),
+        }
+    }
+
+    Some(items)
+}
+
+fn illegal_param(key: u16) -> DecodeError {
+    DecodeErrorKind::IllegalValue(format!(
+        "failed to parse SvcParam: value of key {} is malformed",
+        key
+    ))
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::RData;
+    use crate::resource_record::Type;
+
+    fn params(rdata: RData) -> SvcParams {
+        match rdata {
+            RData::SVCB { params, .. } | RData::HTTPS { params, .. } => params,
+            _ => panic!("not an SVCB or HTTPS record"),
+        }
+    }
+
+    #[test]
+    fn test_serialization_and_deserialization() {
+        let params: SvcParams = [
+            SvcParam::Mandatory(vec![SvcParamKey::Alpn, SvcParamKey::Ipv4Hint]),
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+            SvcParam::NoDefaultAlpn,
+            SvcParam::Port(8443),
+            SvcParam::Ipv4Hint(vec![[192, 0, 2, 1].into(), [192, 0, 2, 2].into()]),
+            SvcParam::Ech(vec![1, 2, 3]),
+            SvcParam::Ipv6Hint(vec!["2001:db8::1".parse().unwrap()]),
+            SvcParam::DohPath("/dns-query{?dns}".to_string()),
+            SvcParam::Unknown {
+                key: 667,
+                value: b"hello".to_vec(),
+            },
+        ]
+        .into_iter()
+        .collect();
+
+        let mut bytes = vec![];
+        params.clone().encode(&mut bytes).unwrap();
+        assert_eq!(&bytes[..8], [0, 0, 0, 4, 0, 1, 0, 4]);
+        assert_eq!(SvcParams::decode(&mut Cursor::new(&bytes)).unwrap(), params);
+    }
+
+    #[test]
+    fn test_serialization_checks_alpn_ids() {
+        let encode = |ids: Vec<Vec<u8>>| SvcParam::Alpn(ids).encode(&mut vec![]);
+
+        assert!(encode(vec![b"h2".to_vec(), vec![b'a'; 255]]).is_ok());
+        assert!(matches!(
+            encode(vec![b"h2".to_vec(), vec![]]),
+            Err(EncodeError::IllegalValue(_))
+        ));
+        assert!(matches!(
+            encode(vec![vec![b'a'; 256]]),
+            Err(EncodeError::IllegalValue(_))
+        ));
+        assert!(matches!(encode(vec![]), Err(EncodeError::IllegalValue(_))));
+    }
+
+    #[test]
+    fn test_deserialization_checks_params() {
+        let decode = |bytes: &[u8]| SvcParams::decode(&mut Cursor::new(bytes));
+
+        // Keys must be in strictly increasing order.
+        assert!(decode(&[0, 3, 0, 2, 1, 187, 0, 1, 0, 3, 2, b'h', b'2']).is_err());
+        assert!(decode(&[0, 3, 0, 2, 1, 187, 0, 3, 0, 2, 1, 187]).is_err());
+        // Key 65535 is invalid.
+        assert!(decode(&[255, 255, 0, 0]).is_err());
+        // The port must be 2 bytes, and no-default-alpn must be empty.
+        assert!(decode(&[0, 3, 0, 1, 1]).is_err());
+        assert!(decode(&[0, 2, 0, 1, 1]).is_err());
+        // Empty ALPN IDs, or an ALPN ID longer than the value.
+        assert!(decode(&[0, 1, 0, 1, 0]).is_err());
+        assert!(decode(&[0, 1, 0, 2, 2, b'h']).is_err());
+        // IPv4 hints must be a multiple of 4 bytes.
+        assert!(decode(&[0, 4, 0, 3, 192, 0, 2]).is_err());
+        // The value is longer than the rdata.
+        assert!(decode(&[0, 3, 0, 4, 1, 187]).is_err());
+        // Mandatory keys must be present, and can't include "mandatory" itself.
+        assert!(decode(&[0, 0, 0, 2, 0, 3]).is_err());
+        assert!(decode(&[0, 0, 0, 2, 0, 0]).is_err());
+        assert!(decode(&[0, 0, 0, 2, 0, 3, 0, 3, 0, 2, 1, 187]).is_ok());
+    }
+
+    #[test]
+    fn test_presentation_format() {
+        // The examples of appendix D of RFC 9460.
+        let rdata = RData::from_presentation(&Type::SVCB, "0 foo.example.com.").unwrap();
+        assert!(params(rdata.clone()).is_empty());
+        assert_eq!(rdata.to_string(), "0 foo.example.com.");
+
+        let rdata = RData::from_presentation(&Type::SVCB, "16 foo.example.org. key667=hello");
+        assert_eq!(
+            params(rdata.unwrap()).get(SvcParamKey::Unknown(667)),
+            Some(&SvcParam::Unknown {
+                key: 667,
+                value: b"hello".to_vec()
+            })
+        );
+
+        let rdata =
+            RData::from_presentation(&Type::SVCB, r#"16 foo.example.org. key667="hello\210qoo""#)
+                .unwrap();
+        assert_eq!(
+            rdata.to_string(),
+            r#"16 foo.example.org. key667="hello\210qoo""#
+        );
+
+        let rdata = RData::from_presentation(
+            &Type::SVCB,
+            r#"1 foo.example.com. ipv6hint="2001:db8::1,2001:db8::53:1""#,
+        )
+        .unwrap();
+        assert_eq!(
+            rdata.to_string(),
+            "1 foo.example.com. ipv6hint=2001:db8::1,2001:db8::53:1"
+        );
+
+        let rdata = RData::from_presentation(
+            &Type::SVCB,
+            r#"16 foo.example.org. alpn=h2,h3-19 mandatory=ipv4hint,alpn ipv4hint=192.0.2.1"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rdata.to_string(),
+            r#"16 foo.example.org. mandatory=alpn,ipv4hint alpn="h2,h3-19" ipv4hint=192.0.2.1"#
+        );
+
+        // An ALPN ID holding a comma and a backslash is escaped twice.
+        let rdata = RData::from_presentation(
+            &Type::SVCB,
+            r#"16 foo.example.org. alpn="f\\\\oo\\,bar,h2""#,
+        )
+        .unwrap();
+        assert_eq!(
+            params(rdata.clone()).get(SvcParamKey::Alpn),
+            Some(&SvcParam::Alpn(vec![b"f\\oo,bar".to_vec(), b"h2".to_vec()]))
+        );
+        assert_eq!(
+            rdata.to_string(),
+            r#"16 foo.example.org. alpn="f\\\\oo\\,bar,h2""#
+        );
+
+        let rdata = RData::from_presentation(
+            &Type::HTTPS,
+            r#"1 . alpn=h2 no-default-alpn ech=AQID dohpath="/dns-query{?dns}""#,
+        )
+        .unwrap();
+        assert_eq!(
+            params(rdata.clone()).get(SvcParamKey::Ech),
+            Some(&SvcParam::Ech(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            rdata.to_string(),
+            r#"1 . alpn="h2" no-default-alpn ech=AQID dohpath="/dns-query{?dns}""#
+        );
+
+        // The failure cases of appendix D.3 of RFC 9460.
+        for value in [
+            "1 foo.example.com. key123=abc key123=def",
+            "1 foo.example.com. mandatory",
+            "1 foo.example.com. alpn",
+            "1 foo.example.com. port",
+            "1 foo.example.com. ipv4hint",
+            "1 foo.example.com. ipv6hint",
+            "1 foo.example.com. no-default-alpn=abc",
+            "1 foo.example.com. mandatory=key123",
+            "1 foo.example.com. mandatory=mandatory",
+            r#"1 foo.example.com. mandatory=key123,key123 key123=abc"#,
+            "1 foo.example.com. key65535=abc",
+            r#"1 foo.example.com. alpn="h2"#,
+        ] {
+            assert!(
+                RData::from_presentation(&Type::SVCB, value).is_err(),
+                "{}",
+                value
+            );
+        }
+    }
+}
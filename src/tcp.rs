@@ -0,0 +1,489 @@
+//! Send and receive messages over TCP.
+//!
+//! Over TCP, each message is prefixed with its length as a 2 byte integer. A connection carries
+//! any number of messages: a client may send several queries without waiting for their
+//! responses, and a server may answer them in any order. Responses are matched to queries by
+//! the ID in their header. See [`RFC 7766`].
+//!
+//! TCP carries responses that don't fit in a UDP packet, like zone transfers, which may span
+//! several messages.
+//!
+//! [`RFC 7766`]: https://www.rfc-editor.org/rfc/rfc7766
+use crate::message::Message;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How long a server keeps a connection without queries open by default. See section 6.2.3 of
+/// RFC 7766.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many connections a server accepts at the same time by default.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 128;
+
+// How many queries of a connection a server handles at the same time. Further queries are
+// handled one after another, which stops the server from reading the connection meanwhile.
+const MAX_PIPELINED_QUERIES: usize = 16;
+
+/// Read a length-prefixed message from `reader`. Returns `None` if the connection was closed
+/// before the next message.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    match reader.read_exact(&mut length) {
+        Ok(()) => (),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    let mut message = vec![0; u16::from_be_bytes(length).into()];
+    reader.read_exact(&mut message)?;
+
+    Ok(Some(message))
+}
+
+/// Write `message` prefixed with its length to `writer`. Fails if the message is longer than
+/// 65535 bytes.
+pub fn write_frame(writer: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let length = u16::try_from(message.len()).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "message is longer than 65535 bytes",
+        )
+    })?;
+
+    // Write the message at once, so it isn't split over several TCP segments needlessly.
+    writer.write_all(&[length.to_be_bytes().as_slice(), message].concat())
+}
+
+/// A connection from a client to a server.
+///
+/// Queries can be pipelined: several queries can be sent before receiving their responses.
+/// Responses are matched to queries by ID, so the ID of each query that's waiting for a
+/// response must be unique. Responses to other queries that arrive in the meantime are kept
+/// until they're asked for.
+///
+/// ```no_run
+/// use dns::sections::QType;
+/// use dns::tcp::Connection;
+/// use dns::Message;
+/// use std::time::Duration;
+///
+/// let mut connection = Connection::connect("198.41.0.4:53", Duration::from_secs(5)).unwrap();
+///
+/// let first = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+/// let second = Message::query("orangetux.nl".parse().unwrap(), QType::AAAA).build();
+/// let (first_id, second_id) = (first.header.id, second.header.id);
+/// connection.send(first).unwrap();
+/// connection.send(second).unwrap();
+///
+/// let second = connection.receive(second_id).unwrap();
+/// let first = connection.receive(first_id).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+    responses: HashMap<u16, VecDeque<Message>>,
+}
+
+impl Connection {
+    /// Connect to the server at `address`. Connecting, and later receiving a response, fails
+    /// after `timeout`.
+    pub fn connect(address: impl ToSocketAddrs, timeout: Duration) -> io::Result<Self> {
+        let mut last_error = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+
+                    return Ok(Self {
+                        stream,
+                        responses: HashMap::new(),
+                    });
+                }
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "address resolved to no addresses")
+        }))
+    }
+
+    /// Send `query` without waiting for the response.
+    pub fn send(&mut self, query: Message) -> io::Result<()> {
+        let query = query
+            .into_bytes()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+        write_frame(&mut self.stream, &query)
+    }
+
+    /// Receive the next response with ID `id`. Responses with other IDs that arrive first are
+    /// kept for later calls.
+    ///
+    /// Fails with `ErrorKind::UnexpectedEof` if the server closes the connection, and with
+    /// `ErrorKind::InvalidData` if a response can't be decoded.
+    pub fn receive(&mut self, id: u16) -> io::Result<Message> {
+        if let Some(response) = self.responses.get_mut(&id).and_then(VecDeque::pop_front) {
+            return Ok(response);
+        }
+
+        loop {
+            let frame = read_frame(&mut self.stream)?.ok_or_else(|| {
+                io::Error::new(ErrorKind::UnexpectedEof, "server closed the connection")
+            })?;
+            let response = Message::try_from(frame.as_slice())
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+            if response.header.id == id {
+                return Ok(response);
+            }
+            self.responses
+                .entry(response.header.id)
+                .or_default()
+                .push_back(response);
+        }
+    }
+
+    /// Send `query` and receive its response.
+    pub fn query(&mut self, query: Message) -> io::Result<Message> {
+        let id = query.header.id;
+        self.send(query)?;
+        self.receive(id)
+    }
+}
+
+/// The settings of `serve()`.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// How long to wait for the next query before closing a connection. The whole query must
+    /// arrive within this time, however slowly the client sends it.
+    pub idle_timeout: Duration,
+
+    /// How many connections to serve at the same time. Further connections are closed right
+    /// away.
+    pub max_connections: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+        }
+    }
+}
+
+/// Accept connections on `listener` forever, serving each on its own thread.
+///
+/// `handler` is called with every message received and the address of the client. The bytes
+/// it returns, if any, are sent back. Queries pipelined on a connection are handled
+/// concurrently, each on its own thread, and answered as soon as they're handled, so a slow
+/// query doesn't hold up the ones after it. See section 6.2.1.1 of RFC 7766.
+///
+/// Failing to accept a connection, for example because the process ran out of file
+/// descriptors, doesn't stop the server. Returns an error only if the listener itself is
+/// unusable, for example because it isn't listening.
+pub fn serve<H>(listener: &TcpListener, config: &ServerConfig, handler: &H) -> io::Result<()>
+where
+    H: Fn(&[u8], SocketAddr) -> Option<Vec<u8>> + Sync,
+{
+    serve_accepted(|| listener.accept(), config, handler)
+}
+
+// Serve the connections returned by `accept`, like `serve()`.
+fn serve_accepted<A, H>(mut accept: A, config: &ServerConfig, handler: &H) -> io::Result<()>
+where
+    A: FnMut() -> io::Result<(TcpStream, SocketAddr)>,
+    H: Fn(&[u8], SocketAddr) -> Option<Vec<u8>> + Sync,
+{
+    let connections = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| loop {
+        let (stream, peer) = match accept() {
+            Ok(connection) => connection,
+            // Returning would wait for every connection being served to end, see
+            // `std::thread::scope()`, so only give up if the listener can't recover.
+            Err(error) if is_fatal(&error) => return Err(error),
+            // The client gave up before the connection was accepted.
+            Err(error) if error.kind() == ErrorKind::ConnectionAborted => continue,
+            Err(_) => {
+                // Give the cause, like a lack of file descriptors, time to go away.
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+        };
+
+        if connections.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+
+        let connections = &connections;
+        scope.spawn(move || {
+            // An error only affects this connection, which is closed.
+            let _ = serve_connection(stream, peer, config.idle_timeout, handler);
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    })
+}
+
+// Whether an error accepting a connection means the listener itself is unusable, rather than a
+// single connection failing or the process running out of resources for a while.
+fn is_fatal(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::InvalidInput | ErrorKind::Unsupported
+    )
+}
+
+// Serve the queries on a connection until it's closed or idle. The connection is closed once
+// the queries read from it are answered.
+fn serve_connection<H>(
+    stream: TcpStream,
+    peer: SocketAddr,
+    idle_timeout: Duration,
+    handler: &H,
+) -> io::Result<()>
+where
+    H: Fn(&[u8], SocketAddr) -> Option<Vec<u8>> + Sync,
+{
+    stream.set_nodelay(true)?;
+
+    // Responses are written whole, one at a time.
+    let writer = Mutex::new(stream.try_clone()?);
+    let in_flight = AtomicUsize::new(0);
+    let respond = |query: Vec<u8>| {
+        if let Some(response) = handler(&query, peer) {
+            // A failed write closes the connection for the reader as well.
+            let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+            if write_frame(&mut *writer, &response).is_err() {
+                let _ = writer.shutdown(Shutdown::Both);
+            }
+        }
+    };
+
+    std::thread::scope(|scope| {
+        while let Some(query) = read_frame(&mut DeadlineReader::new(&stream, idle_timeout))? {
+            if in_flight.fetch_add(1, Ordering::SeqCst) >= MAX_PIPELINED_QUERIES {
+                respond(query);
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+
+            let (respond, in_flight) = (&respond, &in_flight);
+            scope.spawn(move || {
+                respond(query);
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+
+        Ok(())
+    })
+}
+
+// Reads from a stream until a deadline. A timeout on each read would let a client that sends a
+// byte now and then keep a connection open forever.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> DeadlineReader<'a> {
+    fn new(stream: &'a TcpStream, timeout: Duration) -> Self {
+        Self {
+            stream,
+            deadline: Instant::now() + timeout,
+        }
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sections::QType;
+    use std::thread;
+
+    fn start_server(config: ServerConfig) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            serve(&listener, &config, &|query: &[u8], _| {
+                let query = Message::try_from(query).ok()?;
+                query.response().build().into_bytes().ok()
+            })
+        });
+
+        address
+    }
+
+    #[test]
+    fn test_framing() {
+        let mut bytes = vec![];
+        write_frame(&mut bytes, &[1, 2, 3]).unwrap();
+        write_frame(&mut bytes, &[]).unwrap();
+        assert_eq!(bytes, [0, 3, 1, 2, 3, 0, 0]);
+
+        let mut reader = bytes.as_slice();
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(vec![]));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        // A message cut short is an error.
+        let error = read_frame(&mut [0, 3, 1].as_slice()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+        let error = write_frame(&mut vec![], &vec![0; 65536]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_pipelined_queries() {
+        let address = start_server(ServerConfig::default());
+        let mut connection = Connection::connect(address, Duration::from_secs(5)).unwrap();
+
+        let query = |id| {
+            Message::query("orangetux.nl".parse().unwrap(), QType::A)
+                .id(id)
+                .build()
+        };
+        connection.send(query(1)).unwrap();
+        connection.send(query(2)).unwrap();
+
+        // The response to the first query is kept while waiting for the second.
+        assert_eq!(connection.receive(2).unwrap().header.id, 2);
+        assert_eq!(connection.receive(1).unwrap().header.id, 1);
+        assert_eq!(connection.query(query(3)).unwrap().header.id, 3);
+    }
+
+    #[test]
+    fn test_pipelined_queries_are_handled_concurrently() {
+        // Queries for "slow.example" take a while to answer.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            serve(&listener, &ServerConfig::default(), &|query: &[u8], _| {
+                let query = Message::try_from(query).ok()?;
+                if query.questions[0].qname == "slow.example".parse().unwrap() {
+                    thread::sleep(Duration::from_secs(2));
+                }
+                query.response().build().into_bytes().ok()
+            })
+        });
+
+        let mut connection = Connection::connect(address, Duration::from_secs(5)).unwrap();
+        let query = |name: &str, id| {
+            Message::query(name.parse().unwrap(), QType::A)
+                .id(id)
+                .build()
+        };
+        connection.send(query("slow.example", 1)).unwrap();
+        connection.send(query("fast.example", 2)).unwrap();
+
+        // The response to the second query arrives first.
+        let mut stream = connection.stream.try_clone().unwrap();
+        let first = Message::try_from(read_frame(&mut stream).unwrap().unwrap().as_slice());
+        assert_eq!(first.unwrap().header.id, 2);
+        assert_eq!(connection.receive(1).unwrap().header.id, 1);
+    }
+
+    #[test]
+    fn test_accept_errors_dont_wait_for_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = ServerConfig {
+            idle_timeout: Duration::from_secs(60),
+            ..ServerConfig::default()
+        };
+
+        // The second accept fails like it does when the process is out of file descriptors.
+        let mut accepted = 0;
+        let accept = move || {
+            accepted += 1;
+            match accepted {
+                2 => Err(io::Error::from_raw_os_error(24)),
+                _ => listener.accept(),
+            }
+        };
+        thread::spawn(move || {
+            serve_accepted(accept, &config, &|query: &[u8], _| {
+                let query = Message::try_from(query).ok()?;
+                query.response().build().into_bytes().ok()
+            })
+        });
+
+        // An idle connection stays open, while another one is served.
+        let _idle = TcpStream::connect(address).unwrap();
+        let mut connection = Connection::connect(address, Duration::from_secs(5)).unwrap();
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        assert!(connection.query(query).is_ok());
+
+        // Fatal errors stop the server.
+        let accept = || Err(io::Error::from(ErrorKind::InvalidInput));
+        let error = serve_accepted(accept, &ServerConfig::default(), &|_: &[u8], _| None);
+        assert_eq!(error.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_server_closes_idle_and_excess_connections() {
+        let address = start_server(ServerConfig {
+            idle_timeout: Duration::from_millis(100),
+            max_connections: 1,
+        });
+
+        let mut first = TcpStream::connect(address).unwrap();
+        first
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // Make sure the server is serving the first connection before opening the second.
+        let query = Message::query("orangetux.nl".parse().unwrap(), QType::A).build();
+        write_frame(&mut first, &query.into_bytes().unwrap()).unwrap();
+        assert!(read_frame(&mut first).unwrap().is_some());
+
+        let mut second = TcpStream::connect(address).unwrap();
+        second
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!(read_frame(&mut second).unwrap(), None);
+
+        // Without further queries, the first connection is closed after the idle timeout.
+        assert_eq!(read_frame(&mut first).unwrap(), None);
+    }
+
+    #[test]
+    fn test_server_closes_connections_sending_slowly() {
+        let address = start_server(ServerConfig {
+            idle_timeout: Duration::from_millis(300),
+            max_connections: 1,
+        });
+
+        // Announce a message of 100 bytes, then send a byte every 50 milliseconds. Writing
+        // fails once the server has closed the connection.
+        let mut stream = TcpStream::connect(address).unwrap();
+        let start = Instant::now();
+        let mut written = stream.write_all(&[0, 100]);
+        while written.is_ok() && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(50));
+            written = stream.write_all(&[0]);
+        }
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,528 @@
+//! An async client and server on [`Tokio`], behind the `tokio` feature.
+//!
+//! They speak the same protocol as their blocking counterparts: the client asks over UDP and
+//! retries over TCP when the response is truncated, and the server answers over both, using a
+//! `RequestHandler`.
+//!
+//! [`Tokio`]: https://tokio.rs
+use crate::cookie::ServerCookies;
+use crate::edns::DEFAULT_UDP_PAYLOAD_SIZE;
+use crate::header::Type;
+use crate::message::Message;
+use crate::sections::Question;
+use crate::server::{self, RequestHandler, Settings, Transport};
+use crate::tcp::ServerConfig;
+use ::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use ::tokio::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use ::tokio::sync::{watch, Mutex, OwnedSemaphorePermit, Semaphore};
+use ::tokio::time::timeout;
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long `Client::query()` waits for a response by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many requests a `Server` handles at the same time by default.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 1024;
+
+/// A client asking a single server.
+///
+/// ```no_run
+/// use dns::sections::{QClass, QType, Question};
+/// use dns::tokio::Client;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let client = Client::new("198.41.0.4:53".parse().unwrap());
+/// let response = client
+///     .query(Question {
+///         qname: "orangetux.nl".parse().unwrap(),
+///         qtype: QType::A,
+///         qclass: QClass::IN,
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Client {
+    server: SocketAddr,
+    timeout: Duration,
+}
+
+impl Client {
+    /// Create a client asking the server at `server`.
+    pub fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set how long to wait for a response, over UDP and over TCP each.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Ask the server `question`, with recursion desired. The query is sent over UDP; if the
+    /// response is truncated, it's sent again over TCP.
+    ///
+    /// Fails with `ErrorKind::TimedOut` if the server doesn't respond in time.
+    pub async fn query(&self, question: Question) -> io::Result<Message> {
+        let mut query = Message::query(question.qname.clone(), question.qtype.clone()).build();
+        query.questions = vec![question];
+
+        let response = self.with_timeout(self.query_over_udp(&query)).await?;
+        if !response.header.truncated {
+            return Ok(response);
+        }
+
+        self.with_timeout(self.query_over_tcp(query)).await
+    }
+
+    async fn with_timeout<T>(&self, future: impl Future<Output = io::Result<T>>) -> io::Result<T> {
+        timeout(self.timeout, future)
+            .await
+            .map_err(|_| io::Error::new(ErrorKind::TimedOut, "server didn't respond in time"))?
+    }
+
+    async fn query_over_udp(&self, query: &Message) -> io::Result<Message> {
+        let local = match self.server {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(self.server).await?;
+        let bytes = query
+            .clone()
+            .into_bytes()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+        socket.send(&bytes).await?;
+
+        let mut buf = vec![0; DEFAULT_UDP_PAYLOAD_SIZE.into()];
+        loop {
+            let length = socket.recv(&mut buf).await?;
+
+            // Skip packets that aren't the response, like responses to earlier queries or
+            // spoofed ones.
+            match Message::try_from(&buf[..length]) {
+                Ok(response) if is_response_to(&response, query) => return Ok(response),
+                _ => continue,
+            }
+        }
+    }
+
+    async fn query_over_tcp(&self, query: Message) -> io::Result<Message> {
+        let mut stream = TcpStream::connect(self.server).await?;
+        stream.set_nodelay(true)?;
+
+        let id = query.header.id;
+        let bytes = query
+            .into_bytes()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+        write_frame(&mut stream, &bytes).await?;
+
+        loop {
+            let frame = read_frame(&mut stream).await?.ok_or_else(|| {
+                io::Error::new(ErrorKind::UnexpectedEof, "server closed the connection")
+            })?;
+            let response = Message::try_from(frame.as_slice())
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+
+            if response.header.id == id {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+fn is_response_to(response: &Message, query: &Message) -> bool {
+    response.header.id == query.header.id
+        && response.header.message_type == Type::Reply
+        && response.questions == query.questions
+}
+
+/// A server listening on the same address over UDP and TCP, like `server::Server`.
+///
+/// Requests are handled concurrently, up to a maximum number in flight. Further packets and
+/// queries wait until a request has been answered. As the handler may block, it runs on the
+/// blocking threads of Tokio.
+///
+/// ```no_run
+/// use dns::server::Request;
+/// use dns::tokio::Server;
+/// use dns::Message;
+/// use std::time::Duration;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> std::io::Result<()> {
+/// let server = Server::bind("127.0.0.1:5353", |request: &Request| -> Message {
+///     request.message.response().build()
+/// })
+/// .await?;
+///
+/// // Serve for an hour, then finish the requests in flight.
+/// server
+///     .run_until(tokio::time::sleep(Duration::from_secs(3600)))
+///     .await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Server<H> {
+    handler: H,
+    udp: UdpSocket,
+    tcp: TcpListener,
+    tcp_config: ServerConfig,
+    settings: Settings,
+    max_in_flight: usize,
+}
+
+impl<H: RequestHandler + Send + 'static> Server<H> {
+    /// Bind a UDP socket and a TCP listener to `address`, handling requests with `handler`.
+    ///
+    /// With port 0, the listener takes the port the operating system picked for the socket.
+    pub async fn bind(address: impl ToSocketAddrs, handler: H) -> io::Result<Self> {
+        let udp = UdpSocket::bind(address).await?;
+        let tcp = TcpListener::bind(udp.local_addr()?).await?;
+
+        Ok(Self {
+            handler,
+            udp,
+            tcp,
+            tcp_config: ServerConfig::default(),
+            settings: Settings::default(),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+        })
+    }
+
+    /// Set the idle timeout and connection limit of TCP connections.
+    pub fn tcp_config(mut self, config: ServerConfig) -> Self {
+        self.tcp_config = config;
+        self
+    }
+
+    /// Set the size of the largest UDP message the server receives or sends. Responses are
+    /// truncated to the smallest of this size and the size the client accepts.
+    pub fn udp_payload_size(mut self, udp_payload_size: u16) -> Self {
+        self.settings.udp_payload_size = udp_payload_size;
+        self
+    }
+
+    /// Generate and check server cookies with `cookies`, see `server::Server::cookies()`.
+    pub fn cookies(mut self, cookies: ServerCookies) -> Self {
+        self.settings.cookies = Some(cookies);
+        self
+    }
+
+    /// Set whether requests over UDP holding a client cookie must hold a valid server cookie,
+    /// see `server::Server::require_valid_cookies()`.
+    pub fn require_valid_cookies(mut self, require_valid_cookies: bool) -> Self {
+        self.settings.require_valid_cookies = require_valid_cookies;
+        self
+    }
+
+    /// Set how many requests are handled at the same time, over UDP and TCP together.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.udp.local_addr()
+    }
+
+    /// Serve requests until `shutdown` completes. Then stop receiving requests, wait until the
+    /// requests in flight have been answered and return.
+    ///
+    /// Failing to receive a packet or to accept a connection doesn't stop the server.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) {
+        let max_in_flight = permits(self.max_in_flight);
+        let max_connections = permits(self.tcp_config.max_connections);
+        let shared = Arc::new(Shared {
+            handler: self.handler,
+            settings: self.settings,
+            idle_timeout: self.tcp_config.idle_timeout,
+            in_flight: Arc::new(Semaphore::new(max_in_flight as usize)),
+            connections: Arc::new(Semaphore::new(max_connections as usize)),
+        });
+
+        let (stop, stopped) = watch::channel(false);
+        let udp = ::tokio::spawn(serve_udp(self.udp, shared.clone(), stopped.clone()));
+        let tcp = ::tokio::spawn(serve_tcp(self.tcp, shared.clone(), stopped));
+
+        shutdown.await;
+        let _ = stop.send(true);
+        let _ = udp.await;
+        let _ = tcp.await;
+
+        // Requests and connections hold a permit until they're done.
+        let _ = shared.connections.acquire_many(max_connections).await;
+        let _ = shared.in_flight.acquire_many(max_in_flight).await;
+    }
+}
+
+// A number of permits a semaphore can hand out at once.
+fn permits(count: usize) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+// The state shared by the tasks of a server.
+struct Shared<H> {
+    handler: H,
+    settings: Settings,
+    idle_timeout: Duration,
+    in_flight: Arc<Semaphore>,
+    connections: Arc<Semaphore>,
+}
+
+impl<H: RequestHandler + Send + 'static> Shared<H> {
+    // Wait until another request may be handled, or until the server stops.
+    async fn admit(&self, stopped: &mut watch::Receiver<bool>) -> Option<OwnedSemaphorePermit> {
+        ::tokio::select! {
+            _ = stopped.wait_for(|stopped| *stopped) => None,
+            permit = self.in_flight.clone().acquire_owned() => permit.ok(),
+        }
+    }
+
+    // Return the bytes to respond to `query` with, if any.
+    async fn respond(
+        self: Arc<Self>,
+        query: Vec<u8>,
+        source: SocketAddr,
+        transport: Transport,
+    ) -> Option<Vec<u8>> {
+        ::tokio::task::spawn_blocking(move || {
+            let handler = &self.handler;
+            server::respond(handler, &query, source, transport, &self.settings)
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+async fn serve_udp<H: RequestHandler + Send + 'static>(
+    socket: UdpSocket,
+    shared: Arc<Shared<H>>,
+    mut stopped: watch::Receiver<bool>,
+) {
+    let socket = Arc::new(socket);
+    let mut buf = vec![0; shared.settings.udp_payload_size.into()];
+
+    while let Some(permit) = shared.admit(&mut stopped).await {
+        let received = ::tokio::select! {
+            _ = stopped.wait_for(|stopped| *stopped) => return,
+            received = socket.recv_from(&mut buf) => received,
+        };
+        // Errors concern a single packet, like an ICMP error in response to an earlier packet
+        // that some platforms report.
+        let Ok((length, source)) = received else {
+            continue;
+        };
+
+        let query = buf[..length].to_vec();
+        let (socket, shared) = (socket.clone(), shared.clone());
+        ::tokio::spawn(async move {
+            if let Some(response) = shared.respond(query, source, Transport::Udp).await {
+                // The client is gone or unreachable; there's nobody to tell.
+                let _ = socket.send_to(&response, source).await;
+            }
+            drop(permit);
+        });
+    }
+}
+
+async fn serve_tcp<H: RequestHandler + Send + 'static>(
+    listener: TcpListener,
+    shared: Arc<Shared<H>>,
+    mut stopped: watch::Receiver<bool>,
+) {
+    loop {
+        let accepted = ::tokio::select! {
+            _ = stopped.wait_for(|stopped| *stopped) => return,
+            accepted = listener.accept() => accepted,
+        };
+        let (stream, source) = match accepted {
+            Ok(connection) => connection,
+            Err(_) => {
+                // Give the cause, like a lack of file descriptors, time to go away.
+                ::tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        // Connections beyond the limit are closed right away.
+        let Ok(permit) = shared.connections.clone().try_acquire_owned() else {
+            continue;
+        };
+        ::tokio::spawn(serve_connection(
+            stream,
+            source,
+            shared.clone(),
+            stopped.clone(),
+            permit,
+        ));
+    }
+}
+
+// Serve the queries on a connection until it's closed, idle or the server stops. Queries are
+// handled concurrently and answered in the order they're handled.
+async fn serve_connection<H: RequestHandler + Send + 'static>(
+    stream: TcpStream,
+    source: SocketAddr,
+    shared: Arc<Shared<H>>,
+    mut stopped: watch::Receiver<bool>,
+    _permit: OwnedSemaphorePermit,
+) {
+    let _ = stream.set_nodelay(true);
+    let (mut reader, writer) = stream.into_split();
+    let writer = Arc::new(Mutex::new(writer));
+
+    loop {
+        let query = ::tokio::select! {
+            _ = stopped.wait_for(|stopped| *stopped) => return,
+            query = timeout(shared.idle_timeout, read_frame(&mut reader)) => query,
+        };
+        let Ok(Ok(Some(query))) = query else {
+            return;
+        };
+        let Some(permit) = shared.admit(&mut stopped).await else {
+            return;
+        };
+
+        let (writer, shared) = (writer.clone(), shared.clone());
+        ::tokio::spawn(async move {
+            if let Some(response) = shared.respond(query, source, Transport::Tcp).await {
+                let _ = write_frame(&mut *writer.lock().await, &response).await;
+            }
+            drop(permit);
+        });
+    }
+}
+
+// Read a length-prefixed message, like `tcp::read_frame()`.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    match reader.read_exact(&mut length).await {
+        Ok(_) => (),
+        Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    let mut message = vec![0; u16::from_be_bytes(length).into()];
+    reader.read_exact(&mut message).await?;
+
+    Ok(Some(message))
+}
+
+// Write a message prefixed with its length, like `tcp::write_frame()`.
+async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), message: &[u8]) -> io::Result<()> {
+    let length = u16::try_from(message.len()).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "message is longer than 65535 bytes",
+        )
+    })?;
+
+    writer
+        .write_all(&[length.to_be_bytes().as_slice(), message].concat())
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::RData;
+    use crate::resource_record::{Class, ResourceRecord};
+    use crate::sections::{QClass, QType};
+    use crate::server::Request;
+    use ::tokio::sync::oneshot;
+
+    fn question(name: &str) -> Question {
+        Question {
+            qname: name.parse().unwrap(),
+            qtype: QType::A,
+            qclass: QClass::IN,
+        }
+    }
+
+    // Answer with 100 records, which don't fit in a UDP packet, for `large.example`. Take a
+    // while for `slow.example`.
+    fn handle(request: &Request) -> Message {
+        let qname = &request.message.questions[0].qname;
+        let count = if *qname == "large.example".parse().unwrap() {
+            100
+        } else {
+            1
+        };
+        if *qname == "slow.example".parse().unwrap() {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+
+        let mut response = request.message.response();
+        for _ in 0..count {
+            response = response.answer(ResourceRecord {
+                name: qname.clone(),
+                class: Class::IN,
+                ttl: 300,
+                rdata: RData::A([192, 0, 2, 1].into()),
+            });
+        }
+
+        response.build()
+    }
+
+    async fn start_server() -> (
+        SocketAddr,
+        oneshot::Sender<()>,
+        ::tokio::task::JoinHandle<()>,
+    ) {
+        let server = Server::bind("127.0.0.1:0", handle)
+            .await
+            .unwrap()
+            .udp_payload_size(512);
+        let address = server.local_addr().unwrap();
+        let (shutdown, stop) = oneshot::channel();
+        let running = ::tokio::spawn(server.run_until(async {
+            stop.await.ok();
+        }));
+
+        (address, shutdown, running)
+    }
+
+    #[::tokio::test]
+    async fn test_query_falls_back_to_tcp() {
+        let (address, _shutdown, _running) = start_server().await;
+        let client = Client::new(address);
+
+        let response = client.query(question("small.example")).await.unwrap();
+        assert_eq!(response.answers.len(), 1);
+
+        let response = client.query(question("large.example")).await.unwrap();
+        assert!(!response.header.truncated);
+        assert_eq!(response.answers.len(), 100);
+    }
+
+    #[::tokio::test]
+    async fn test_shutdown_finishes_requests_in_flight() {
+        let (address, shutdown, running) = start_server().await;
+        let client = Client::new(address);
+
+        let query = ::tokio::spawn(async move { client.query(question("slow.example")).await });
+        ::tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown.send(()).unwrap();
+
+        assert_eq!(query.await.unwrap().unwrap().answers.len(), 1);
+        running.await.unwrap();
+
+        // The server no longer answers.
+        let client = Client::new(address).timeout(Duration::from_millis(100));
+        assert!(client.query(question("small.example")).await.is_err());
+    }
+}
@@ -0,0 +1,479 @@
+//! Answer queries authoritatively from the records of a zone.
+//!
+//! A `Zone` holds the records of the names at and below its origin. It looks up the records of
+//! a name, follows CNAME records within the zone and redirects names below DNAME records, see
+//! [`RFC 6672`]. Wildcards and delegations to child zones are not supported.
+//!
+//! A `Zone` is a `RequestHandler`, so it can be served by a `Server`:
+//!
+//! ```no_run
+//! use dns::rdata::RData;
+//! use dns::resource_record::{Class, ResourceRecord, Type};
+//! use dns::server::Server;
+//! use dns::zone::Zone;
+//!
+//! let mut zone = Zone::new("example.com".parse().unwrap());
+//! zone.insert(ResourceRecord {
+//!     name: "example.com".parse().unwrap(),
+//!     class: Class::IN,
+//!     ttl: 3600,
+//!     rdata: RData::from_presentation(
+//!         &Type::SOA,
+//!         "ns.example.com. hostmaster.example.com. 1 7200 3600 1209600 300",
+//!     )
+//!     .unwrap(),
+//! });
+//!
+//! Server::bind("127.0.0.1:5353", zone).unwrap().run();
+//! ```
+//!
+//! [`RFC 6672`]: https://www.rfc-editor.org/rfc/rfc6672
+use crate::domain_name::DomainName;
+use crate::edns;
+use crate::header::ResponseCode;
+use crate::message::Message;
+use crate::rdata::RData;
+use crate::resource_record::{ResourceRecord, Type};
+use crate::sections::{QClass, QType};
+use crate::server::{Request, RequestHandler};
+use std::collections::BTreeMap;
+
+// The number of CNAME and DNAME records a lookup follows, which stops it from looping forever.
+const MAX_ALIASES: usize = 16;
+
+/// The records of a zone.
+#[derive(Clone, Debug, Default)]
+pub struct Zone {
+    origin: DomainName,
+    records: BTreeMap<DomainName, Vec<ResourceRecord>>,
+}
+
+/// The outcome of a lookup in a `Zone`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Answer {
+    /// The response code: NOERROR, NXDOMAIN if the name doesn't exist, YXDOMAIN if a DNAME
+    /// record would redirect the name to a name that's too long, or SERVFAIL if the lookup
+    /// follows too many aliases.
+    pub rcode: ResponseCode,
+
+    /// The records for the answer section, including the aliases leading to them.
+    pub answers: Vec<ResourceRecord>,
+
+    /// The records for the authority section: the SOA record of the zone if there are no
+    /// records of the requested type.
+    pub authority: Vec<ResourceRecord>,
+}
+
+impl Zone {
+    /// Create an empty zone for the names at and below `origin`.
+    pub fn new(origin: DomainName) -> Self {
+        Self {
+            origin,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// The name at the top of the zone.
+    pub fn origin(&self) -> &DomainName {
+        &self.origin
+    }
+
+    /// Add `record` to the zone. Returns false, and ignores the record, if its name isn't in
+    /// the zone.
+    pub fn insert(&mut self, record: ResourceRecord) -> bool {
+        if !record.name.is_subdomain_of(&self.origin) {
+            return false;
+        }
+
+        self.records
+            .entry(record.name.clone())
+            .or_default()
+            .push(record);
+        true
+    }
+
+    /// Look up the records of `qtype` and `qclass` for `qname`, as described by section 4.3.2 of
+    /// RFC 1034 and section 3.2 of RFC 6672. Records of other classes are ignored.
+    ///
+    /// A name below the owner of a DNAME record is redirected: its suffix is replaced by the
+    /// target of the DNAME record. The answer holds the DNAME record, followed by a CNAME record
+    /// from the name to the new name. The lookup then continues with the new name, as it does
+    /// with the target of a CNAME record. It stops at names outside the zone, which the client
+    /// must look up elsewhere.
+    ///
+    /// A CNAME record at the name is followed unless it matches `qtype`: for CNAME and ANY
+    /// queries, the CNAME record itself is the answer.
+    pub fn lookup(&self, qname: &DomainName, qtype: &QType, qclass: &QClass) -> Answer {
+        let mut answer = Answer {
+            rcode: ResponseCode::NoError,
+            answers: vec![],
+            authority: vec![],
+        };
+
+        let mut qname = qname.clone();
+        for _ in 0..=MAX_ALIASES {
+            if !qname.is_subdomain_of(&self.origin) {
+                return answer;
+            }
+
+            if let Some(dname) = self.dname_above(&qname, qclass) {
+                answer.answers.push(dname.clone());
+                let RData::DNAME(target) = &dname.rdata else {
+                    unreachable!("dname_above() only returns DNAME records");
+                };
+
+                // The new name must not exceed 255 octets, see section 2.2 of RFC 6672.
+                let Some(name) = substitute(&qname, &dname.name, target) else {
+                    answer.rcode = ResponseCode::YXDomain;
+                    return answer;
+                };
+                answer.answers.push(ResourceRecord {
+                    name: qname,
+                    class: dname.class.clone(),
+                    ttl: dname.ttl,
+                    rdata: RData::CNAME(name.clone()),
+                });
+                qname = name;
+                continue;
+            }
+
+            let records: Vec<_> = self
+                .records
+                .get(&qname)
+                .into_iter()
+                .flatten()
+                .filter(|record| in_class(record, qclass))
+                .collect();
+            if *qtype != QType::CNAME && *qtype != QType::Any {
+                let cname = records.iter().find_map(|record| match &record.rdata {
+                    RData::CNAME(target) => Some((record, target)),
+                    _ => None,
+                });
+                if let Some((cname, target)) = cname {
+                    answer.answers.push((*cname).clone());
+                    qname = target.clone();
+                    continue;
+                }
+            }
+
+            let before = answer.answers.len();
+            answer.answers.extend(
+                records
+                    .into_iter()
+                    .filter(|record| {
+                        *qtype == QType::Any || u16::from(qtype.clone()) == record.r#type().into()
+                    })
+                    .cloned(),
+            );
+            if answer.answers.len() == before {
+                if !self.exists(&qname) {
+                    answer.rcode = ResponseCode::NameError;
+                }
+                answer.authority.extend(self.negative_soa(qclass));
+            }
+            return answer;
+        }
+
+        answer.rcode = ResponseCode::ServerFailure;
+        answer
+    }
+
+    // The DNAME record of the ancestor of `name` closest to the origin. Names below a DNAME
+    // record are occluded by it, see section 2.4 of RFC 6672. A DNAME record doesn't redirect
+    // its owner itself.
+    fn dname_above(&self, name: &DomainName, qclass: &QClass) -> Option<&ResourceRecord> {
+        let depth = name.labels().len() - self.origin.labels().len();
+        (1..=depth)
+            .rev()
+            .map(|skip| DomainName::from_labels(name.labels()[skip..].to_vec()).unwrap())
+            .filter_map(|ancestor| self.records.get(&ancestor))
+            .flat_map(|records| records.iter())
+            .find(|record| record.r#type() == Type::DNAME && in_class(record, qclass))
+    }
+
+    // Whether `name` exists: it has records, or a name below it has. Names below `name` come
+    // right after it in canonical order.
+    fn exists(&self, name: &DomainName) -> bool {
+        self.records
+            .range(name..)
+            .next()
+            .is_some_and(|(other, _)| other.is_subdomain_of(name))
+    }
+
+    // The SOA record of the zone, for answers without records. Its TTL is the TTL of negative
+    // answers, see section 3 of RFC 2308.
+    fn negative_soa(&self, qclass: &QClass) -> Option<ResourceRecord> {
+        let soa = self
+            .records
+            .get(&self.origin)?
+            .iter()
+            .find(|record| record.r#type() == Type::SOA && in_class(record, qclass))?;
+        let RData::SOA { minimum, .. } = soa.rdata else {
+            return None;
+        };
+
+        Some(ResourceRecord {
+            ttl: soa.ttl.min(minimum),
+            ..soa.clone()
+        })
+    }
+}
+
+impl RequestHandler for Zone {
+    // Answers the first question. Questions about names outside the zone are refused.
+    fn handle(&self, request: &Request) -> Message {
+        let message = &request.message;
+        let response = message.response();
+        if message
+            .edns
+            .as_ref()
+            .is_some_and(|edns| edns.version > edns::VERSION)
+        {
+            return response.build();
+        }
+
+        let question = match message.questions.first() {
+            Some(question) => question,
+            None => return response.rcode(ResponseCode::FormatError).build(),
+        };
+        if !question.qname.is_subdomain_of(&self.origin) {
+            return response.rcode(ResponseCode::Refused).build();
+        }
+
+        let answer = self.lookup(&question.qname, &question.qtype, &question.qclass);
+        let mut response = response.authoritative(true).rcode(answer.rcode);
+        for record in answer.answers {
+            response = response.answer(record);
+        }
+        for record in answer.authority {
+            response = response.authority(record);
+        }
+
+        response.build()
+    }
+}
+
+// Whether `record` is of `qclass`.
+fn in_class(record: &ResourceRecord, qclass: &QClass) -> bool {
+    *qclass == QClass::Any || u16::from(qclass.clone()) == record.class.clone().into()
+}
+
+// Replace the suffix `owner` of `name` by `target`. `None` if the new name is longer than 255
+// octets.
+fn substitute(name: &DomainName, owner: &DomainName, target: &DomainName) -> Option<DomainName> {
+    let prefix = name.labels().len() - owner.labels().len();
+    let mut labels = name.labels()[..prefix].to_vec();
+    labels.extend_from_slice(target.labels());
+
+    DomainName::from_labels(labels).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resource_record::Class;
+    use crate::server::Transport;
+
+    // A zone for example.com with `records` and an SOA record.
+    fn example_com(records: &[(&str, Type, &str)]) -> Zone {
+        let mut zone = Zone::new("example.com".parse().unwrap());
+        let soa = (
+            "example.com",
+            Type::SOA,
+            "ns.example.com. hostmaster.example.com. 1 7200 3600 1209600 300",
+        );
+        for (name, r#type, value) in [soa].iter().chain(records) {
+            assert!(zone.insert(record(name, r#type, value)));
+        }
+
+        zone
+    }
+
+    fn record(name: &str, r#type: &Type, value: &str) -> ResourceRecord {
+        ResourceRecord {
+            name: name.parse().unwrap(),
+            class: Class::IN,
+            ttl: 3600,
+            rdata: RData::from_presentation(r#type, value).unwrap(),
+        }
+    }
+
+    fn lookup(zone: &Zone, name: &str, qtype: QType) -> Answer {
+        zone.lookup(&name.parse().unwrap(), &qtype, &QClass::IN)
+    }
+
+    #[test]
+    fn test_lookup() {
+        let zone = example_com(&[
+            ("www.example.com", Type::A, "192.0.2.1"),
+            ("www.example.com", Type::AAAA, "2001:db8::1"),
+            ("alias.example.com", Type::CNAME, "www.example.com."),
+            ("elsewhere.example.com", Type::CNAME, "www.example.org."),
+            ("a.b.example.com", Type::A, "192.0.2.2"),
+        ]);
+
+        let answer = lookup(&zone, "alias.example.com", QType::A);
+        assert_eq!(answer.rcode, ResponseCode::NoError);
+        assert_eq!(
+            answer.answers,
+            [
+                record("alias.example.com", &Type::CNAME, "www.example.com."),
+                record("www.example.com", &Type::A, "192.0.2.1"),
+            ]
+        );
+        assert!(answer.authority.is_empty());
+
+        // The client follows aliases out of the zone.
+        let answer = lookup(&zone, "elsewhere.example.com", QType::A);
+        assert_eq!(answer.answers.len(), 1);
+
+        // Names without records of the type, and names without records at all.
+        let answer = lookup(&zone, "www.example.com", QType::MX);
+        assert_eq!(answer.rcode, ResponseCode::NoError);
+        assert!(answer.answers.is_empty());
+        assert_eq!(answer.authority[0].ttl, 300);
+
+        let answer = lookup(&zone, "b.example.com", QType::A);
+        assert_eq!(answer.rcode, ResponseCode::NoError);
+
+        let answer = lookup(&zone, "nope.example.com", QType::A);
+        assert_eq!(answer.rcode, ResponseCode::NameError);
+        assert_eq!(answer.authority.len(), 1);
+
+        // Aliases that loop.
+        let zone = example_com(&[
+            ("a.example.com", Type::CNAME, "b.example.com."),
+            ("b.example.com", Type::CNAME, "a.example.com."),
+        ]);
+        let answer = lookup(&zone, "a.example.com", QType::A);
+        assert_eq!(answer.rcode, ResponseCode::ServerFailure);
+    }
+
+    #[test]
+    fn test_lookup_doesnt_follow_aliases_for_any_queries() {
+        let zone = example_com(&[
+            ("alias.example.com", Type::CNAME, "www.example.com."),
+            ("www.example.com", Type::A, "192.0.2.1"),
+        ]);
+
+        for qtype in [QType::CNAME, QType::Any] {
+            let answer = lookup(&zone, "alias.example.com", qtype);
+            assert_eq!(answer.rcode, ResponseCode::NoError);
+            assert_eq!(
+                answer.answers,
+                [record(
+                    "alias.example.com",
+                    &Type::CNAME,
+                    "www.example.com."
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_filters_on_class() {
+        let mut zone = example_com(&[("www.example.com", Type::A, "192.0.2.1")]);
+        let chaos = ResourceRecord {
+            class: Class::CH,
+            ..record("www.example.com", &Type::TXT, "\"chaos\"")
+        };
+        assert!(zone.insert(chaos.clone()));
+
+        let www = "www.example.com".parse().unwrap();
+        let answer = zone.lookup(&www, &QType::Any, &QClass::IN);
+        assert_eq!(
+            answer.answers,
+            [record("www.example.com", &Type::A, "192.0.2.1")]
+        );
+
+        let answer = zone.lookup(&www, &QType::TXT, &QClass::IN);
+        assert!(answer.answers.is_empty());
+        assert_eq!(answer.authority.len(), 1);
+
+        let answer = zone.lookup(&www, &QType::TXT, &QClass::CH);
+        assert_eq!(answer.answers, [chaos]);
+
+        let answer = zone.lookup(&www, &QType::Any, &QClass::Any);
+        assert_eq!(answer.answers.len(), 2);
+    }
+
+    #[test]
+    fn test_dname_substitution() {
+        let zone = example_com(&[
+            ("old.example.com", Type::DNAME, "new.example.com."),
+            ("old.example.com", Type::A, "192.0.2.1"),
+            ("www.new.example.com", Type::A, "192.0.2.2"),
+            ("moved.example.com", Type::DNAME, "example.org."),
+        ]);
+
+        let answer = lookup(&zone, "www.old.example.com", QType::A);
+        assert_eq!(answer.rcode, ResponseCode::NoError);
+        assert_eq!(
+            answer.answers,
+            [
+                record("old.example.com", &Type::DNAME, "new.example.com."),
+                record("www.old.example.com", &Type::CNAME, "www.new.example.com."),
+                record("www.new.example.com", &Type::A, "192.0.2.2"),
+            ]
+        );
+
+        // The owner itself isn't redirected.
+        let answer = lookup(&zone, "old.example.com", QType::A);
+        assert_eq!(
+            answer.answers,
+            [record("old.example.com", &Type::A, "192.0.2.1")]
+        );
+
+        // Names redirected out of the zone are left to the client.
+        let answer = lookup(&zone, "a.b.moved.example.com", QType::A);
+        assert_eq!(
+            answer.answers,
+            [
+                record("moved.example.com", &Type::DNAME, "example.org."),
+                record("a.b.moved.example.com", &Type::CNAME, "a.b.example.org."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dname_substitution_too_long() {
+        let long = ["a".repeat(63), "b".repeat(63), "c".repeat(63)].join(".");
+        let zone = example_com(&[(
+            "old.example.com",
+            Type::DNAME,
+            &format!("{}.example.org.", long),
+        )]);
+
+        // The target takes 3 * 64 + 13 octets, which leaves 50 for the label in front of it.
+        let name = format!("{}.old.example.com", "x".repeat(49));
+        let answer = lookup(&zone, &name, QType::A);
+        assert_eq!(answer.rcode, ResponseCode::NoError);
+        assert_eq!(answer.answers.len(), 2);
+
+        let name = format!("{}.old.example.com", "x".repeat(50));
+        let answer = lookup(&zone, &name, QType::A);
+        assert_eq!(answer.rcode, ResponseCode::YXDomain);
+        assert_eq!(answer.answers.len(), 1);
+        assert_eq!(answer.answers[0].r#type(), Type::DNAME);
+    }
+
+    #[test]
+    fn test_handle() {
+        let zone = example_com(&[("old.example.com", Type::DNAME, "new.example.com.")]);
+        let request = |qname: &str| Request {
+            message: Message::query(qname.parse().unwrap(), QType::A).build(),
+            source: "127.0.0.1:53".parse().unwrap(),
+            transport: Transport::Udp,
+        };
+
+        let response = zone.handle(&request("www.old.example.com"));
+        assert!(response.header.authoritive_answer);
+        assert_eq!(response.header.r_code, ResponseCode::NameError);
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(response.authority.len(), 1);
+
+        let response = zone.handle(&request("example.org"));
+        assert!(!response.header.authoritive_answer);
+        assert_eq!(response.header.r_code, ResponseCode::Refused);
+    }
+}